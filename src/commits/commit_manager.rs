@@ -109,13 +109,17 @@ impl CommitManager {
     }
 
     /// Public: Add doc through commit manager
+    ///
+    /// The document is checked against the index mappings first; the logged
+    /// copy is the coerced one so replay never has to validate again.
     pub fn add_document(
         &mut self,
         store: &mut DocumentStore,
         data: &HashMap<String, Value>,
         max_depth: Option<usize>,
-    ) -> String {
-        validate_document(&data);
+    ) -> Result<String, String> {
+        let mut data = data.clone();
+        validate_document(&mut data, &mut store.settings)?;
 
         let doc_id = random_id();
 
@@ -125,7 +129,7 @@ impl CommitManager {
         });
         self.append_to_log(&commit);
 
-        store.add_document(&doc_id, data, max_depth);
+        store.add_document(&doc_id, &data, max_depth);
 
         if (commit.id) % 100 == 0 {
             let snapshot = store.to_snapshot();
@@ -134,7 +138,7 @@ impl CommitManager {
 
         self.save_meta();
 
-        doc_id
+        Ok(doc_id)
     }
 
     /// Public: Delete doc through commit manager
@@ -194,7 +198,7 @@ impl CommitManager {
             commits.push(commit);
         }
 
-        *store = DocumentStore::new(None);
+        *store = DocumentStore::with_settings(store.settings.clone());
         for c in commits.into_iter().filter(|c| c.id <= commit_id) {
             match c.op {
                 CommitOp::Add { id, data } => {
@@ -219,10 +223,14 @@ impl CommitManager {
         if let Some(snapshot) = snapshot_opt {
             // restore index structures
             store.normal_index = snapshot.normal_index;
-            store.n_gram_index = snapshot.n_gram_index;
-            store.n_gram_trie = snapshot.n_gram_trie;
             store.value_tree = snapshot.value_tree;
             store.forward_index = snapshot.forward_index;
+            store.keyword_index = snapshot.keyword_index;
+            store.bool_index = snapshot.bool_index;
+            store.geo_index = snapshot.geo_index;
+            store.nested = snapshot.nested;
+            store.completion = snapshot.completion;
+            store.restore_unserialized();
             last_snapshot_commit = snapshot.last_commit_id.parse().unwrap_or(0);
 
             // documents must already be loaded separately from data.json
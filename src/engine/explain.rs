@@ -0,0 +1,195 @@
+use std::fmt;
+
+use crate::index::inverted_index::inverted_index::{CollectionStats, InvertedIndex};
+
+/// How a score was computed: its value, what it stands for, and the values
+/// it was computed from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    pub value: f64,
+    pub description: String,
+    pub details: Vec<Explanation>,
+}
+
+impl Explanation {
+    pub fn new(value: f64, description: impl Into<String>) -> Self {
+        Self {
+            value,
+            description: description.into(),
+            details: Vec::new(),
+        }
+    }
+
+    pub fn with(mut self, detail: Explanation) -> Self {
+        self.details.push(detail);
+        self
+    }
+
+    /// The sum of `details`, described as `description`.
+    pub fn sum(description: impl Into<String>, details: Vec<Explanation>) -> Self {
+        Self {
+            value: details.iter().map(|d| d.value).sum(),
+            description: description.into(),
+            details,
+        }
+    }
+
+    fn write_tree(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        writeln!(
+            f,
+            "{:indent$}{:.4} = {}",
+            "",
+            self.value,
+            self.description,
+            indent = depth * 2
+        )?;
+        for detail in &self.details {
+            detail.write_tree(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// One line per node, children indented under their parent.
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_tree(f, 0)
+    }
+}
+
+/// The BM25 score `term` gives `doc_id`, broken down into idf, term
+/// frequency and length normalisation, or None if the document does not
+/// hold the term.
+pub fn explain_bm25(
+    index: &InvertedIndex,
+    doc_id: &str,
+    term: &str,
+    k1: f64,
+    b: f64,
+    stats: &CollectionStats,
+) -> Option<Explanation> {
+    let posting = index.posting(term, doc_id)?;
+    let df = stats
+        .doc_freqs
+        .get(term)
+        .copied()
+        .unwrap_or_else(|| index.get_postings(term).map_or(0, Iterator::count));
+    let idf = InvertedIndex::bm25_idf(stats.n_docs, df);
+    let tf = posting.term_freq as f64;
+    let doc_len = index.doc_length(doc_id);
+    let avg_doc_len = stats.avg_doc_len();
+    let norm = 1.0 - b + b * doc_len as f64 / avg_doc_len;
+
+    let idf = Explanation::new(idf, "idf, ln(1 + (N - df + 0.5) / (df + 0.5))")
+        .with(Explanation::new(stats.n_docs as f64, "N, documents"))
+        .with(Explanation::new(df as f64, "df, documents with the term"));
+    let tf_part = Explanation::new(
+        tf * (k1 + 1.0) / (tf + k1 * norm),
+        "tf part, tf * (k1 + 1) / (tf + k1 * norm)",
+    )
+    .with(Explanation::new(tf, "tf, occurrences in the document"))
+    .with(Explanation::new(k1, "k1"))
+    .with(
+        Explanation::new(norm, "norm, 1 - b + b * dl / avgdl")
+            .with(Explanation::new(b, "b"))
+            .with(Explanation::new(doc_len as f64, "dl, document length"))
+            .with(Explanation::new(
+                avg_doc_len,
+                "avgdl, average document length",
+            )),
+    );
+
+    Some(
+        Explanation::new(
+            InvertedIndex::bm25_score(tf, doc_len, idf.value, k1, b, avg_doc_len),
+            format!("bm25 of '{}', idf * tf part", term),
+        )
+        .with(idf)
+        .with(tf_part),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::query_service::QueryService;
+    use crate::index::analysis::synonyms::{SearchSynonyms, SynonymConfig};
+    use crate::index::settings::IndexSettings;
+    use crate::test_support::text_store;
+
+    #[test]
+    fn explanations_add_up_to_the_ranked_scores() {
+        let settings = IndexSettings {
+            min_ngram: Some(2),
+            max_ngram: Some(3),
+            search_synonyms: Some(SearchSynonyms {
+                rules: SynonymConfig {
+                    synonyms: vec!["thunder, electric".into()],
+                    ..Default::default()
+                },
+                weight: 0.5,
+            }),
+            ..Default::default()
+        };
+        let store = text_store(
+            settings,
+            "text",
+            [
+                ("1", "pikachu thunder thunder"),
+                ("2", "raichu thunder stone"),
+                ("3", "electric pichu"),
+            ],
+        );
+        let qs = QueryService::new(&store);
+
+        let hits = qs.search("thunder pikachu", 1.2, 0.75, 10);
+        assert_eq!(hits.len(), 3);
+        for (doc_id, score) in &hits {
+            let explanation = qs.explain(doc_id, "thunder pikachu", 1.2, 0.75).unwrap();
+            assert!((explanation.value - score).abs() < 1e-9);
+        }
+        let synonym = &qs.explain("3", "thunder", 1.2, 0.75).unwrap().details[0];
+        assert!(synonym.description.starts_with("synonym 'electric'"));
+        assert_eq!(synonym.value, 0.5 * synonym.details[1].value);
+        assert!(qs.explain("missing", "thunder", 1.2, 0.75).is_none());
+
+        let hits = qs.ngram_bm25("pikachuu", 1.2, 0.75, 0.6, 0.4, 5);
+        assert_eq!(hits[0].0, "1");
+        for (doc_id, score) in &hits {
+            let explanation = qs
+                .explain_ngram_bm25(doc_id, "pikachuu", 1.2, 0.75, 0.6, 0.4, 5)
+                .unwrap();
+            assert!((explanation.value - score).abs() < 1e-9);
+        }
+
+        let printed = qs
+            .explain_ngram_bm25("1", "pikachuu", 1.2, 0.75, 0.6, 0.4, 5)
+            .unwrap()
+            .to_string();
+        assert!(printed.contains("\n  ") && printed.contains("n-gram jaccard"));
+        assert!(printed.contains("idf, ln(1 + (N - df + 0.5) / (df + 0.5))"));
+    }
+
+    #[test]
+    fn ngram_jaccard_is_a_ratio() {
+        let settings = IndexSettings {
+            min_ngram: Some(2),
+            max_ngram: Some(3),
+            ..Default::default()
+        };
+        let store = text_store(settings, "text", [("1", "pikachu")]);
+        let qs = QueryService::new(&store);
+
+        // candidate -> weight -> [alpha, jaccard, beta, similarity]
+        let jaccard = |query: &str| {
+            let explanation = qs
+                .explain_ngram_bm25("1", query, 1.2, 0.75, 0.6, 0.4, 5)
+                .unwrap();
+            explanation.details[0].details[0].details[1].clone()
+        };
+        let exact = jaccard("pikachu");
+        assert!(exact.description.starts_with("n-gram jaccard"));
+        assert_eq!(exact.value, 1.0);
+        let close = jaccard("pikachuu").value;
+        assert!(close > 0.0 && close < 1.0);
+    }
+}
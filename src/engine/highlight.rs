@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How matched words are marked and how field values are cut into fragments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HighlightOptions {
+    pub pre_tag: String,
+    pub post_tag: String,
+    /// Characters per fragment at most, unless a single word is longer.
+    /// Fragments are cut between words; 0 keeps every value whole.
+    pub fragment_size: usize,
+    /// Most fragments returned per field, the best first.
+    pub number_of_fragments: usize,
+    /// Share of n-grams a word needs in common with a query term to count as
+    /// a match, when the index keeps n-grams.
+    pub min_ngram_similarity: f64,
+}
+
+impl Default for HighlightOptions {
+    fn default() -> Self {
+        Self {
+            pre_tag: "<em>".to_string(),
+            post_tag: "</em>".to_string(),
+            fragment_size: 100,
+            number_of_fragments: 5,
+            min_ngram_similarity: 0.6,
+        }
+    }
+}
+
+/// A piece of a field value with its matched words tagged. The score adds up
+/// the best weight of every distinct word matched, so a fragment holding
+/// more of the query ranks above one repeating a single word.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fragment {
+    pub text: String,
+    pub score: f64,
+}
+
+/// The fragments of `text` holding at least one match, in text order.
+/// `matches` gives the weight of a word, or None when it does not match.
+pub fn highlight_text(
+    text: &str,
+    matches: impl Fn(&str) -> Option<f64>,
+    options: &HighlightOptions,
+) -> Vec<Fragment> {
+    let words: Vec<(usize, &str, Option<f64>)> = text
+        .unicode_word_indices()
+        .map(|(start, word)| (start, word, matches(word)))
+        .collect();
+    if words.iter().all(|(_, _, weight)| weight.is_none()) {
+        return Vec::new();
+    }
+
+    // a fragment starts at the word that would take the previous one past
+    // `fragment_size` characters
+    let mut bounds = vec![0];
+    let mut fragment_start = 0;
+    for &(start, word, _) in &words {
+        let length = text[fragment_start..start + word.len()].chars().count();
+        if options.fragment_size > 0 && start > fragment_start && length > options.fragment_size {
+            bounds.push(start);
+            fragment_start = start;
+        }
+    }
+    bounds.push(text.len());
+
+    bounds
+        .windows(2)
+        .filter_map(|range| {
+            let (from, to) = (range[0], range[1]);
+            let mut marked = String::new();
+            let mut copied = from;
+            let mut matched: HashMap<String, f64> = HashMap::new();
+
+            for &(start, word, weight) in &words {
+                let Some(weight) = weight.filter(|_| (from..to).contains(&start)) else {
+                    continue;
+                };
+                marked.push_str(&text[copied..start]);
+                marked.push_str(&options.pre_tag);
+                marked.push_str(word);
+                marked.push_str(&options.post_tag);
+                copied = start + word.len();
+
+                let best = matched.entry(word.to_lowercase()).or_insert(0.0);
+                *best = best.max(weight);
+            }
+            if matched.is_empty() {
+                return None;
+            }
+            marked.push_str(&text[copied..to]);
+
+            Some(Fragment {
+                text: marked.trim().to_string(),
+                score: matched.values().sum(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HighlightOptions, highlight_text};
+    use crate::engine::query_service::QueryService;
+    use crate::index::analysis::synonyms::{SearchSynonyms, SynonymConfig};
+    use crate::index::settings::IndexSettings;
+    use crate::index::value::Value;
+    use crate::test_support::store_with;
+
+    #[test]
+    fn fragments_are_cut_between_words_and_ranked() {
+        let options = HighlightOptions {
+            pre_tag: "[".into(),
+            post_tag: "]".into(),
+            fragment_size: 20,
+            ..Default::default()
+        };
+        let matches = |word: &str| match word {
+            "Pikachu" => Some(1.0),
+            "thunder" => Some(0.5),
+            _ => None,
+        };
+        let fragments = highlight_text(
+            "Pikachu uses thunder. Ash smiles at the crowd. Pikachu naps.",
+            matches,
+            &options,
+        );
+
+        let texts: Vec<&str> = fragments.iter().map(|f| f.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            ["[Pikachu] uses [thunder].", "crowd. [Pikachu] naps."]
+        );
+        assert_eq!(fragments[0].score, 1.5);
+
+        let whole = HighlightOptions {
+            fragment_size: 0,
+            ..Default::default()
+        };
+        let fragments = highlight_text("Ash and Pikachu", matches, &whole);
+        assert_eq!(fragments[0].text, "Ash and <em>Pikachu</em>");
+        assert!(highlight_text("Ash and Misty", matches, &whole).is_empty());
+    }
+
+    #[test]
+    fn highlights_stemmed_synonym_and_ngram_matches() {
+        let settings = IndexSettings {
+            use_stemming: true,
+            min_ngram: Some(2),
+            max_ngram: Some(3),
+            search_synonyms: Some(SearchSynonyms {
+                rules: SynonymConfig {
+                    synonyms: vec!["thunder, electric".into()],
+                    ..Default::default()
+                },
+                weight: 0.5,
+            }),
+            ..Default::default()
+        };
+        let bio = "Pikachu evolves with a thunder stone. Evolution is optional.";
+        let fields = vec![
+            ("bio", Value::Text(bio.into())),
+            ("name", Value::Text("Electric mouse".into())),
+        ];
+        let store = store_with(settings, [("1", fields)]);
+        let qs = QueryService::new(&store);
+        let options = HighlightOptions {
+            fragment_size: 40,
+            number_of_fragments: 1,
+            ..Default::default()
+        };
+
+        // stored text is lowercased on the way in
+        let highlights = qs.highlight("1", "evolving pikachuu thunder", &options);
+        assert_eq!(
+            highlights["bio"],
+            ["<em>pikachu</em> <em>evolves</em> with a <em>thunder</em> stone."]
+        );
+        assert_eq!(highlights["name"], ["<em>electric</em> mouse"]);
+
+        assert!(qs.highlight("1", "bulbasaur", &options).is_empty());
+        assert!(qs.highlight("missing", "pikachu", &options).is_empty());
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    engine::search_engine::SearchEngine,
+    index::{
+        documents_store::DocumentStore, inverted_index::inverted_index::CollectionStats,
+        settings::IndexSettings,
+    },
+    storage::local_store::LocalStore,
+};
+
+/// A single ranked hit from a search that fanned out over several indices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossIndexHit {
+    pub index: String,
+    pub doc_id: String,
+    pub score: f64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RegistryMeta {
+    indices: Vec<String>,
+    aliases: HashMap<String, Vec<String>>,
+}
+
+/**
+ * Owns several named indices living under one root directory and the aliases
+ * pointing at them. Every index keeps its own data file, commit log and
+ * snapshots under `<root>/<name>/`, while `<root>/registry.json` records which
+ * indices exist and what each alias resolves to.
+ */
+pub struct IndexRegistry {
+    root: String,
+    indices: HashMap<String, SearchEngine>,
+    aliases: HashMap<String, Vec<String>>,
+}
+
+impl IndexRegistry {
+    pub fn open(root: impl Into<String>) -> std::io::Result<Self> {
+        let root = root.into();
+        let meta_path = Self::meta_path_for(&root);
+
+        let meta: RegistryMeta = if LocalStore::exists(&meta_path) {
+            LocalStore::load(&meta_path)?
+        } else {
+            RegistryMeta::default()
+        };
+
+        let mut indices = HashMap::new();
+        for name in &meta.indices {
+            let engine = Self::open_engine(&root, name, None)?;
+            indices.insert(name.clone(), engine);
+        }
+
+        Ok(Self {
+            root,
+            indices,
+            aliases: meta.aliases,
+        })
+    }
+
+    fn meta_path_for(root: &str) -> String {
+        format!("{}/registry.json", root)
+    }
+
+    fn open_engine(
+        root: &str,
+        name: &str,
+        settings: Option<IndexSettings>,
+    ) -> std::io::Result<SearchEngine> {
+        let base = Path::new(root).join(name);
+        let path = |rel: &str| base.join(rel).to_string_lossy().to_string();
+
+        SearchEngine::open(
+            path("data.json"),
+            path("commit_logs/commits.log"),
+            path("snapshots"),
+            settings,
+        )
+    }
+
+    fn save_meta(&self) -> std::io::Result<()> {
+        let mut indices: Vec<String> = self.indices.keys().cloned().collect();
+        indices.sort();
+
+        let meta = RegistryMeta {
+            indices,
+            aliases: self.aliases.clone(),
+        };
+        LocalStore::save(&meta, &Self::meta_path_for(&self.root))
+    }
+
+    /// Index and alias names become directory names and are listed comma
+    /// separated in `resolve`, so they may not hold separators.
+    fn check_name(kind: &str, name: &str) -> std::io::Result<()> {
+        if name.is_empty() || name.contains(['/', '\\', ',']) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid {} name '{}'", kind, name),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn create_index(
+        &mut self,
+        name: &str,
+        settings: Option<IndexSettings>,
+    ) -> std::io::Result<()> {
+        Self::check_name("index", name)?;
+        if self.indices.contains_key(name) || self.aliases.contains_key(name) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("'{}' is already an index or alias", name),
+            ));
+        }
+
+        let engine = Self::open_engine(&self.root, name, settings)?;
+        self.indices.insert(name.to_string(), engine);
+        self.save_meta()
+    }
+
+    pub fn index(&self, name: &str) -> Option<&SearchEngine> {
+        self.indices.get(name)
+    }
+
+    pub fn index_mut(&mut self, name: &str) -> Option<&mut SearchEngine> {
+        self.indices.get_mut(name)
+    }
+
+    pub fn index_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.indices.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    /// Create or replace `alias` so that it points at `indices`.
+    pub fn put_alias(&mut self, alias: &str, indices: Vec<String>) -> std::io::Result<()> {
+        Self::check_name("alias", alias)?;
+        if self.indices.contains_key(alias) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("alias '{}' clashes with an index name", alias),
+            ));
+        }
+        if indices.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("alias '{}' must point to at least one index", alias),
+            ));
+        }
+        if let Some(missing) = indices.iter().find(|i| !self.indices.contains_key(*i)) {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("index '{}' does not exist", missing),
+            ));
+        }
+
+        self.aliases.insert(alias.to_string(), indices);
+        self.save_meta()
+    }
+
+    pub fn remove_alias(&mut self, alias: &str) -> std::io::Result<()> {
+        if self.aliases.remove(alias).is_none() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("alias '{}' does not exist", alias),
+            ));
+        }
+        self.save_meta()
+    }
+
+    pub fn aliases(&self) -> &HashMap<String, Vec<String>> {
+        &self.aliases
+    }
+
+    /// Resolve a comma separated list of index names and aliases into the
+    /// distinct index names they cover.
+    pub fn resolve(&self, target: &str) -> std::io::Result<Vec<String>> {
+        let mut resolved: Vec<String> = Vec::new();
+
+        for part in target.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let names = if self.indices.contains_key(part) {
+                vec![part.to_string()]
+            } else if let Some(names) = self.aliases.get(part) {
+                names.clone()
+            } else {
+                return Err(Error::new(
+                    ErrorKind::NotFound,
+                    format!("no index or alias named '{}'", part),
+                ));
+            };
+
+            for name in names {
+                if !resolved.contains(&name) {
+                    resolved.push(name);
+                }
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    /// BM25 search over every index `target` resolves to. Document frequencies
+    /// and average document length are merged across those indices first, so a
+    /// document scores the same as it would in one combined index.
+    pub fn search(
+        &self,
+        target: &str,
+        query: &str,
+        k1: f64,
+        b: f64,
+        top_k: usize,
+    ) -> std::io::Result<Vec<CrossIndexHit>> {
+        let names = self.resolve(target)?;
+        let stores: Vec<(&str, &DocumentStore)> = names
+            .iter()
+            .filter_map(|n| self.indices.get(n).map(|e| (n.as_str(), e.store())))
+            .collect();
+
+        Ok(cross_index_bm25(&stores, query, k1, b, top_k))
+    }
+}
+
+/// Score `query` against each store using collection statistics merged over
+/// all of them, then merge the hits into a single ranked list.
+pub fn cross_index_bm25(
+    stores: &[(&str, &DocumentStore)],
+    query: &str,
+    k1: f64,
+    b: f64,
+    top_k: usize,
+) -> Vec<CrossIndexHit> {
+    let per_store_terms: Vec<Vec<String>> = stores
+        .iter()
+        .map(|(_, store)| store.tokenizer.tokenize(query, false).0)
+        .collect();
+
+    let mut global = CollectionStats::default();
+    for ((_, store), terms) in stores.iter().zip(&per_store_terms) {
+        let refs: Vec<&str> = terms.iter().map(String::as_str).collect();
+        global.merge(&store.normal_index.collection_stats(&refs));
+    }
+
+    let mut hits: Vec<CrossIndexHit> = Vec::new();
+    for ((name, store), terms) in stores.iter().zip(&per_store_terms) {
+        let refs: Vec<&str> = terms.iter().map(String::as_str).collect();
+        let scores = store
+            .normal_index
+            .bm25_search_with_stats(&refs, k1, b, &global);
+
+        hits.extend(scores.into_iter().map(|(doc_id, score)| CrossIndexHit {
+            index: name.to_string(),
+            doc_id,
+            score,
+        }));
+    }
+
+    hits.sort_by(|a, b| {
+        OrderedFloat(b.score)
+            .cmp(&OrderedFloat(a.score))
+            .then_with(|| a.index.cmp(&b.index))
+            .then_with(|| a.doc_id.cmp(&b.doc_id))
+    });
+    hits.truncate(top_k);
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::text_doc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn alias_search_uses_global_stats() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().to_string_lossy().to_string();
+
+        let mut registry = IndexRegistry::open(root.clone()).unwrap();
+        registry.create_index("logs-01", None).unwrap();
+        registry.create_index("logs-02", None).unwrap();
+        registry
+            .put_alias("logs", vec!["logs-01".into(), "logs-02".into()])
+            .unwrap();
+
+        let jan = registry.index_mut("logs-01").unwrap();
+        jan.add_document(text_doc("title", "rust search engine"), None)
+            .unwrap();
+        jan.add_document(text_doc("title", "rust"), None).unwrap();
+        let feb = registry.index_mut("logs-02").unwrap();
+        feb.add_document(text_doc("title", "search"), None).unwrap();
+
+        // the same three docs in a single index give the reference scores
+        let mut combined = DocumentStore::new(None);
+        combined.add_document("a", &text_doc("title", "rust search engine"), None);
+        combined.add_document("b", &text_doc("title", "rust"), None);
+        combined.add_document("c", &text_doc("title", "search"), None);
+        let expected = combined.normal_index.bm25_search(&["search"], 1.2, 0.75);
+
+        let hits = registry.search("logs", "search", 1.2, 0.75, 10).unwrap();
+        assert_eq!(hits.len(), 2);
+        for hit in &hits {
+            let reference = if hit.index == "logs-01" {
+                expected["a"]
+            } else {
+                expected["c"]
+            };
+            assert!((hit.score - reference).abs() < 1e-9);
+        }
+
+        // an alias name must be resolvable on its own
+        for bad in ["", "a,b", "a/b"] {
+            let err = registry.put_alias(bad, vec!["logs-01".into()]).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        }
+        assert!(registry.create_index("a,b", None).is_err());
+
+        // aliases survive a reopen
+        let reopened = IndexRegistry::open(root).unwrap();
+        assert_eq!(
+            reopened.resolve("logs").unwrap(),
+            vec!["logs-01".to_string(), "logs-02".to_string()]
+        );
+    }
+}
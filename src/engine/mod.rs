@@ -1,2 +1,5 @@
+pub mod explain;
+pub mod highlight;
+pub mod index_registry;
 pub mod query_service;
 pub mod search_engine;
@@ -1,37 +1,261 @@
+use std::cell::Cell;
 use std::cmp::Ordering;
+use std::ops::Bound;
 use std::{
     collections::{BinaryHeap, HashMap, HashSet},
     string,
 };
 
+use levenshtein_automata::{Distance, LevenshteinAutomatonBuilder, SINK_STATE};
 use ordered_float::OrderedFloat;
-use regex::SetMatches;
+use regex::{RegexBuilder, SetMatches};
 
+use crate::engine::explain::{Explanation, explain_bm25};
+use crate::engine::highlight::{Fragment, HighlightOptions, highlight_text};
 use crate::index::{
-    documents_store::{Document, DocumentStore},
-    forward_indexer::ForwardIndex,
+    analysis::{analyzer::AnalysisRegistry, synonyms::Alternative},
+    bool_index::BoolIndex,
+    completion::{CompletionIndex, Suggestion},
+    documents_store::{Document, DocumentStore, NestedBlock},
+    forward_indexer::{ForwardDoc, ForwardIndex},
+    geo_index::{GeoBox, GeoIndex, GeoPoint},
     inverted_index::inverted_index::InvertedIndex,
-    n_gram::{n_gram_index::NgramIndex, n_gram_trie::NgramTrie},
+    keyword_index::{KeywordIndex, normalize_keyword, path_matches},
+    n_gram::{n_gram_store::NgramStore, n_gram_trie::NgramTrie},
+    settings::{FieldType, IndexSettings},
     tokenizer::tokenizer::Tokenizer,
-    value_tree::b_tree::ValueTreeIndex,
+    value_tree::{b_tree::ValueTreeIndex, sortable::cmp_i64_f64},
 };
-use crate::query_lang::ast::SortOrder;
+use crate::query_lang::ast::{SortMode, SortOrder};
+use crate::utils::date_normalizer::{now_millis, parse_date_math};
+
+/// Most terms a prefix is expanded to, shortest first. The walk over the
+/// term trie stops there, so a one-letter prefix stays cheap.
+pub const MAX_PREFIX_EXPANSIONS: usize = 64;
+
+/// Most terms a wildcard or regexp query is expanded to. Candidates are
+/// matched one at a time and the search stops there.
+pub const MAX_PATTERN_EXPANSIONS: usize = 128;
+
+// compiled patterns larger than this (in bytes) are refused
+const MAX_PATTERN_SIZE: usize = 1 << 20;
+
+/// Most edits a fuzzy term query allows.
+pub const MAX_FUZZY_EDITS: u8 = 2;
+
+/// Controls of a fuzzy term query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FuzzyOptions {
+    /// Most edits: insertions, deletions, substitutions and, with
+    /// `transpositions`, swaps of two adjacent characters.
+    pub fuzziness: u8,
+    /// Leading characters that have to match exactly.
+    pub prefix_length: usize,
+    /// Most terms the query expands to; the closest, then most frequent, are
+    /// kept.
+    pub max_expansions: usize,
+    pub transpositions: bool,
+}
+
+impl Default for FuzzyOptions {
+    fn default() -> Self {
+        Self {
+            fuzziness: MAX_FUZZY_EDITS,
+            prefix_length: 0,
+            max_expansions: 50,
+            transpositions: true,
+        }
+    }
+}
+
+impl FuzzyOptions {
+    pub fn new(fuzziness: u8) -> Self {
+        Self {
+            fuzziness,
+            ..Default::default()
+        }
+    }
+}
+
+/// Most edits a "did you mean" correction may make to a word.
+pub const MAX_SUGGEST_EDITS: usize = 2;
+
+// score given up per edit when choosing a phrase correction
+const SUGGEST_EDIT_PENALTY: f64 = 0.5;
+
+/// An indexed term offered as a correction of a query word.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TermSuggestion {
+    pub term: String,
+    pub distance: usize,
+    pub doc_freq: usize,
+}
+
+// A term `ngram_bm25` searches for, with what its weight was computed from.
+struct NgramCandidate<'t> {
+    term: &'t str,
+    shared_grams: usize,
+    query_grams: usize,
+    term_grams: usize,
+    // to the closest query word
+    distance: usize,
+    weight: f64,
+}
+
+/// Ranked hits, plus a corrected query when some of its words are not in
+/// the index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResponse {
+    pub hits: Vec<(String, f64)>,
+    pub did_you_mean: Option<String>,
+}
 
 #[derive(Debug, Clone)]
 pub struct SortField {
     pub field_path: String,
     pub ascending: bool,
+    /// How a multi-valued field is reduced to one sort value. Defaults to the
+    /// smallest value when ascending and the largest when descending.
+    pub mode: Option<SortMode>,
+    /// Sort a geo_point field by distance from this point instead of by value.
+    pub origin: Option<GeoPoint>,
+}
+
+impl SortField {
+    pub fn new(field_path: impl Into<String>, ascending: bool) -> Self {
+        Self {
+            field_path: field_path.into(),
+            ascending,
+            mode: None,
+            origin: None,
+        }
+    }
+
+    pub fn with_mode(mut self, mode: SortMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    pub fn by_distance_from(mut self, origin: GeoPoint) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    fn effective_mode(&self) -> SortMode {
+        self.mode.unwrap_or(if self.ascending {
+            SortMode::Min
+        } else {
+            SortMode::Max
+        })
+    }
+}
+
+fn reduce_numbers(values: &[f64], mode: SortMode) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    Some(match mode {
+        SortMode::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+        SortMode::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        SortMode::Sum => values.iter().sum(),
+        SortMode::Avg => values.iter().sum::<f64>() / values.len() as f64,
+    })
+}
+
+// keywords have no average or sum; those modes fall back to the smallest
+fn reduce_keywords(values: &[String], mode: SortMode) -> Option<String> {
+    match mode {
+        SortMode::Max => values.iter().max().cloned(),
+        _ => values.iter().min().cloned(),
+    }
+}
+
+// shared n-grams over all n-grams of the two sets
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+/// Value a document is sorted on: numeric fields first, keyword fields as a
+/// fallback for fields that have no numeric value.
+#[derive(Debug, Clone)]
+enum SortKey {
+    Number(f64),
+    Integer(i64),
+    Keyword(String),
+}
+
+// integers compare with floats exactly; any number sorts before a keyword
+impl PartialOrd for SortKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (SortKey::Number(a), SortKey::Number(b)) => a.partial_cmp(b),
+            (SortKey::Integer(a), SortKey::Integer(b)) => Some(a.cmp(b)),
+            (SortKey::Integer(a), SortKey::Number(b)) => cmp_i64_f64(*a, *b),
+            (SortKey::Number(a), SortKey::Integer(b)) => cmp_i64_f64(*b, *a).map(Ordering::reverse),
+            (SortKey::Keyword(a), SortKey::Keyword(b)) => Some(a.cmp(b)),
+            (SortKey::Keyword(_), _) => Some(Ordering::Greater),
+            (_, SortKey::Keyword(_)) => Some(Ordering::Less),
+        }
+    }
+}
+
+impl PartialEq for SortKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+// integers stay exact for the smallest and largest value; a sum or an
+// average of them is a float anyway
+fn reduce_numeric(floats: &[f64], integers: &[i64], mode: SortMode) -> Option<SortKey> {
+    let integer = match mode {
+        SortMode::Min => integers.iter().min(),
+        SortMode::Max => integers.iter().max(),
+        SortMode::Sum | SortMode::Avg => {
+            let all: Vec<f64> = (floats.iter().copied())
+                .chain(integers.iter().map(|i| *i as f64))
+                .collect();
+            return reduce_numbers(&all, mode).map(SortKey::Number);
+        }
+    };
+    let integer = integer.copied().map(SortKey::Integer);
+    let float = reduce_numbers(floats, mode).map(SortKey::Number);
+
+    match (float, integer) {
+        (Some(float), Some(integer)) => {
+            let integer_smaller = integer < float;
+            Some(if integer_smaller == (mode == SortMode::Min) {
+                integer
+            } else {
+                float
+            })
+        }
+        (float, integer) => float.or(integer),
+    }
 }
 
 pub struct QueryService<'a> {
     store: &'a HashMap<String, Document>,
     allow_ngram: bool,
     tokenizer: &'a Tokenizer,
+    analysis: &'a AnalysisRegistry,
     normal_index: &'a InvertedIndex,
-    n_gram_index: &'a Option<NgramIndex>,
-    n_gram_trie: &'a Option<NgramTrie>,
+    n_grams: Option<&'a dyn NgramStore>,
+    term_trie: &'a NgramTrie,
     value_tree: &'a ValueTreeIndex,
     forward_index: &'a ForwardIndex,
+    keyword_index: &'a KeywordIndex,
+    bool_index: &'a BoolIndex,
+    geo_index: &'a GeoIndex,
+    completion: &'a CompletionIndex,
+    nested: &'a HashMap<String, NestedBlock>,
+    settings: &'a IndexSettings,
+    // index entries read so far, for query profiles
+    postings_read: Cell<usize>,
 }
 
 impl<'a> QueryService<'a> {
@@ -40,84 +264,943 @@ impl<'a> QueryService<'a> {
             store: &state.store,
             allow_ngram: state.allow_ngram,
             tokenizer: &state.tokenizer,
+            analysis: &state.analysis,
             normal_index: &state.normal_index,
-            n_gram_index: &state.n_gram_index,
-            n_gram_trie: &state.n_gram_trie,
+            n_grams: state.n_grams.as_deref(),
+            term_trie: &state.term_trie,
             value_tree: &state.value_tree,
             forward_index: &state.forward_index,
+            keyword_index: &state.keyword_index,
+            bool_index: &state.bool_index,
+            geo_index: &state.geo_index,
+            completion: &state.completion,
+            nested: &state.nested,
+            settings: &state.settings,
+            postings_read: Cell::new(0),
         }
     }
-    pub fn ngram_bm25(
+
+    /// Postings and other index entries read by the queries run so far,
+    /// documents scanned one by one included.
+    pub fn postings_read(&self) -> usize {
+        self.postings_read.get()
+    }
+
+    // count `n` more index entries read
+    fn read_postings(&self, n: usize) {
+        self.postings_read.set(self.postings_read.get() + n);
+    }
+
+    // the hits of an index lookup, counted as read
+    fn counted<T>(&self, hits: Vec<T>) -> Vec<T> {
+        self.read_postings(hits.len());
+        hits
+    }
+
+    pub fn all_doc_ids(&self) -> Vec<String> {
+        self.counted(self.store.keys().cloned().collect())
+    }
+
+    pub fn doc_count(&self) -> usize {
+        self.store.len()
+    }
+
+    /// Documents whose boolean `field` equals `value`.
+    pub fn bool_query(&self, field: &str, value: bool) -> Vec<String> {
+        self.counted(self.bool_index.query(field, value))
+    }
+
+    /// Documents with a non-null value at `field`.
+    pub fn exists(&self, field: &str) -> Vec<String> {
+        // no index of its own: every forward doc is read
+        self.read_postings(self.forward_index.docs.len());
+        self.forward_index
+            .docs
+            .iter()
+            .filter(|(_, forward)| forward.has_value(field))
+            .map(|(doc_id, _)| doc_id.clone())
+            .collect()
+    }
+
+    /// Documents with a point of `field` within `distance_m` metres of `center`.
+    pub fn geo_distance(&self, field: &str, center: GeoPoint, distance_m: f64) -> Vec<String> {
+        self.counted(self.geo_index.distance(field, center, distance_m))
+    }
+
+    /// Documents with a point of `field` inside the box spanned by its
+    /// top-left and bottom-right corners.
+    pub fn geo_bounding_box(
+        &self,
+        field: &str,
+        top_left: GeoPoint,
+        bottom_right: GeoPoint,
+    ) -> Vec<String> {
+        self.counted(
+            self.geo_index
+                .bounding_box(field, &GeoBox::new(top_left, bottom_right)),
+        )
+    }
+
+    /// Distances in metres from `origin` to every point of `field` in `forward`.
+    fn distances(forward: &ForwardDoc, field: &str, origin: &GeoPoint) -> Vec<f64> {
+        forward
+            .geo_fields
+            .iter()
+            .filter(|(path, _)| path_matches(path, field))
+            .flat_map(|(_, points)| points.iter().map(|p| origin.distance_to(p)))
+            .collect()
+    }
+
+    /// Run `query` against the sub-documents of the nested field `path` and
+    /// return the parents of the matching elements, so every condition of
+    /// `query` has to hold within the same array element.
+    pub fn nested<F>(&self, path: &str, mut query: F) -> Result<Vec<String>, String>
+    where
+        F: FnMut(&QueryService) -> Result<Vec<String>, String>,
+    {
+        // a nested field with no documents yet has no block, only a mapping
+        let mapped = self.settings.mappings.iter().any(|(mapped_path, mapping)| {
+            mapping.field_type == Some(FieldType::Nested) && path_matches(mapped_path, path)
+        });
+        if !mapped && !self.nested.keys().any(|block_path| path_matches(block_path, path)) {
+            return Err(format!("'{}' is not a nested field", path));
+        }
+
+        let mut result: HashSet<String> = HashSet::new();
+
+        for (block_path, block) in self.nested {
+            if path_matches(block_path, path) {
+                let inner = QueryService::new(&block.store);
+                let sub_ids = query(&inner)?;
+                self.read_postings(inner.postings_read());
+                result.extend(block.parent_ids(&sub_ids));
+            }
+        }
+
+        Ok(result.into_iter().collect())
+    }
+
+    /// Documents where `field` is null or absent.
+    pub fn missing(&self, field: &str) -> Vec<String> {
+        self.read_postings(self.store.len());
+        self.store
+            .keys()
+            .filter(|doc_id| {
+                self.forward_index
+                    .get(doc_id)
+                    .is_none_or(|forward| !forward.has_value(field))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Exact match against a keyword field (or a text field's `.keyword`
+    /// sub-field).
+    pub fn term(&self, field: &str, value: &str) -> Vec<String> {
+        self.counted(self.keyword_index.term_query(field, value))
+    }
+
+    pub fn is_keyword_field(&self, field: &str) -> bool {
+        self.keyword_index.has_field(field)
+    }
+
+    /// Whether `field` holds dates, by mapping or by what has been indexed.
+    pub fn is_date_field(&self, field: &str) -> bool {
+        self.settings.mappings.iter().any(|(path, mapping)| {
+            mapping.field_type == Some(FieldType::Date) && path_matches(path, field)
+        }) || self.value_tree.has_date_field(field)
+    }
+
+    /// Documents containing every word of `text` after analysis with the
+    /// analyzer of `field`. With language detection on, the text is also
+    /// analyzed as every language detected in the index, and a document
+    /// matching any of those analyses is returned. Search synonyms of a word
+    /// match in its place.
+    pub fn match_text(&self, field: &str, text: &str) -> Vec<String> {
+        // (analyzer name, terms) for every analysis of the text
+        let mut analyses = vec![(
+            self.analysis.query_field_analyzer(field),
+            self.analyze(Some(field), text),
+        )];
+        if self.settings.language_detection && !self.analysis.has_field_analyzer(field) {
+            for language in self.indexed_languages() {
+                if let Some(analyzer) = self.analysis.get(language) {
+                    let words = analyzer.analyze(text);
+                    if !analyses.iter().any(|(_, seen)| *seen == words) {
+                        analyses.push((language, words));
+                    }
+                }
+            }
+        }
+
+        let mut result: HashSet<String> = HashSet::new();
+        for (analyzer, words) in &analyses {
+            result.extend(self.match_expanded(analyzer, words));
+        }
+        result.into_iter().collect()
+    }
+
+    /// Documents matching every span of the expanded `terms`, produced by
+    /// the analyzer named `analyzer`, through at least one of its
+    /// alternatives.
+    fn match_expanded(&self, analyzer: &str, terms: &[String]) -> HashSet<String> {
+        let mut result: Option<HashSet<String>> = None;
+
+        for span in self.analysis.expand_query(analyzer, terms) {
+            let mut ids: HashSet<String> = HashSet::new();
+            for alternative in &span {
+                ids.extend(self.and_word(alternative.tokens.iter().map(String::as_str).collect()));
+            }
+
+            let ids = match result {
+                Some(mut result) => {
+                    result.retain(|id| ids.contains(id));
+                    result
+                }
+                None => ids,
+            };
+            if ids.is_empty() {
+                return ids;
+            }
+            result = Some(ids);
+        }
+
+        result.unwrap_or_default()
+    }
+
+    /// Completion suggestions for `prefix` from a field mapped with
+    /// `completion`, heaviest first, each with the documents it comes from.
+    pub fn complete(
+        &self,
+        field: &str,
+        prefix: &str,
+        fuzziness: u32,
+        size: usize,
+    ) -> Result<Vec<Suggestion>, String> {
+        self.completion.suggest(field, prefix, fuzziness, size)
+    }
+
+    /// Indexed terms starting with `prefix`, shortest first, at most
+    /// `MAX_PREFIX_EXPANSIONS` of them.
+    pub fn prefix_terms(&self, prefix: &str) -> Vec<String> {
+        self.expand(
+            self.term_trie.iter_terms_with_prefix(prefix),
+            None,
+            MAX_PREFIX_EXPANSIONS,
+        )
+    }
+
+    // the first `cap` candidates occurring in `field`, or in any field when
+    // there is none. Other fields' terms never take up the cap.
+    fn expand<'t>(
+        &self,
+        candidates: impl Iterator<Item = &'t str>,
+        field: Option<&str>,
+        cap: usize,
+    ) -> Vec<String> {
+        candidates
+            .filter(|term| {
+                field.is_none_or(|field| self.normal_index.has_term_in_field(term, field))
+            })
+            .take(cap)
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Documents with a term of `field` starting with `prefix`. The prefix is
+    /// lowercased but not analyzed further, as it is usually a partial word.
+    pub fn prefix(&self, field: &str, prefix: &str) -> Vec<String> {
+        let terms = self.expand(
+            self.term_trie.iter_terms_with_prefix(&prefix.to_lowercase()),
+            Some(field),
+            MAX_PREFIX_EXPANSIONS,
+        );
+        self.docs_with_any_term(field, &terms)
+    }
+
+    // documents holding at least one of `terms` in `field`
+    fn docs_with_any_term(&self, field: &str, terms: &[String]) -> Vec<String> {
+        let mut ids: HashSet<String> = HashSet::new();
+
+        for term in terms {
+            let postings = self.normal_index.search_term_with_fields(term);
+            self.read_postings(postings.len());
+            for (doc_id, paths) in postings {
+                if paths.iter().any(|path| path_matches(path, field)) {
+                    ids.insert(doc_id);
+                }
+            }
+        }
+
+        ids.into_iter().collect()
+    }
+
+    /// Indexed terms within `options.fuzziness` edits of `term` (lowercased,
+    /// not analyzed), with their distance, found by running a
+    /// Damerau-Levenshtein automaton over the term trie.
+    pub fn fuzzy_terms(
+        &self,
+        term: &str,
+        options: &FuzzyOptions,
+    ) -> Result<Vec<(String, u8)>, String> {
+        self.fuzzy_terms_in(term, options, None)
+    }
+
+    // `fuzzy_terms` limited to the terms of `field`, when given, before the
+    // expansions are capped
+    fn fuzzy_terms_in(
+        &self,
+        term: &str,
+        options: &FuzzyOptions,
+        field: Option<&str>,
+    ) -> Result<Vec<(String, u8)>, String> {
+        if options.fuzziness > MAX_FUZZY_EDITS {
+            return Err(format!(
+                "fuzziness {} is above the maximum of {}",
+                options.fuzziness, MAX_FUZZY_EDITS
+            ));
+        }
+        let term = term.to_lowercase();
+        let split = term
+            .char_indices()
+            .nth(options.prefix_length)
+            .map_or(term.len(), |(i, _)| i);
+        let (prefix, rest) = term.split_at(split);
+
+        let dfa = LevenshteinAutomatonBuilder::new(options.fuzziness, options.transpositions)
+            .build_dfa(rest);
+        let step = |state: &u32, ch: char| {
+            let mut utf8 = [0; 4];
+            let next = ch
+                .encode_utf8(&mut utf8)
+                .bytes()
+                .fold(*state, |state, byte| dfa.transition(state, byte));
+            (next != SINK_STATE).then_some(next)
+        };
+        let accept = |state: &u32| match dfa.distance(*state) {
+            Distance::Exact(edits) => Some(edits),
+            Distance::AtLeast(_) => None,
+        };
+
+        let mut terms: Vec<(String, u8, usize)> = self
+            .term_trie
+            .search(prefix, dfa.initial_state(), step, accept)
+            .into_iter()
+            .filter(|(term, _)| {
+                field.is_none_or(|field| self.normal_index.has_term_in_field(term, field))
+            })
+            .map(|(term, edits)| {
+                let docs = self.normal_index.get_postings(term).map_or(0, Iterator::count);
+                (term.to_string(), edits, docs)
+            })
+            .collect();
+        terms.sort_by(|a, b| {
+            a.1.cmp(&b.1)
+                .then_with(|| b.2.cmp(&a.2))
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        terms.truncate(options.max_expansions);
+
+        Ok(terms.into_iter().map(|(term, edits, _)| (term, edits)).collect())
+    }
+
+    /// Documents with a term of `field` within the allowed edits of `term`.
+    pub fn fuzzy(
+        &self,
+        field: &str,
+        term: &str,
+        options: &FuzzyOptions,
+    ) -> Result<Vec<String>, String> {
+        let terms: Vec<String> = self
+            .fuzzy_terms_in(term, options, Some(field))?
+            .into_iter()
+            .map(|(term, _)| term)
+            .collect();
+        Ok(self.docs_with_any_term(field, &terms))
+    }
+
+    /// BM25 ranking where every word of `query` matches fuzzily on its own.
+    /// A document counts the best expansion of each word, scaled down by the
+    /// share of the word that had to be edited.
+    pub fn fuzzy_search(
         &self,
         query: &str,
+        options: &FuzzyOptions,
         k1: f64,
         b: f64,
-        alpha: f64,
-        beta: f64,
         top_k: usize,
-    ) -> Vec<(String, f64)> {
-        if !self.allow_ngram {
-            return Vec::new();
+    ) -> Result<Vec<(String, f64)>, String> {
+        let mut scores: HashMap<String, f64> = HashMap::new();
+
+        for word in query.split_whitespace() {
+            let length = word.chars().count() as f64;
+            let mut best: HashMap<String, f64> = HashMap::new();
+
+            for (term, edits) in self.fuzzy_terms(word, options)? {
+                let similarity = (1.0 - edits as f64 / length).max(0.0);
+                for (doc_id, score) in self.normal_index.bm25_search(&[term.as_str()], k1, b) {
+                    let entry = best.entry(doc_id).or_insert(0.0);
+                    *entry = entry.max(score * similarity);
+                }
+            }
+            for (doc_id, score) in best {
+                *scores.entry(doc_id).or_insert(0.0) += score;
+            }
         }
 
-        // ----------------
-        // Step 1: tokenize query
-        let (tokenized_words, tokenized_ngrams) = self.tokenizer.tokenize(query, self.allow_ngram);
+        let mut ranked: Vec<(String, f64)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranked.truncate(top_k);
+        Ok(ranked)
+    }
 
-        // ----------------
-        // Step 2: collect candidate words
-        let mut word_counts: HashMap<&str, usize> = HashMap::new();
-        let mut n_total = 0usize;
-
-        if let Some(ngram_index) = self.n_gram_trie.as_ref() {
-            for grams in &tokenized_ngrams {
-                n_total += grams.len();
-                for g in grams {
-                    for gr in &g.ngrams {
-                        for term in ngram_index.get_terms(gr) {
-                            *word_counts.entry(term).or_insert(0) += 1;
-                        }
-                    }
+    /// Indexed terms matching a wildcard `pattern`, where `*` stands for any
+    /// run of characters and `?` for exactly one, ignoring case.
+    pub fn wildcard_terms(&self, pattern: &str) -> Result<Vec<String>, String> {
+        self.wildcard_terms_in(pattern, None)
+    }
+
+    fn wildcard_terms_in(&self, pattern: &str, field: Option<&str>) -> Result<Vec<String>, String> {
+        let pattern = pattern.to_lowercase();
+        let mut regex = String::new();
+        for ch in pattern.chars() {
+            match ch {
+                '*' => regex.push_str(".*"),
+                '?' => regex.push('.'),
+                ch => regex.push_str(&regex::escape(ch.encode_utf8(&mut [0; 4]))),
+            }
+        }
+
+        // every match starts with the first literal run; the longest one
+        // narrows down the rest through its n-grams
+        let literals: Vec<&str> = pattern.split(['*', '?']).collect();
+        let infix = literals
+            .iter()
+            .max_by_key(|literal| literal.chars().count())
+            .copied()
+            .unwrap_or_default();
+        self.pattern_terms(&regex, literals[0], infix, field)
+    }
+
+    /// Documents with a term of `field` matching the wildcard `pattern`.
+    pub fn wildcard(&self, field: &str, pattern: &str) -> Result<Vec<String>, String> {
+        let terms = self.wildcard_terms_in(pattern, Some(field))?;
+        Ok(self.docs_with_any_term(field, &terms))
+    }
+
+    /// Indexed terms the regular expression matches as a whole, ignoring
+    /// case: `pi.a.*` matches "pikachu" but not "rapidash".
+    pub fn regexp_terms(&self, pattern: &str) -> Result<Vec<String>, String> {
+        self.regexp_terms_in(pattern, None)
+    }
+
+    fn regexp_terms_in(&self, pattern: &str, field: Option<&str>) -> Result<Vec<String>, String> {
+        let prefix = Self::regex_literal_prefix(pattern).to_lowercase();
+        self.pattern_terms(pattern, &prefix, "", field)
+    }
+
+    /// Documents with a term of `field` matched by the regular expression.
+    pub fn regexp(&self, field: &str, pattern: &str) -> Result<Vec<String>, String> {
+        let terms = self.regexp_terms_in(pattern, Some(field))?;
+        Ok(self.docs_with_any_term(field, &terms))
+    }
+
+    // plain characters every match of the regex starts with, less the last
+    // one when a quantifier makes it optional
+    fn regex_literal_prefix(pattern: &str) -> String {
+        if pattern.contains('|') {
+            return String::new();
+        }
+        let mut prefix = String::new();
+        for ch in pattern.chars() {
+            match ch {
+                ch if ch.is_alphanumeric() || ch == '_' => prefix.push(ch),
+                '?' | '*' | '{' => {
+                    prefix.pop();
+                    break;
                 }
+                _ => break,
             }
         }
+        prefix
+    }
 
-        // ----------------
-        // Step 3: score candidates by n-gram overlap + edit distance
-        let mut heap = BinaryHeap::new(); // (score, &str)
+    // the terms of `field` (any field when None) `pattern` matches in full,
+    // up to the cap. Candidates are the terms under `prefix` in the term
+    // trie, else the ones holding every n-gram of `infix`, else the whole
+    // dictionary.
+    fn pattern_terms(
+        &self,
+        pattern: &str,
+        prefix: &str,
+        infix: &str,
+        field: Option<&str>,
+    ) -> Result<Vec<String>, String> {
+        let regex = RegexBuilder::new(&format!("^(?:{})$", pattern))
+            .case_insensitive(true)
+            .size_limit(MAX_PATTERN_SIZE)
+            .build()
+            .map_err(|e| format!("invalid pattern '{}': {}", pattern, e))?;
+
+        let candidates: Box<dyn Iterator<Item = &str>> = if !prefix.is_empty() {
+            Box::new(self.term_trie.iter_terms_with_prefix(prefix))
+        } else if let Some(terms) = self.terms_with_ngrams_of(infix) {
+            Box::new(terms.into_iter())
+        } else {
+            Box::new(self.normal_index.terms().map(String::as_str))
+        };
+
+        let matching = candidates.filter(|term| regex.is_match(term));
+        Ok(self.expand(matching, field, MAX_PATTERN_EXPANSIONS))
+    }
+
+    // terms holding every n-gram of `literal`, shortest first, or None when
+    // the n-grams cannot narrow the search
+    fn terms_with_ngrams_of(&self, literal: &str) -> Option<Vec<&'a str>> {
+        let n_grams = self.n_grams?;
+        let sizes = self.tokenizer.ngram_sizes();
+        let n = literal.chars().count().min(*sizes.end());
+        if !sizes.contains(&n) {
+            return None;
+        }
 
-        let query_text = tokenized_words.join(" ");
+        let mut found: Option<HashSet<&str>> = None;
+        for gram in Tokenizer::ngram_tokenize(literal, n) {
+            let terms: HashSet<&str> = n_grams.terms(&gram).into_iter().collect();
+            found = Some(match found {
+                Some(found) => &found & &terms,
+                None => terms,
+            });
+        }
+        let mut terms: Vec<&str> = found?.into_iter().collect();
+        terms.sort_unstable_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+        Some(terms)
+    }
 
-        for (&word, &count) in &word_counts {
-            let jaccard_score = count as f64 / n_total.max(1) as f64;
+    /// Ranked documents for input typed into a search box: every complete
+    /// word must match, and the last one, unless followed by a space, matches
+    /// as a prefix. Scores are BM25, the best expansion of the prefix
+    /// counting for it.
+    pub fn search_as_you_type(&self, text: &str, top_k: usize) -> Vec<(String, f64)> {
+        const K1: f64 = 1.2;
+        const B: f64 = 0.75;
 
-            let ed = self.edit_distance(&query_text, word);
-            let edit_score = 1.0 - (ed as f64 / word.len().max(1) as f64);
+        let (complete, partial) = if text.ends_with(char::is_whitespace) {
+            (text, "")
+        } else {
+            text.rsplit_once(char::is_whitespace).unwrap_or(("", text))
+        };
+        let partial = partial
+            .trim_matches(|c: char| !c.is_alphanumeric())
+            .to_lowercase();
 
-            let candidate_score = alpha * jaccard_score + beta * edit_score;
+        let (terms, _) = self.tokenizer.tokenize(complete, false);
+        let mut clauses: Vec<HashMap<String, f64>> = terms
+            .iter()
+            .map(|term| self.normal_index.bm25_search(&[term.as_str()], K1, B))
+            .collect();
 
-            heap.push((OrderedFloat(candidate_score), word));
+        if !partial.is_empty() {
+            // an expansion counts toward the cap only when it hits a document
+            // every complete word matched
+            let stats = self.normal_index.collection_stats(&[]);
+            let mut best: HashMap<String, f64> = HashMap::new();
+            let mut expansions = 0;
+            for term in self.term_trie.iter_terms_with_prefix(&partial) {
+                let mut hits = self.normal_index.bm25_search_with_stats(&[term], K1, B, &stats);
+                hits.retain(|doc_id, _| clauses.iter().all(|clause| clause.contains_key(doc_id)));
+                if hits.is_empty() {
+                    continue;
+                }
+                for (doc_id, score) in hits {
+                    let entry = best.entry(doc_id).or_insert(0.0);
+                    *entry = entry.max(score);
+                }
+                expansions += 1;
+                if expansions == MAX_PREFIX_EXPANSIONS {
+                    break;
+                }
+            }
+            clauses.push(best);
         }
 
-        // ----------------
-        // Step 4: select top-k
-        let mut candidates: Vec<(&str, f64)> = heap
-            .into_sorted_vec() // sort descending
+        let Some((first, rest)) = clauses.split_first() else {
+            return Vec::new();
+        };
+        let mut ranked: Vec<(String, f64)> = first
+            .iter()
+            .filter_map(|(doc_id, score)| {
+                let others: Option<f64> = rest.iter().map(|clause| clause.get(doc_id)).sum();
+                others.map(|others| (doc_id.clone(), score + others))
+            })
+            .collect();
+
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranked.truncate(top_k);
+        ranked
+    }
+
+    /// BM25 ranking of `query`, analyzed with the default analyzer, best
+    /// first. A document matching a search synonym instead of the query term
+    /// gets the synonym weight times its score.
+    pub fn search(&self, query: &str, k1: f64, b: f64, top_k: usize) -> Vec<(String, f64)> {
+        let mut scores: HashMap<String, f64> = HashMap::new();
+
+        for alternative in self.query_alternatives(query) {
+            let tokens: Vec<&str> = alternative.tokens.iter().map(String::as_str).collect();
+            for (doc_id, score) in self.normal_index.bm25_search(&tokens, k1, b) {
+                *scores.entry(doc_id).or_insert(0.0) += score * alternative.weight;
+            }
+        }
+
+        let mut ranked: Vec<(String, f64)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranked.truncate(top_k);
+        ranked
+    }
+
+    // every way `query`, analyzed with the default analyzer, can match: its
+    // own terms and their search synonyms
+    fn query_alternatives(&self, query: &str) -> Vec<Alternative> {
+        let (terms, _) = self.tokenizer.tokenize(query, false);
+        self.analysis
+            .expand_query(self.analysis.default_analyzer_name(), &terms)
             .into_iter()
-            .rev()
-            .take(top_k)
-            .map(|(score, word)| (word, score.into_inner()))
+            .flatten()
+            .collect()
+    }
+
+    /// How `search` scored `doc_id` for `query`: the BM25 of every matching
+    /// term, times the synonym weight when it matched through a synonym.
+    /// None when the document is not in the index.
+    pub fn explain(&self, doc_id: &str, query: &str, k1: f64, b: f64) -> Option<Explanation> {
+        if !self.store.contains_key(doc_id) {
+            return None;
+        }
+        let mut details = Vec::new();
+
+        for alternative in self.query_alternatives(query) {
+            let tokens: Vec<&str> = alternative.tokens.iter().map(String::as_str).collect();
+            let stats = self.normal_index.collection_stats(&tokens);
+            let mut terms: Vec<Explanation> = tokens
+                .iter()
+                .filter_map(|term| explain_bm25(self.normal_index, doc_id, term, k1, b, &stats))
+                .collect();
+            let matched = match terms.len() {
+                0 => continue,
+                1 => terms.remove(0),
+                _ => Explanation::sum(format!("'{}', sum of", tokens.join(" ")), terms),
+            };
+
+            details.push(if alternative.weight == 1.0 {
+                matched
+            } else {
+                Explanation::new(
+                    matched.value * alternative.weight,
+                    format!("synonym '{}', boost * score", tokens.join(" ")),
+                )
+                .with(Explanation::new(alternative.weight, "boost, search synonym weight"))
+                .with(matched)
+            });
+        }
+
+        Some(Explanation::sum(
+            format!("score of '{}', sum of", doc_id),
+            details,
+        ))
+    }
+
+    /// `search`, with a "did you mean" correction when a query word is
+    /// unknown to the index.
+    pub fn search_with_suggestion(
+        &self,
+        query: &str,
+        k1: f64,
+        b: f64,
+        top_k: usize,
+    ) -> SearchResponse {
+        let (terms, _) = self.tokenizer.tokenize(query, false);
+        let misspelled = terms.iter().any(|term| self.normal_index.doc_freq(term) == 0);
+
+        SearchResponse {
+            hits: self.search(query, k1, b, top_k),
+            did_you_mean: if misspelled {
+                self.suggest_phrase(query)
+            } else {
+                None
+            },
+        }
+    }
+
+    // Index terms that may be `word` misspelled: those sharing an n-gram with
+    // it, or, when the index keeps no n-grams, those the Levenshtein
+    // automaton finds within `max_edits` (at most `MAX_FUZZY_EDITS`) in the
+    // term trie.
+    fn spelling_candidates(&self, word: &str, max_edits: usize) -> HashSet<String> {
+        match self.n_grams {
+            Some(n_grams) => {
+                let (_, grams) = self.tokenizer.tokenize(word, true);
+                grams
+                    .unwrap_or_default()
+                    .iter()
+                    .flat_map(|g| &g.ngrams)
+                    .flat_map(|gram| n_grams.terms(gram))
+                    .map(str::to_string)
+                    .collect()
+            }
+            None => {
+                let options = FuzzyOptions {
+                    fuzziness: max_edits.min(MAX_FUZZY_EDITS as usize) as u8,
+                    prefix_length: 0,
+                    max_expansions: usize::MAX,
+                    // edit_distance does not count swaps as one edit
+                    transpositions: false,
+                };
+                self.fuzzy_terms(word, &options)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(term, _)| term)
+                    .collect()
+            }
+        }
+    }
+
+    /// Indexed terms within `max_edits` of `word` (analyzed with the default
+    /// analyzer), closest first, then the most frequent.
+    pub fn suggest_terms(&self, word: &str, max_edits: usize, size: usize) -> Vec<TermSuggestion> {
+        let (terms, _) = self.tokenizer.tokenize(word, false);
+        let Some(word) = terms.first() else {
+            return Vec::new();
+        };
+
+        let mut suggestions: Vec<TermSuggestion> = self
+            .spelling_candidates(word, max_edits)
+            .into_iter()
+            .filter(|term| term != word)
+            .filter_map(|term| {
+                let distance = self.edit_distance(word, &term);
+                let doc_freq = self.normal_index.doc_freq(&term);
+                (distance <= max_edits && doc_freq > 0).then_some(TermSuggestion {
+                    term,
+                    distance,
+                    doc_freq,
+                })
+            })
             .collect();
 
+        suggestions.sort_by(|a, b| {
+            a.distance
+                .cmp(&b.distance)
+                .then_with(|| b.doc_freq.cmp(&a.doc_freq))
+                .then_with(|| a.term.cmp(&b.term))
+        });
+        suggestions.truncate(size);
+        suggestions
+    }
+
+    /// The query with its words corrected, or `None` when nothing changes.
+    /// Known words are kept; each unknown one is replaced by one of its term
+    /// suggestions, choosing the sequence that best trades edits against how
+    /// common the terms are and how often neighbours appear together.
+    pub fn suggest_phrase(&self, text: &str) -> Option<String> {
+        let (terms, _) = self.tokenizer.tokenize(text, false);
+        if terms.is_empty() {
+            return None;
+        }
+
+        // every position's candidates, each with its own score
+        let candidates: Vec<Vec<(String, f64)>> = terms
+            .iter()
+            .map(|term| {
+                let doc_freq = self.normal_index.doc_freq(term);
+                if doc_freq > 0 {
+                    return vec![(term.clone(), (1.0 + doc_freq as f64).ln())];
+                }
+                let mut corrections: Vec<(String, f64)> = self
+                    .suggest_terms(term, MAX_SUGGEST_EDITS, 5)
+                    .into_iter()
+                    .map(|s| {
+                        let score = (1.0 + s.doc_freq as f64).ln()
+                            - SUGGEST_EDIT_PENALTY * s.distance as f64;
+                        (s.term, score)
+                    })
+                    .collect();
+                if corrections.is_empty() {
+                    corrections.push((term.clone(), 0.0));
+                }
+                corrections
+            })
+            .collect();
+
+        let docs = |term: &str| -> HashSet<String> {
+            self.normal_index.search_term(&[term]).into_iter().collect()
+        };
+
+        // best path ending in each candidate of the current position
+        let mut paths: Vec<(f64, Vec<&str>)> = candidates[0]
+            .iter()
+            .map(|(term, score)| (*score, vec![term.as_str()]))
+            .collect();
+        for position in &candidates[1..] {
+            paths = position
+                .iter()
+                .map(|(term, score)| {
+                    let term_docs = docs(term);
+                    paths
+                        .iter()
+                        .map(|(path_score, path)| {
+                            let together = docs(path[path.len() - 1])
+                                .intersection(&term_docs)
+                                .count();
+                            let mut path = path.clone();
+                            path.push(term.as_str());
+                            (path_score + score + (1.0 + together as f64).ln(), path)
+                        })
+                        .max_by(|a, b| a.0.total_cmp(&b.0))
+                        .expect("every position has a candidate")
+                })
+                .collect();
+        }
+
+        let (_, best) = paths.into_iter().max_by(|a, b| a.0.total_cmp(&b.0))?;
+        (best != terms).then(|| best.join(" "))
+    }
+
+    /// Distinct languages detected for the indexed documents.
+    pub fn indexed_languages(&self) -> HashSet<&str> {
+        self.forward_index
+            .docs
+            .values()
+            .filter_map(|forward| forward.language.as_deref())
+            .collect()
+    }
+
+    /// Terms `text` is turned into by the analyzer of `field`, or by the
+    /// index default analyzer.
+    pub fn analyze(&self, field: Option<&str>, text: &str) -> Vec<String> {
+        match field {
+            Some(field) => self.analysis.for_query_field(field).analyze(text),
+            None => self.tokenizer.tokenize(text, false).0,
+        }
+    }
+
+    /// Document count per distinct value of a keyword field, most frequent
+    /// first, optionally restricted to `candidates`.
+    pub fn terms_aggregation(
+        &self,
+        field: &str,
+        candidates: Option<&HashSet<String>>,
+    ) -> Vec<(String, usize)> {
+        self.keyword_index.terms_aggregation(field, candidates)
+    }
+
+    /// Fragments of the text fields of `doc_id` with the words of `query`
+    /// tagged, by field path, the best first. A word matches when it analyzes
+    /// like a query term, so "evolves" matches "evolving" under stemming, or
+    /// like one of its search synonyms. With n-grams on, a word sharing
+    /// enough n-grams with a query term matches too, weighted by that share.
+    pub fn highlight(
+        &self,
+        doc_id: &str,
+        query: &str,
+        options: &HighlightOptions,
+    ) -> HashMap<String, Vec<String>> {
+        let Some(forward) = self.forward_index.get(doc_id) else {
+            return HashMap::new();
+        };
+        let mut highlights = HashMap::new();
+
+        for (path, values) in &forward.text_fields {
+            if !self.settings.is_indexed(path) {
+                continue;
+            }
+            let name = self
+                .analysis
+                .document_field_analyzer(path, forward.language.as_deref());
+            let analyzer = self
+                .analysis
+                .for_document_field(path, forward.language.as_deref());
+
+            let mut terms: HashMap<String, f64> = HashMap::new();
+            for span in self.analysis.expand_query(name, &analyzer.analyze(query)) {
+                for alternative in span {
+                    for token in alternative.tokens {
+                        let weight = terms.entry(token).or_insert(0.0);
+                        *weight = weight.max(alternative.weight);
+                    }
+                }
+            }
+            let term_grams: Vec<HashSet<String>> = match self.n_grams {
+                Some(_) => terms.keys().map(|term| self.ngram_set(term)).collect(),
+                None => Vec::new(),
+            };
+
+            let matches = |word: &str| {
+                let mut best: Option<f64> = None;
+                for term in analyzer.analyze(word) {
+                    let weight = terms.get(&term).copied().or_else(|| {
+                        let grams = self.ngram_set(&term);
+                        term_grams
+                            .iter()
+                            .map(|query_grams| jaccard(&grams, query_grams))
+                            .filter(|share| *share >= options.min_ngram_similarity)
+                            .reduce(f64::max)
+                    });
+                    if let Some(weight) = weight {
+                        best = Some(best.map_or(weight, |best| best.max(weight)));
+                    }
+                }
+                best
+            };
+
+            let mut fragments: Vec<Fragment> = values
+                .iter()
+                .flat_map(|value| highlight_text(value, matches, options))
+                .collect();
+            if fragments.is_empty() {
+                continue;
+            }
+            fragments.sort_by(|a, b| b.score.total_cmp(&a.score));
+            fragments.truncate(options.number_of_fragments);
+            highlights.insert(
+                path.clone(),
+                fragments.into_iter().map(|fragment| fragment.text).collect(),
+            );
+        }
+
+        highlights
+    }
+
+    // every n-gram of `word` in the sizes the index uses
+    fn ngram_set(&self, word: &str) -> HashSet<String> {
+        self.tokenizer
+            .ngram_sizes()
+            .flat_map(|n| Tokenizer::ngram_tokenize(word, n))
+            .collect()
+    }
+
+    pub fn ngram_bm25(
+        &self,
+        query: &str,
+        k1: f64,
+        b: f64,
+        alpha: f64,
+        beta: f64,
+        top_k: usize,
+    ) -> Vec<(String, f64)> {
+        // ----------------
+        // Steps 1 to 4: the top-k terms sharing n-grams with the query
+        let candidates = self.ngram_candidates(query, alpha, beta, top_k);
+
         // ----------------
         // Step 5: run BM25 and combine
         let mut doc_scores: HashMap<String, f64> = HashMap::new();
 
-        for (term, weight) in candidates.drain(..) {
-            let doc_scores_map = self.normal_index.bm25_search(&[term], k1, b);
+        for candidate in candidates {
+            let doc_scores_map = self.normal_index.bm25_search(&[candidate.term], k1, b);
 
             for (doc_id, bm25_score) in doc_scores_map {
-                *doc_scores.entry(doc_id).or_insert(0.0) += bm25_score * weight;
+                *doc_scores.entry(doc_id).or_insert(0.0) += bm25_score * candidate.weight;
             }
         }
 
@@ -126,6 +1209,150 @@ impl<'a> QueryService<'a> {
         doc_scores_vec
     }
 
+    // Steps 1 to 4 of `ngram_bm25`: index terms sharing n-grams with the
+    // query, weighted by n-gram overlap and edit distance, best `top_k` first
+    fn ngram_candidates(
+        &self,
+        query: &str,
+        alpha: f64,
+        beta: f64,
+        top_k: usize,
+    ) -> Vec<NgramCandidate<'a>> {
+        let Some(ngram_index) = self.n_grams else {
+            return Vec::new();
+        };
+
+        // ----------------
+        // Step 1: tokenize query
+        let (tokenized_words, tokenized_ngrams) = self.tokenizer.tokenize(query, self.allow_ngram);
+
+        // ----------------
+        // Step 2: collect candidate words, with the distinct query n-grams
+        // each one shares
+        let mut query_grams: HashSet<&str> = HashSet::new();
+        if let Some(grams) = &tokenized_ngrams {
+            for g in grams {
+                query_grams.extend(g.ngrams.iter().map(String::as_str));
+            }
+        }
+        let mut word_counts: HashMap<&str, usize> = HashMap::new();
+        for gr in &query_grams {
+            for term in ngram_index.terms(gr) {
+                *word_counts.entry(term).or_insert(0) += 1;
+            }
+        }
+
+        // ----------------
+        // Step 3: score candidates by n-gram overlap + edit distance
+        let mut candidates: Vec<NgramCandidate> = word_counts
+            .into_iter()
+            .map(|(word, count)| {
+                // shared n-grams over the union of the query's and the term's
+                let term_grams = self.ngram_set(word).len();
+                let union = query_grams.len() + term_grams.saturating_sub(count);
+                let jaccard_score = count as f64 / union.max(1) as f64;
+
+                // distance to the closest query word, not to the whole query
+                let ed = tokenized_words
+                    .iter()
+                    .map(|query_word| self.edit_distance(query_word, word))
+                    .min()
+                    .unwrap_or(0);
+                let edit_score = 1.0 - (ed as f64 / word.chars().count().max(1) as f64);
+
+                NgramCandidate {
+                    term: word,
+                    shared_grams: count,
+                    query_grams: query_grams.len(),
+                    term_grams,
+                    distance: ed,
+                    weight: alpha * jaccard_score + beta * edit_score,
+                }
+            })
+            .collect();
+
+        // ----------------
+        // Step 4: select top-k
+        candidates.sort_by(|a, b| {
+            b.weight
+                .total_cmp(&a.weight)
+                .then_with(|| b.term.cmp(a.term))
+        });
+        candidates.truncate(top_k);
+        candidates
+    }
+
+    /// How `ngram_bm25` scored `doc_id`: for every candidate term the
+    /// document holds, its BM25 times the candidate weight, itself the
+    /// `alpha`/`beta` blend of n-gram overlap and edit similarity. None when
+    /// the document is not in the index.
+    #[allow(clippy::too_many_arguments)]
+    pub fn explain_ngram_bm25(
+        &self,
+        doc_id: &str,
+        query: &str,
+        k1: f64,
+        b: f64,
+        alpha: f64,
+        beta: f64,
+        top_k: usize,
+    ) -> Option<Explanation> {
+        if !self.store.contains_key(doc_id) {
+            return None;
+        }
+        let mut details = Vec::new();
+
+        for candidate in self.ngram_candidates(query, alpha, beta, top_k) {
+            let term = candidate.term;
+            let stats = self.normal_index.collection_stats(&[term]);
+            let Some(bm25) = explain_bm25(self.normal_index, doc_id, term, k1, b, &stats) else {
+                continue;
+            };
+
+            let term_len = term.chars().count().max(1);
+            let union =
+                candidate.query_grams + candidate.term_grams.saturating_sub(candidate.shared_grams);
+            let jaccard = Explanation::new(
+                candidate.shared_grams as f64 / union.max(1) as f64,
+                "n-gram jaccard, shared n-grams / (query n-grams + term n-grams - shared)",
+            )
+            .with(Explanation::new(candidate.shared_grams as f64, "shared n-grams"))
+            .with(Explanation::new(candidate.query_grams as f64, "query n-grams"))
+            .with(Explanation::new(candidate.term_grams as f64, "term n-grams"));
+            let similarity = Explanation::new(
+                1.0 - candidate.distance as f64 / term_len as f64,
+                "edit similarity, 1 - distance / term length",
+            )
+            .with(Explanation::new(
+                candidate.distance as f64,
+                "edit distance to the closest query word",
+            ))
+            .with(Explanation::new(term_len as f64, "term length"));
+            let weight = Explanation::new(
+                candidate.weight,
+                "candidate weight, alpha * jaccard + beta * edit similarity",
+            )
+            .with(Explanation::new(alpha, "alpha"))
+            .with(jaccard)
+            .with(Explanation::new(beta, "beta"))
+            .with(similarity);
+
+            details.push(
+                Explanation::new(
+                    candidate.weight * bm25.value,
+                    format!("candidate '{}', weight * bm25", term),
+                )
+                .with(weight)
+                .with(bm25),
+            );
+        }
+
+        Some(Explanation::sum(
+            format!("score of '{}', sum of", doc_id),
+            details,
+        ))
+    }
+
     // finally using dp in protect lol
     fn edit_distance(&self, a: &str, b: &str) -> usize {
         let a_chars: Vec<char> = a.chars().collect();
@@ -165,65 +1392,130 @@ impl<'a> QueryService<'a> {
         dp[n][m]
     }
 
-    pub fn range_query(&self, field_path: &str, min: i64, max: i64) -> Vec<(&String, &String)> {
+    pub fn range_query(&self, field_path: &str, min: f64, max: f64) -> Vec<(&String, &String)> {
         self.value_tree.range_query(field_path, min, max)
     }
 
     pub fn greater_than(
         &'a self,
         field_path: &str,
-        min: i64,
-        exclude: Option<&[i64]>,
+        min: f64,
+        exclude: Option<&[f64]>,
     ) -> Vec<(&'a String, &'a String)> {
-        let max_bound = i64::MAX;
-        // > min → (min+1 ..= max_bound)
-        self.value_tree
-            .range_query_with_exclude(field_path, Some(min + 1), None, exclude)
+        self.counted(self.value_tree.range_query_with_exclude(
+            field_path,
+            Bound::Excluded(min),
+            Bound::Unbounded,
+            exclude,
+        ))
     }
 
     pub fn greater_than_equal(
         &'a self,
         field_path: &str,
-        min: i64,
-        exclude: Option<&[i64]>,
+        min: f64,
+        exclude: Option<&[f64]>,
     ) -> Vec<(&'a String, &'a String)> {
-        self.value_tree
-            .range_query_with_exclude(field_path, Some(min), None, exclude)
+        self.counted(self.value_tree.range_query_with_exclude(
+            field_path,
+            Bound::Included(min),
+            Bound::Unbounded,
+            exclude,
+        ))
     }
 
     pub fn less_than(
         &'a self,
         field_path: &str,
-        max: i64,
-        exclude: Option<&[i64]>,
+        max: f64,
+        exclude: Option<&[f64]>,
     ) -> Vec<(&'a String, &'a String)> {
-        // < max → (min_bound ..= max-1)
-        self.value_tree
-            .range_query_with_exclude(field_path, None, Some(max - 1), exclude)
+        self.counted(self.value_tree.range_query_with_exclude(
+            field_path,
+            Bound::Unbounded,
+            Bound::Excluded(max),
+            exclude,
+        ))
     }
 
     pub fn less_than_equal(
         &'a self,
         field_path: &str,
-        max: i64,
-        exclude: Option<&[i64]>,
+        max: f64,
+        exclude: Option<&[f64]>,
     ) -> Vec<(&'a String, &'a String)> {
-        let min_bound = i64::MIN;
-
-        self.value_tree
-            .range_query_with_exclude(field_path, None, Some(max), exclude)
+        self.counted(self.value_tree.range_query_with_exclude(
+            field_path,
+            Bound::Unbounded,
+            Bound::Included(max),
+            exclude,
+        ))
     }
 
     pub fn between(
         &'a self,
         field_path: &str,
-        min: i64,
-        max: i64,
-        exclude: Option<&[i64]>,
+        min: f64,
+        max: f64,
+        exclude: Option<&[f64]>,
     ) -> Vec<(&'a String, &'a String)> {
-        self.value_tree
-            .range_query_with_exclude(field_path, Some(min), Some(max), exclude)
+        self.counted(self.value_tree.range_query_with_exclude(
+            field_path,
+            Bound::Included(min),
+            Bound::Included(max),
+            exclude,
+        ))
     }
+
+    /// Documents with a date at `field_path` between `from` and `to`
+    /// (inclusive, either may be open). Bounds are dates or date math such
+    /// as `now-7d/d`; a rounded upper bound covers the whole unit.
+    pub fn date_range(
+        &self,
+        field_path: &str,
+        from: Option<&str>,
+        to: Option<&str>,
+    ) -> Result<Vec<String>, String> {
+        let from = from.map_or(Bound::Unbounded, Bound::Included);
+        let to = to.map_or(Bound::Unbounded, Bound::Included);
+        self.date_range_bounds(field_path, from, to)
+    }
+
+    /// `date_range` with exclusive bounds as well. Rounding follows the
+    /// bound: `> now/d` starts after today, `< now/d` ends before it.
+    pub fn date_range_bounds(
+        &self,
+        field_path: &str,
+        from: Bound<&str>,
+        to: Bound<&str>,
+    ) -> Result<Vec<String>, String> {
+        let now = now_millis();
+        let formats = &self.settings.date_formats;
+
+        let min = match from {
+            Bound::Included(expr) => Some(parse_date_math(expr, now, false, formats)?),
+            Bound::Excluded(expr) => {
+                Some(parse_date_math(expr, now, true, formats)?.saturating_add(1))
+            }
+            Bound::Unbounded => None,
+        };
+        let max = match to {
+            Bound::Included(expr) => Some(parse_date_math(expr, now, true, formats)?),
+            Bound::Excluded(expr) => {
+                Some(parse_date_math(expr, now, false, formats)?.saturating_sub(1))
+            }
+            Bound::Unbounded => None,
+        };
+
+        let ids: HashSet<String> = self
+            .counted(self.value_tree.date_range_query(field_path, min, max))
+            .into_iter()
+            .map(|(doc_id, _)| doc_id.clone())
+            .collect();
+
+        Ok(ids.into_iter().collect())
+    }
+
     pub fn not_word(&self, word: Vec<&str>) -> Vec<String> {
         let excluded_ids: HashSet<String> =
             self.normal_index.search_term(&word).into_iter().collect();
@@ -263,13 +1555,13 @@ impl<'a> QueryService<'a> {
         };
 
         let mut result: HashSet<String> = self
-            .normal_index
-            .search_term(&[first])
+            .counted(self.normal_index.search_term(&[first]))
             .into_iter()
             .collect();
 
         for word in iter {
-            let ids: HashSet<String> = self.normal_index.search_term(&[word]).into_iter().collect();
+            let ids: HashSet<String> =
+                self.counted(self.normal_index.search_term(&[word])).into_iter().collect();
             result.retain(|id| ids.contains(id));
             if result.is_empty() {
                 break;
@@ -302,12 +1594,12 @@ impl<'a> QueryService<'a> {
         let mut word_counts: HashMap<String, usize> = HashMap::new();
         let mut n_total = 0;
 
-        if let Some(ngram_index) = self.n_gram_trie.as_ref() {
+        if let Some(ngram_index) = self.n_grams {
             for grams in tokenized_ngrams.iter() {
                 for g in grams.iter() {
                     n_total += grams.len();
                     for gr in g.ngrams.clone() {
-                        let terms = ngram_index.get_terms(&gr);
+                        let terms = ngram_index.terms(&gr);
                         for t in terms {
                             *word_counts.entry(t.to_owned()).or_insert(0) += 1;
                         }
@@ -324,7 +1616,7 @@ impl<'a> QueryService<'a> {
             let jaccard_score = (count as f64) / (n_total as f64);
 
             let ed = self.edit_distance(&(tokenized_words.join(" ")), &word);
-            let edit_score = 1.0 - (ed as f64 / word.len().max(1) as f64);
+            let edit_score = 1.0 - (ed as f64 / word.chars().count().max(1) as f64);
 
             let candidate_score = alpha * jaccard_score + beta * edit_score;
 
@@ -369,21 +1661,33 @@ impl<'a> QueryService<'a> {
         search_field: &str,
         ascending: bool,
     ) -> Vec<String> {
-        let mut doc_scores: Vec<(String, i64)> = Vec::new();
+        let mut doc_scores: Vec<(String, OrderedFloat<f64>)> = Vec::new();
 
         if let Some(candidates) = candidates {
             for doc_id in candidates {
                 if let Some(forward_doc) = self.forward_index.get(doc_id) {
-                    let mut best: Option<i64> = None;
-
-                    for (field, value) in &forward_doc.numeric_fields {
+                    let mut best: Option<OrderedFloat<f64>> = None;
+
+                    let integers = forward_doc
+                        .integer_fields
+                        .iter()
+                        .map(|(field, values)| (field, values.iter().map(|i| *i as f64).collect()));
+                    let numbers: Vec<(&String, Vec<f64>)> = forward_doc
+                        .numeric_fields
+                        .iter()
+                        .map(|(field, values)| (field, values.clone()))
+                        .chain(integers)
+                        .collect();
+                    for (field, values) in &numbers {
                         if field.contains(search_field) {
-                            let v = *value as i64;
-
-                            best = match best {
-                                Some(current) => Some(current.max(v)), // aggregation rule
-                                None => Some(v),
-                            };
+                            for value in values {
+                                let v = OrderedFloat(*value);
+
+                                best = match best {
+                                    Some(current) => Some(current.max(v)), // aggregation rule
+                                    None => Some(v),
+                                };
+                            }
                         }
                     }
 
@@ -415,15 +1719,28 @@ impl<'a> QueryService<'a> {
                 let mut keys: Vec<Option<f64>> = Vec::with_capacity(sort_fields.len());
                 print!("{:?}", forward.numeric_fields);
                 for sf in sort_fields {
+                    if let Some(origin) = &sf.origin {
+                        let distances = Self::distances(forward, &sf.field_path, origin);
+                        keys.push(reduce_numbers(&distances, sf.effective_mode()));
+                        continue;
+                    }
+
                     let query_last = sf.field_path.rsplit('.').next().unwrap_or(&sf.field_path);
 
                     let mut values: Vec<f64> = Vec::new();
 
-                    for (field_path, value) in &forward.numeric_fields {
+                    for (field_path, field_values) in &forward.numeric_fields {
+                        let field_last = field_path.rsplit('.').next().unwrap_or(field_path);
+
+                        if field_last == query_last {
+                            values.extend(field_values);
+                        }
+                    }
+                    for (field_path, field_values) in &forward.integer_fields {
                         let field_last = field_path.rsplit('.').next().unwrap_or(field_path);
 
                         if field_last == query_last {
-                            values.push(*value);
+                            values.extend(field_values.iter().map(|i| *i as f64));
                         }
                     }
 
@@ -471,16 +1788,16 @@ impl<'a> QueryService<'a> {
 
 
 
-    pub fn suffix_matches(
+    pub fn suffix_matches<T: Clone>(
         &self,
-        numeric_indexes: &HashMap<String, f64>,
+        numeric_indexes: &HashMap<String, Vec<T>>,
         suffix: &str,
-    ) -> Vec<(String, f64)> {
+    ) -> Vec<(String, Vec<T>)> {
 
-        let mut matches: Vec<(String, f64)> = numeric_indexes
+        let mut matches: Vec<(String, Vec<T>)> = numeric_indexes
             .iter()
             .filter(|(field_path, _)| field_path.ends_with(suffix))
-            .map(|(field_path, value)| (field_path.clone(), *value))
+            .map(|(field_path, values)| (field_path.clone(), values.clone()))
             .collect();
 
         // Sort by longest field path first (most specific prefix wins)
@@ -499,24 +1816,59 @@ pub fn sort_docs_2(
         return doc_ids;
     }
 
-    let mut docs_with_keys: Vec<(String, Vec<Option<f64>>)> = Vec::new();
+    let mut docs_with_keys: Vec<(String, Vec<Option<SortKey>>)> = Vec::new();
 
     for doc_id in doc_ids {
-        let mut values: Vec<Option<f64>> = Vec::new();
+        let mut values: Vec<Option<SortKey>> = Vec::new();
 
         if let Some(forward_doc) = self.forward_index.get(&doc_id) {
             for sf in sort_fields {
+                // distance sorts rank by the nearest (or farthest) point
+                if let Some(origin) = &sf.origin {
+                    let distances = Self::distances(forward_doc, &sf.field_path, origin);
+                    values.push(reduce_numbers(&distances, sf.effective_mode()).map(SortKey::Number));
+                    continue;
+                }
 
                 // 🔥 ONLY ADDITION — use your suffix_matches
                 let matches = self.suffix_matches(
                     &forward_doc.numeric_fields,
                     &sf.field_path,
                 );
-
-                // take best match (first or however your fn returns)
-                let value = matches
-                    .first()
-                    .map(|(_, v)| *v);
+                let integer_matches =
+                    self.suffix_matches(&forward_doc.integer_fields, &sf.field_path);
+
+                // take best match, reducing multi-valued fields by the sort mode
+                let mode = sf.effective_mode();
+                let floats = matches.first().map_or(&[][..], |(_, v)| v);
+                let integers = integer_matches.first().map_or(&[][..], |(_, v)| v);
+                let value = reduce_numeric(floats, integers, mode)
+                    .or_else(|| {
+                        forward_doc
+                            .date_fields
+                            .iter()
+                            .filter(|(path, _)| path_matches(path, &sf.field_path))
+                            .max_by_key(|(path, _)| path.len())
+                            .and_then(|(_, v)| {
+                                let millis: Vec<f64> = v.iter().map(|d| *d as f64).collect();
+                                reduce_numbers(&millis, mode)
+                            })
+                            .map(SortKey::Number)
+                    })
+                    .or_else(|| {
+                        forward_doc
+                            .keyword_fields
+                            .iter()
+                            .filter(|(path, _)| path_matches(path, &sf.field_path))
+                            .max_by_key(|(path, _)| path.len())
+                            .and_then(|(_, v)| {
+                                // in the order terms and aggregations see them
+                                let terms: Vec<String> =
+                                    v.iter().map(|k| normalize_keyword(k)).collect();
+                                reduce_keywords(&terms, mode)
+                            })
+                            .map(SortKey::Keyword)
+                    });
 
                 values.push(value);
             }
@@ -529,11 +1881,11 @@ pub fn sort_docs_2(
 
     docs_with_keys.sort_by(|a, b| {
         for (i, sf) in sort_fields.iter().enumerate() {
-            let va = a.1[i];
-            let vb = b.1[i];
+            let va = &a.1[i];
+            let vb = &b.1[i];
 
             let ord = match (va, vb) {
-                (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+                (Some(x), Some(y)) => x.partial_cmp(y).unwrap_or(Ordering::Equal),
                 (None, Some(_)) => Ordering::Greater,
                 (Some(_), None) => Ordering::Less,
                 (None, None) => Ordering::Equal,
@@ -557,3 +1909,88 @@ pub fn sort_docs_2(
 
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{text_doc, text_store};
+
+    #[test]
+    fn expansions_are_capped_after_the_field_filter() {
+        let mut store = DocumentStore::new(None);
+        for i in 0..70 {
+            for copy in 0..2 {
+                let doc = text_doc("other", &format!("pa{}x", i));
+                store.add_document(&format!("other-{}-{}", i, copy), &doc, None);
+            }
+        }
+        store.add_document("target", &text_doc("name", "pzzzz"), None);
+        let qs = QueryService::new(&store);
+
+        // every `other` term comes first and would fill the cap
+        assert_eq!(qs.prefix_terms("p").len(), MAX_PREFIX_EXPANSIONS);
+        assert!(!qs.prefix_terms("p").contains(&"pzzzz".to_string()));
+
+        assert_eq!(qs.prefix("name", "p"), ["target"]);
+        assert_eq!(qs.wildcard("name", "p*").unwrap(), ["target"]);
+        assert_eq!(qs.regexp("name", "p.*").unwrap(), ["target"]);
+        let closest_only = FuzzyOptions {
+            max_expansions: 1,
+            ..FuzzyOptions::new(2)
+        };
+        assert_eq!(qs.fuzzy("name", "pa1zz", &closest_only).unwrap(), ["target"]);
+        assert_eq!(qs.search_as_you_type("pzzzz p", 10)[0].0, "target");
+    }
+
+    #[test]
+    fn did_you_mean_terms_and_phrases() {
+        let settings = IndexSettings {
+            min_ngram: Some(2),
+            max_ngram: Some(3),
+            ..Default::default()
+        };
+        let store = text_store(
+            settings,
+            "text",
+            [
+                ("1", "pikachu thunder"),
+                ("2", "pikachu electric"),
+                ("3", "pichu electric"),
+                ("4", "charmander fire"),
+            ],
+        );
+        let qs = QueryService::new(&store);
+
+        let terms = qs.suggest_terms("pikachuu", MAX_SUGGEST_EDITS, 5);
+        assert_eq!(terms[0].term, "pikachu");
+        assert_eq!((terms[0].distance, terms[0].doc_freq), (1, 2));
+        assert!(terms.iter().all(|s| s.distance <= MAX_SUGGEST_EDITS));
+
+        // "pichu" is closer, but "pikachu" is the one seen with "thunder"
+        assert_eq!(qs.suggest_phrase("pachu").as_deref(), Some("pichu"));
+        assert_eq!(
+            qs.suggest_phrase("pachu thunder").as_deref(),
+            Some("pikachu thunder")
+        );
+        assert_eq!(qs.suggest_phrase("pikachu electric"), None);
+
+        let response = qs.search_with_suggestion("pikachuu electrik", 1.2, 0.75, 10);
+        assert!(response.hits.is_empty());
+        assert_eq!(response.did_you_mean.as_deref(), Some("pikachu electric"));
+        let response = qs.search_with_suggestion("pikachu", 1.2, 0.75, 10);
+        assert_eq!(response.hits.len(), 2);
+        assert_eq!(response.did_you_mean, None);
+
+        // without n-grams the term trie is searched within the allowed edits,
+        // a wrong first letter included
+        let store = text_store(
+            IndexSettings::default(),
+            "text",
+            [("1", "pikachu"), ("2", "pichu"), ("3", "psyduck")],
+        );
+        let qs = QueryService::new(&store);
+        let terms = qs.suggest_terms("bikachu", MAX_SUGGEST_EDITS, 5);
+        let terms: Vec<&str> = terms.iter().map(|s| s.term.as_str()).collect();
+        assert_eq!(terms, ["pikachu"]);
+    }
+}
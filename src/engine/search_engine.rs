@@ -1,10 +1,13 @@
 use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
 
 use crate::{
     commits::commit_manager::CommitManager,
     engine::query_service::QueryService,
     index::{
         documents_store::{self, Document, DocumentStore},
+        settings::IndexSettings,
         tokenizer::tokenizer::TokenizerConfig,
         value::Value,
     },
@@ -14,6 +17,7 @@ use crate::{
 
 pub struct SearchEngine {
     index_path: String,
+    settings_path: String,
     commit_log_path: String,
     snapshot_path: String,
     documents_store: DocumentStore,
@@ -89,6 +93,33 @@ impl<'a> SearchEngine {
         snapshot_path: String,
         config: Option<TokenizerConfig>,
     ) -> std::io::Result<Self> {
+        // a bare tokenizer config only overrides the tokenizer part of
+        // whatever settings the index already has
+        let settings = match config {
+            Some(config) => {
+                let settings_path = Self::settings_path_for(&index_path);
+                let mut settings = Self::load_settings(&settings_path)?.unwrap_or_default();
+                settings.set_tokenizer_config(config);
+                Some(settings)
+            }
+            None => None,
+        };
+
+        Self::open(index_path, commit_log_path, snapshot_path, settings)
+    }
+
+    /// Open (or create) an index. Settings are persisted next to the index on
+    /// first open; later opens either reuse them (`settings: None`) or must
+    /// request compatible ones.
+    pub fn open(
+        index_path: String,
+        commit_log_path: String,
+        snapshot_path: String,
+        settings: Option<IndexSettings>,
+    ) -> std::io::Result<Self> {
+        let settings_path = Self::settings_path_for(&index_path);
+        let settings = Self::resolve_settings(&settings_path, settings)?;
+
         // 1️⃣ Load just the documents from index_path
         let docs_store: HashMap<String, Document> = if LocalStore::exists(&index_path) {
             match LocalStore::load(&index_path) {
@@ -106,7 +137,7 @@ impl<'a> SearchEngine {
         };
 
         // 2️⃣ Initialize DocumentStore with config and loaded docs
-        let mut documents_store = DocumentStore::new(config);
+        let mut documents_store = DocumentStore::with_settings(settings);
         documents_store.store = docs_store;
 
         // 3️⃣ Initialize CommitManager with snapshot support
@@ -117,6 +148,7 @@ impl<'a> SearchEngine {
         commit_manager.replay_withSnapshot(&mut documents_store);
         Ok(Self {
             index_path,
+            settings_path,
             documents_store,
             commit_manager,
             commit_log_path,
@@ -124,18 +156,74 @@ impl<'a> SearchEngine {
         })
     }
 
+    /// Settings live next to the index file and are named after it:
+    /// `data/data.json` keeps them in `data/data.settings.json`, so indexes
+    /// sharing a directory keep their own.
+    pub fn settings_path_for(index_path: &str) -> String {
+        Path::new(index_path)
+            .with_extension("settings.json")
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn load_settings(settings_path: &str) -> std::io::Result<Option<IndexSettings>> {
+        if LocalStore::exists(settings_path) {
+            LocalStore::load(settings_path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn resolve_settings(
+        settings_path: &str,
+        requested: Option<IndexSettings>,
+    ) -> std::io::Result<IndexSettings> {
+        let persisted = Self::load_settings(settings_path)?;
+
+        let settings = match (persisted, requested) {
+            (Some(mut persisted), Some(requested)) => {
+                persisted
+                    .check_compatible(&requested)
+                    .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+                persisted.merge_mappings(&requested);
+                persisted
+            }
+            (Some(persisted), None) => persisted,
+            (None, requested) => requested.unwrap_or_default(),
+        };
+
+        settings
+            .validate()
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+        LocalStore::save(&settings, settings_path)?;
+
+        Ok(settings)
+    }
+
+    pub fn settings(&self) -> &IndexSettings {
+        &self.documents_store.settings
+    }
+
     pub fn add_document(
         &mut self,
         data: HashMap<String, Value>,
         max_depth: Option<usize>,
     ) -> std::io::Result<String> {
+        let known_mappings = self.documents_store.settings.mappings.clone();
+
         // 1️⃣ Append the document via CommitManager
         let doc_id = self
             .commit_manager
-            .add_document(&mut self.documents_store, &data, max_depth);
+            .add_document(&mut self.documents_store, &data, max_depth)
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
 
         LocalStore::save(&self.documents_store.store, &self.index_path)?;
 
+        // 2️⃣ Persist any field types picked up by dynamic mapping
+        if self.documents_store.settings.mappings != known_mappings {
+            LocalStore::save(&self.documents_store.settings, &self.settings_path)?;
+        }
+
         // 3️⃣ Create a snapshot every 1000 commits
 
         Ok(doc_id)
@@ -171,16 +259,12 @@ impl<'a> SearchEngine {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::index::settings::{FieldMapping, FieldType, MappingMode};
     use crate::index::value::Value;
+    use crate::test_support::text_doc;
     use std::collections::HashMap;
     use tempfile::tempdir;
 
-    fn make_doc(title: &str) -> HashMap<String, Value> {
-        let mut doc = HashMap::new();
-        doc.insert("title".to_string(), Value::Text(title.to_string()));
-        doc
-    }
-
     #[test]
     fn crash_and_recover_add() {
         let dir = tempdir().unwrap();
@@ -199,8 +283,12 @@ mod tests {
             )
             .unwrap();
 
-            engine.add_document(make_doc("hello world"), None).unwrap();
-            engine.add_document(make_doc("rust search"), None).unwrap();
+            engine
+                .add_document(text_doc("title", "hello world"), None)
+                .unwrap();
+            engine
+                .add_document(text_doc("title", "rust search"), None)
+                .unwrap();
 
             // no graceful shutdown → simulate crash
         }
@@ -232,7 +320,9 @@ mod tests {
             )
             .unwrap();
 
-            engine.add_document(make_doc("once"), None).unwrap();
+            engine
+                .add_document(text_doc("title", "once"), None)
+                .unwrap();
         }
 
         // Second run
@@ -257,4 +347,238 @@ mod tests {
             assert_eq!(count, 1);
         }
     }
+
+    #[test]
+    fn indexes_sharing_a_directory_keep_their_own_settings() {
+        assert_eq!(
+            SearchEngine::settings_path_for("data/data.json"),
+            "data/data.settings.json"
+        );
+        assert_eq!(
+            SearchEngine::settings_path_for("data/pokemon"),
+            "data/pokemon.settings.json"
+        );
+
+        let dir = tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        for (index, stemming) in [("plain.json", false), ("stemmed.json", true)] {
+            let settings = IndexSettings {
+                use_stemming: stemming,
+                ..Default::default()
+            };
+            let open = |settings| {
+                SearchEngine::open(
+                    path(index),
+                    path(&format!("{}.log", index)),
+                    path(&format!("{}.snapshots", index)),
+                    settings,
+                )
+            };
+            assert!(open(Some(settings)).is_ok(), "{}", index);
+            assert_eq!(open(None).unwrap().settings().use_stemming, stemming);
+        }
+    }
+
+    #[test]
+    fn reopen_with_incompatible_settings_fails() {
+        let dir = tempdir().unwrap();
+
+        let index_path = dir.path().join("index.json").to_string_lossy().to_string();
+        let commit_path = dir.path().join("commits.log").to_string_lossy().to_string();
+        let snapshot_path = dir.path().join("snapshots").to_string_lossy().to_string();
+
+        let config = TokenizerConfig {
+            use_stemming: false,
+            min_ngram: Some(2),
+            max_ngram: Some(5),
+        };
+
+        {
+            let mut engine = SearchEngine::new(
+                index_path.clone(),
+                commit_path.clone(),
+                snapshot_path.clone(),
+                Some(config.clone()),
+            )
+            .unwrap();
+            engine
+                .add_document(text_doc("title", "pikachu"), None)
+                .unwrap();
+        }
+
+        // no settings requested → the persisted ones are used
+        {
+            let engine = SearchEngine::new(
+                index_path.clone(),
+                commit_path.clone(),
+                snapshot_path.clone(),
+                None,
+            )
+            .unwrap();
+            assert_eq!(engine.settings().min_ngram, Some(2));
+            assert!(
+                !engine
+                    .query_service()
+                    .ngram_bm25("pika", 1.2, 0.75, 0.6, 0.4, 10)
+                    .is_empty()
+            );
+        }
+
+        let other = TokenizerConfig {
+            use_stemming: true,
+            ..config
+        };
+        let err = SearchEngine::new(index_path, commit_path, snapshot_path, Some(other))
+            .err()
+            .expect("stemming change must be rejected");
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn dynamic_and_strict_mappings() {
+        let dir = tempdir().unwrap();
+
+        let index_path = dir.path().join("index.json").to_string_lossy().to_string();
+        let commit_path = dir.path().join("commits.log").to_string_lossy().to_string();
+        let snapshot_path = dir.path().join("snapshots").to_string_lossy().to_string();
+
+        let mut engine = SearchEngine::new(
+            index_path.clone(),
+            commit_path.clone(),
+            snapshot_path.clone(),
+            None,
+        )
+        .unwrap();
+
+        let mut doc = HashMap::new();
+        doc.insert("hp".to_string(), Value::Number(35.0));
+        engine.add_document(doc, None).unwrap();
+        assert_eq!(engine.settings().field_type("hp"), Some(FieldType::Number));
+
+        // "60" is coerced into the recorded number type, "lots" cannot be
+        let mut doc = HashMap::new();
+        doc.insert("hp".to_string(), Value::Text("60".into()));
+        let id = engine.add_document(doc, None).unwrap();
+        assert!(matches!(
+            engine.store().get_document(&id).unwrap().data["hp"],
+            Value::Integer(60)
+        ));
+
+        let mut doc = HashMap::new();
+        doc.insert("hp".to_string(), Value::Text("lots".into()));
+        assert!(engine.add_document(doc, None).is_err());
+        for not_a_number in ["NaN", "inf", "-infinity"] {
+            let mut doc = HashMap::new();
+            doc.insert("hp".to_string(), Value::Text(not_a_number.into()));
+            assert!(engine.add_document(doc, None).is_err(), "{}", not_a_number);
+        }
+        drop(engine);
+
+        // recorded mappings are persisted and enforced in strict mode
+        let settings = IndexSettings {
+            mapping_mode: MappingMode::Strict,
+            coerce: false,
+            ..Default::default()
+        };
+        let mut engine =
+            SearchEngine::open(index_path, commit_path, snapshot_path, Some(settings)).unwrap();
+        assert_eq!(engine.settings().field_type("hp"), Some(FieldType::Number));
+
+        let mut doc = HashMap::new();
+        doc.insert("name".to_string(), Value::Text("pikachu".into()));
+        let err = engine.add_document(doc, None).unwrap_err();
+        assert!(err.to_string().contains("not declared"));
+
+        let mut doc = HashMap::new();
+        doc.insert("hp".to_string(), Value::Text("60".into()));
+        assert!(engine.add_document(doc, None).is_err());
+    }
+
+    #[test]
+    fn declared_fields_persist_the_type_they_are_first_seen_with() {
+        let dir = tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+
+        // declared without a type: the first document adds no mapping, it
+        // only fills in the type of one
+        let settings = IndexSettings {
+            mappings: HashMap::from([(
+                "secret".to_string(),
+                FieldMapping {
+                    index: false,
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        let mut engine = SearchEngine::open(
+            path("index.json"),
+            path("commits.log"),
+            path("snapshots"),
+            Some(settings),
+        )
+        .unwrap();
+        let mut doc = HashMap::new();
+        doc.insert("secret".to_string(), Value::Text("hidden".into()));
+        engine.add_document(doc, None).unwrap();
+        drop(engine);
+
+        let engine = SearchEngine::open(
+            path("index.json"),
+            path("commits.log"),
+            path("snapshots"),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            engine.settings().field_type("secret"),
+            Some(FieldType::Text)
+        );
+    }
+
+    #[test]
+    fn date_strings_are_detected_and_range_queried() {
+        let dir = tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+
+        let mut engine = SearchEngine::new(
+            path("index.json"),
+            path("commits.log"),
+            path("snapshots"),
+            None,
+        )
+        .unwrap();
+
+        let mut ids = Vec::new();
+        for caught_at in [
+            "2024-01-02T10:00:00+02:00",
+            "Fri, 05 Jan 2024 09:30:00 +0000",
+        ] {
+            let mut doc = HashMap::new();
+            doc.insert("caught_at".to_string(), Value::Text(caught_at.into()));
+            ids.push(engine.add_document(doc, None).unwrap());
+        }
+        assert_eq!(
+            engine.settings().field_type("caught_at"),
+            Some(FieldType::Date)
+        );
+
+        let mut doc = HashMap::new();
+        doc.insert("caught_at".to_string(), Value::Text("last tuesday".into()));
+        assert!(engine.add_document(doc, None).is_err());
+
+        let qs = QueryService::new(engine.store());
+        assert_eq!(
+            qs.date_range("caught_at", Some("2024-01-02||/d"), Some("2024-01-03"))
+                .unwrap(),
+            vec![ids[0].clone()]
+        );
+        // the upper bound rounds up to the end of the day
+        assert_eq!(
+            qs.date_range("caught_at", Some("2024-01-03"), Some("2024-01-05||/d"))
+                .unwrap(),
+            vec![ids[1].clone()]
+        );
+        assert!(qs.date_range("caught_at", Some("now-7x"), None).is_err());
+    }
 }
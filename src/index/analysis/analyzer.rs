@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::index::analysis::char_filters::{CharFilter, CharFilterConfig};
+use crate::index::analysis::languages::{LANGUAGES, stop_words};
+use crate::index::analysis::synonyms::{Alternative, SynonymSet};
+use crate::index::analysis::token_filters::{TokenFilter, TokenFilterConfig};
+use crate::index::analysis::tokenizers::{TextTokenizer, TokenizerKind};
+use crate::index::keyword_index::path_matches;
+use crate::index::settings::IndexSettings;
+
+/// Turns a piece of text into the terms that are indexed or searched for.
+pub trait Analyzer: Send + Sync {
+    fn analyze(&self, text: &str) -> Vec<String>;
+}
+
+/// Char filters, then a tokenizer, then token filters, each in order.
+pub struct CustomAnalyzer {
+    char_filters: Vec<Box<dyn CharFilter>>,
+    tokenizer: Box<dyn TextTokenizer>,
+    filters: Vec<Box<dyn TokenFilter>>,
+}
+
+impl Analyzer for CustomAnalyzer {
+    fn analyze(&self, text: &str) -> Vec<String> {
+        let mut text = text.to_string();
+        for char_filter in &self.char_filters {
+            text = char_filter.filter(&text);
+        }
+
+        let mut tokens = self.tokenizer.tokenize(&text);
+        for filter in &self.filters {
+            tokens = filter.filter(tokens);
+        }
+        tokens
+    }
+}
+
+/// An analyzer as written in the index settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AnalyzerConfig {
+    #[serde(default)]
+    pub char_filters: Vec<CharFilterConfig>,
+    #[serde(default)]
+    pub tokenizer: TokenizerKind,
+    #[serde(default)]
+    pub filters: Vec<TokenFilterConfig>,
+}
+
+impl AnalyzerConfig {
+    pub fn build(&self) -> Result<CustomAnalyzer, String> {
+        Ok(CustomAnalyzer {
+            char_filters: self
+                .char_filters
+                .iter()
+                .map(CharFilterConfig::build)
+                .collect::<Result<_, _>>()?,
+            tokenizer: self.tokenizer.build()?,
+            filters: self
+                .filters
+                .iter()
+                .map(TokenFilterConfig::build)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Built-in analyzers besides the one per language in `LANGUAGES`.
+pub const BUILTIN_ANALYZERS: &[&str] = &["standard", "simple", "whitespace", "keyword", "stop"];
+
+pub fn is_builtin(name: &str) -> bool {
+    BUILTIN_ANALYZERS.contains(&name) || LANGUAGES.contains(&name)
+}
+
+/// Definition of a built-in analyzer. `standard` stems English words when the
+/// index has `use_stemming` set, as it always has. Language analyzers drop
+/// the language's bundled stop words (when there is a list) and stem.
+pub fn builtin_analyzer(name: &str, use_stemming: bool) -> Option<AnalyzerConfig> {
+    let letters = TokenizerKind::Pattern {
+        pattern: r"\P{L}+".to_string(),
+    };
+    let stemmer = |language: &str| TokenFilterConfig::Stemmer {
+        language: language.to_string(),
+    };
+    let stop = |language: &str| TokenFilterConfig::Stop {
+        words: Vec::new(),
+        language: language.to_string(),
+    };
+
+    let (tokenizer, filters) = match name {
+        "standard" if use_stemming => (
+            TokenizerKind::Standard,
+            vec![TokenFilterConfig::Lowercase, stemmer("english")],
+        ),
+        "standard" => (TokenizerKind::Standard, vec![TokenFilterConfig::Lowercase]),
+        "simple" => (letters, vec![TokenFilterConfig::Lowercase]),
+        "whitespace" => (TokenizerKind::Whitespace, Vec::new()),
+        "keyword" => (TokenizerKind::Keyword, Vec::new()),
+        "stop" => (letters, vec![TokenFilterConfig::Lowercase, stop("english")]),
+        language if LANGUAGES.contains(&language) => {
+            let mut filters = vec![TokenFilterConfig::Lowercase];
+            if stop_words(language).is_some() {
+                filters.push(stop(language));
+            }
+            filters.push(stemmer(language));
+            (TokenizerKind::Standard, filters)
+        }
+        _ => return None,
+    };
+
+    Some(AnalyzerConfig {
+        char_filters: Vec::new(),
+        tokenizer,
+        filters,
+    })
+}
+
+/// The analyzers an index can use, by name, and which one each field uses.
+#[derive(Clone)]
+pub struct AnalysisRegistry {
+    analyzers: HashMap<String, Arc<dyn Analyzer>>,
+    default: String,
+    // field path -> analyzer name, for fields that do not use the default
+    fields: HashMap<String, String>,
+    // query-time synonyms and the weight of a match on one of them
+    search_synonyms: Option<Arc<SearchSynonymSets>>,
+}
+
+// search synonym rules as written, and as each analyzer turns them into
+// terms, built the first time a query goes through that analyzer
+struct SearchSynonymSets {
+    rules: SynonymSet,
+    weight: f64,
+    analyzed: HashMap<String, OnceLock<SynonymSet>>,
+}
+
+impl AnalysisRegistry {
+    /// Build every built-in and custom analyzer and check that the names the
+    /// settings refer to exist.
+    pub fn from_settings(settings: &IndexSettings) -> Result<Self, String> {
+        let mut analyzers: HashMap<String, Arc<dyn Analyzer>> = HashMap::new();
+
+        for name in BUILTIN_ANALYZERS.iter().chain(LANGUAGES) {
+            let config = builtin_analyzer(name, settings.use_stemming)
+                .ok_or_else(|| format!("missing built-in analyzer '{}'", name))?;
+            analyzers.insert(name.to_string(), Arc::new(config.build()?));
+        }
+        for (name, config) in &settings.analyzers {
+            if is_builtin(name) {
+                return Err(format!(
+                    "analyzer '{}' is built in and cannot be redefined",
+                    name
+                ));
+            }
+            let analyzer = config
+                .build()
+                .map_err(|e| format!("analyzer '{}': {}", name, e))?;
+            analyzers.insert(name.clone(), Arc::new(analyzer));
+        }
+
+        let known = |name: &str| -> Result<(), String> {
+            if analyzers.contains_key(name) {
+                Ok(())
+            } else {
+                Err(format!("unknown analyzer '{}'", name))
+            }
+        };
+        known(&settings.analyzer)?;
+
+        let mut fields = HashMap::new();
+        for (field, mapping) in &settings.mappings {
+            let name = match (&mapping.analyzer, &mapping.language) {
+                (Some(_), Some(_)) => {
+                    return Err(format!(
+                        "field '{}': set either an analyzer or a language",
+                        field
+                    ));
+                }
+                (Some(name), None) => name,
+                (None, Some(language)) if LANGUAGES.contains(&language.as_str()) => language,
+                (None, Some(language)) => {
+                    return Err(format!(
+                        "field '{}': unsupported language '{}'",
+                        field, language
+                    ));
+                }
+                (None, None) => continue,
+            };
+            known(name).map_err(|e| format!("field '{}': {}", field, e))?;
+            fields.insert(field.clone(), name.clone());
+        }
+
+        let search_synonyms = match &settings.search_synonyms {
+            Some(synonyms) => {
+                if !(0.0..=1.0).contains(&synonyms.weight) {
+                    return Err(format!(
+                        "search_synonyms: weight {} is not between 0 and 1",
+                        synonyms.weight
+                    ));
+                }
+                let set = synonyms
+                    .rules
+                    .load()
+                    .map_err(|e| format!("search_synonyms: {}", e))?;
+                Some(Arc::new(SearchSynonymSets {
+                    rules: set,
+                    weight: synonyms.weight,
+                    analyzed: analyzers
+                        .keys()
+                        .map(|name| (name.clone(), OnceLock::new()))
+                        .collect(),
+                }))
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            analyzers,
+            default: settings.analyzer.clone(),
+            fields,
+            search_synonyms,
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Analyzer> {
+        self.analyzers.get(name).map(|a| a.as_ref())
+    }
+
+    pub fn default_analyzer(&self) -> Arc<dyn Analyzer> {
+        self.analyzers[&self.default].clone()
+    }
+
+    pub fn default_analyzer_name(&self) -> &str {
+        &self.default
+    }
+
+    /// Analyzer for the indexed field at `field_path` (exact path).
+    pub fn for_field(&self, field_path: &str) -> &dyn Analyzer {
+        let name = self.fields.get(field_path).unwrap_or(&self.default);
+        self.analyzers[name].as_ref()
+    }
+
+    /// Analyzer for `field_path` in a document detected to be written in
+    /// `language`: the field's own analyzer if it has one, otherwise the
+    /// language's, otherwise the default.
+    pub fn for_document_field(&self, field_path: &str, language: Option<&str>) -> &dyn Analyzer {
+        self.analyzers[self.document_field_analyzer(field_path, language)].as_ref()
+    }
+
+    /// Name of the analyzer `for_document_field` picks.
+    pub fn document_field_analyzer(&self, field_path: &str, language: Option<&str>) -> &str {
+        if let Some(name) = self.fields.get(field_path) {
+            return name;
+        }
+        language
+            .and_then(|language| self.analyzers.get_key_value(language))
+            .map(|(name, _)| name)
+            .unwrap_or(&self.default)
+    }
+
+    /// Whether a field named in a query has an analyzer or language mapped.
+    pub fn has_field_analyzer(&self, field: &str) -> bool {
+        self.fields.keys().any(|path| path_matches(path, field))
+    }
+
+    /// Analyzer for a field named in a query, which may be a dotted suffix of
+    /// the full path. The most specific mapped path wins.
+    pub fn for_query_field(&self, field: &str) -> &dyn Analyzer {
+        self.analyzers[self.query_field_analyzer(field)].as_ref()
+    }
+
+    /// Name of the analyzer `for_query_field` picks.
+    pub fn query_field_analyzer(&self, field: &str) -> &str {
+        self.fields
+            .iter()
+            .filter(|(path, _)| path_matches(path, field))
+            .max_by_key(|(path, _)| path.len())
+            .map(|(_, name)| name)
+            .unwrap_or(&self.default)
+    }
+
+    /// Group query terms, as the analyzer named `analyzer` produced them,
+    /// into spans that each match on any of their alternatives: the terms
+    /// themselves, plus the search synonyms. Both sides of every synonym
+    /// rule go through the same analyzer, so they meet stemmed terms.
+    pub fn expand_query(&self, analyzer: &str, terms: &[String]) -> Vec<Vec<Alternative>> {
+        match &self.search_synonyms {
+            Some(synonyms) => {
+                let set = match (synonyms.analyzed.get(analyzer), self.get(analyzer)) {
+                    (Some(set), Some(analyzer)) => {
+                        set.get_or_init(|| synonyms.rules.analyzed(analyzer))
+                    }
+                    _ => &synonyms.rules,
+                };
+                set.expand(terms, synonyms.weight)
+            }
+            None => terms
+                .iter()
+                .map(|term| {
+                    vec![Alternative {
+                        tokens: vec![term.clone()],
+                        weight: 1.0,
+                    }]
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Default for AnalysisRegistry {
+    fn default() -> Self {
+        Self::from_settings(&IndexSettings::default()).expect("built-in analyzers are valid")
+    }
+}
+
+impl fmt::Debug for AnalysisRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<&String> = self.analyzers.keys().collect();
+        names.sort();
+        f.debug_struct("AnalysisRegistry")
+            .field("analyzers", &names)
+            .field("default", &self.default)
+            .field("fields", &self.fields)
+            .field("search_synonyms", &self.search_synonyms.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::query_service::QueryService;
+    use crate::index::analysis::synonyms::SynonymConfig;
+    use crate::index::value::Value;
+    use crate::test_support::store_with;
+
+    #[test]
+    fn custom_pipelines_and_per_field_analyzers() {
+        let custom = AnalyzerConfig {
+            char_filters: vec![
+                CharFilterConfig::HtmlStrip,
+                CharFilterConfig::Mapping {
+                    mappings: vec!["ph => f".into()],
+                },
+            ],
+            tokenizer: TokenizerKind::Whitespace,
+            filters: vec![
+                TokenFilterConfig::Lowercase,
+                TokenFilterConfig::AsciiFolding,
+                TokenFilterConfig::Stop {
+                    words: Vec::new(),
+                    language: "english".into(),
+                },
+                TokenFilterConfig::Length { min: 2, max: 10 },
+                TokenFilterConfig::Synonym(SynonymConfig {
+                    synonyms: vec!["pokemon, pocket monster".into(), "mon => monster".into()],
+                    ..Default::default()
+                }),
+            ],
+        };
+        let analyzer = custom.build().unwrap();
+        assert_eq!(
+            analyzer.analyze("<b>The</b> Élite &amp; X Pokemon mon phantom"),
+            ["elite", "pokemon", "pocket", "monster", "monster", "fantom"]
+        );
+
+        let mut settings = IndexSettings::default();
+        settings.analyzers.insert("html".into(), custom);
+        settings.analyze_field("bio", "english");
+        settings.analyze_field("notes", "html");
+        assert!(settings.validate().is_ok());
+
+        let fields = vec![
+            ("bio", Value::Text("Running with the pokemon".into())),
+            ("title", Value::Text("Running late".into())),
+        ];
+        let store = store_with(settings.clone(), [("1", fields)]);
+        let qs = QueryService::new(&store);
+
+        // `bio` is stemmed and stop-word free, `title` uses the plain default
+        assert_eq!(qs.analyze(Some("bio"), "The runs"), ["run"]);
+        assert_eq!(qs.analyze(Some("title"), "The runs"), ["the", "runs"]);
+        assert_eq!(
+            qs.match_text("bio", "the runner runs"),
+            Vec::<String>::new()
+        );
+        assert_eq!(qs.match_text("bio", "runs"), ["1"]);
+        assert_eq!(qs.match_text("title", "running"), ["1"]);
+
+        settings.analyze_field("bio", "missing");
+        assert!(
+            settings
+                .validate()
+                .unwrap_err()
+                .contains("unknown analyzer")
+        );
+        settings
+            .analyzers
+            .insert("standard".into(), AnalyzerConfig::default());
+        assert!(settings.validate().is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+
+/// Rewrites the raw text before it is split into tokens.
+pub trait CharFilter: Send + Sync {
+    fn filter(&self, text: &str) -> String;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CharFilterConfig {
+    /// Drop HTML tags and decode character entities.
+    HtmlStrip,
+    /// Replace character sequences, one `"from => to"` rule per entry.
+    Mapping { mappings: Vec<String> },
+}
+
+impl CharFilterConfig {
+    pub fn build(&self) -> Result<Box<dyn CharFilter>, String> {
+        Ok(match self {
+            CharFilterConfig::HtmlStrip => Box::new(HtmlStrip),
+            CharFilterConfig::Mapping { mappings } => Box::new(MappingFilter::new(mappings)?),
+        })
+    }
+}
+
+pub struct HtmlStrip;
+
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let number = entity.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+impl CharFilter for HtmlStrip {
+    fn filter(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(pos) = rest.find(['<', '&']) {
+            out.push_str(&rest[..pos]);
+            rest = &rest[pos..];
+
+            if rest.starts_with('<') {
+                match rest.find('>') {
+                    // a tag separates words the way whitespace would
+                    Some(end) => {
+                        out.push(' ');
+                        rest = &rest[end + 1..];
+                    }
+                    None => {
+                        out.push_str(rest);
+                        rest = "";
+                    }
+                }
+            } else {
+                let entity = rest
+                    .find(';')
+                    .filter(|&end| end <= 10)
+                    .and_then(|end| decode_entity(&rest[1..end]).map(|c| (c, end)));
+                match entity {
+                    Some((c, end)) => {
+                        out.push(c);
+                        rest = &rest[end + 1..];
+                    }
+                    None => {
+                        out.push('&');
+                        rest = &rest[1..];
+                    }
+                }
+            }
+        }
+
+        out.push_str(rest);
+        out
+    }
+}
+
+/// Replaces every occurrence of a rule's left side by its right side; where
+/// several rules match at the same position the longest one wins.
+pub struct MappingFilter {
+    rules: Vec<(String, String)>,
+}
+
+impl MappingFilter {
+    pub fn new(mappings: &[String]) -> Result<Self, String> {
+        let mut rules = Vec::with_capacity(mappings.len());
+        for rule in mappings {
+            let (from, to) = rule
+                .split_once("=>")
+                .ok_or_else(|| format!("mapping '{}' must look like 'from => to'", rule))?;
+            let from = from.trim();
+            if from.is_empty() {
+                return Err(format!("mapping '{}' has nothing to replace", rule));
+            }
+            rules.push((from.to_string(), to.trim().to_string()));
+        }
+        rules.sort_by_key(|(from, _)| std::cmp::Reverse(from.len()));
+        Ok(Self { rules })
+    }
+}
+
+impl CharFilter for MappingFilter {
+    fn filter(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(c) = rest.chars().next() {
+            match self
+                .rules
+                .iter()
+                .find(|(from, _)| rest.starts_with(from.as_str()))
+            {
+                Some((from, to)) => {
+                    out.push_str(to);
+                    rest = &rest[from.len()..];
+                }
+                None => {
+                    out.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+
+        out
+    }
+}
@@ -0,0 +1,291 @@
+//! Languages with a Snowball stemmer, their bundled stop-word lists and
+//! document language detection.
+
+use whatlang::Lang;
+
+/// Every language that has a stemmer, and so a built-in analyzer of the same
+/// name.
+pub const LANGUAGES: &[&str] = &[
+    "arabic",
+    "danish",
+    "dutch",
+    "english",
+    "finnish",
+    "french",
+    "german",
+    "greek",
+    "hungarian",
+    "italian",
+    "norwegian",
+    "portuguese",
+    "romanian",
+    "russian",
+    "spanish",
+    "swedish",
+    "tamil",
+    "turkish",
+];
+
+pub const ENGLISH_STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "if", "in", "into", "is", "it",
+    "no", "not", "of", "on", "or", "such", "that", "the", "their", "then", "there", "these",
+    "they", "this", "to", "was", "will", "with",
+];
+
+const FRENCH_STOP_WORDS: &[&str] = &[
+    "au", "aux", "avec", "c", "ce", "ces", "d", "dans", "de", "des", "du", "elle", "en", "est",
+    "et", "été", "était", "eux", "il", "j", "je", "l", "la", "le", "les", "leur", "lui", "m", "ma",
+    "mais", "me", "même", "mes", "moi", "mon", "n", "ne", "nos", "notre", "nous", "on", "ou",
+    "par", "pas", "pour", "qu", "que", "qui", "s", "sa", "se", "ses", "son", "sont", "sur", "t",
+    "ta", "te", "tes", "toi", "ton", "tu", "un", "une", "vos", "votre", "vous", "y", "à",
+];
+
+const GERMAN_STOP_WORDS: &[&str] = &[
+    "aber", "alle", "als", "also", "am", "an", "auch", "auf", "aus", "bei", "bin", "bis", "bist",
+    "da", "damit", "dann", "das", "dass", "dein", "dem", "den", "der", "des", "die", "dies",
+    "doch", "du", "durch", "ein", "eine", "einem", "einen", "einer", "eines", "er", "es", "für",
+    "hat", "hatte", "ich", "ihr", "im", "in", "ist", "ja", "kein", "man", "mein", "mit", "nach",
+    "nicht", "noch", "nur", "ob", "oder", "sich", "sie", "sind", "so", "um", "und", "uns", "unter",
+    "vom", "von", "vor", "war", "was", "wie", "wir", "wird", "zu", "zum", "zur", "über",
+];
+
+const SPANISH_STOP_WORDS: &[&str] = &[
+    "a", "al", "algo", "como", "con", "de", "del", "el", "ella", "ellos", "en", "entre", "era",
+    "es", "esta", "este", "esto", "ha", "hay", "la", "las", "le", "les", "lo", "los", "me", "mi",
+    "muy", "más", "no", "nos", "o", "para", "pero", "por", "que", "se", "sin", "sobre", "su",
+    "sus", "también", "te", "tu", "un", "una", "uno", "y", "ya",
+];
+
+const ITALIAN_STOP_WORDS: &[&str] = &[
+    "a", "ad", "al", "alla", "alle", "anche", "che", "chi", "ci", "come", "con", "da", "dal",
+    "dalla", "degli", "dei", "del", "della", "delle", "di", "e", "gli", "ha", "ho", "i", "il",
+    "in", "io", "la", "le", "lei", "lo", "loro", "lui", "ma", "mi", "mio", "ne", "nel", "nella",
+    "noi", "non", "o", "per", "più", "quello", "questo", "se", "si", "sono", "su", "sua", "suo",
+    "tra", "tu", "un", "una", "uno", "è",
+];
+
+const PORTUGUESE_STOP_WORDS: &[&str] = &[
+    "a", "ao", "aos", "as", "com", "como", "da", "das", "de", "do", "dos", "e", "ela", "ele", "em",
+    "entre", "era", "foi", "isso", "mais", "mas", "me", "muito", "na", "nas", "no", "nos", "não",
+    "o", "os", "ou", "para", "pela", "pelo", "por", "que", "se", "sem", "seu", "sua", "são",
+    "também", "te", "um", "uma", "você", "é",
+];
+
+const DUTCH_STOP_WORDS: &[&str] = &[
+    "aan", "al", "als", "bij", "dat", "de", "den", "der", "deze", "die", "dit", "door", "een",
+    "en", "er", "had", "heb", "het", "hij", "hoe", "ik", "in", "is", "je", "kan", "maar", "me",
+    "met", "mij", "naar", "niet", "nog", "of", "om", "onder", "ook", "op", "over", "te", "tot",
+    "u", "uit", "van", "voor", "was", "wat", "we", "wel", "wie", "wij", "zal", "ze", "zich", "zij",
+    "zijn",
+];
+
+const SWEDISH_STOP_WORDS: &[&str] = &[
+    "alla", "att", "av", "de", "dem", "den", "denna", "det", "detta", "dig", "du", "där", "efter",
+    "eller", "en", "er", "ett", "från", "för", "han", "hon", "honom", "hur", "här", "i", "inte",
+    "jag", "kan", "man", "med", "mig", "min", "mot", "nu", "när", "och", "om", "på", "sig", "sin",
+    "som", "så", "till", "under", "upp", "ut", "var", "vi", "vid", "är",
+];
+
+const DANISH_STOP_WORDS: &[&str] = &[
+    "af", "alle", "at", "blev", "da", "de", "dem", "den", "denne", "der", "det", "dig", "du",
+    "efter", "eller", "en", "er", "et", "for", "fra", "han", "hende", "her", "hun", "hvad", "hvis",
+    "i", "ikke", "jeg", "kan", "man", "med", "mig", "min", "mod", "når", "og", "om", "på", "sig",
+    "sin", "som", "så", "til", "ud", "var", "vi", "være",
+];
+
+const NORWEGIAN_STOP_WORDS: &[&str] = &[
+    "alle", "at", "av", "da", "de", "dem", "den", "denne", "der", "det", "du", "eller", "en", "er",
+    "et", "etter", "for", "fra", "han", "hun", "hva", "hvis", "i", "ikke", "jeg", "kan", "man",
+    "med", "meg", "min", "mot", "når", "og", "om", "på", "seg", "sin", "som", "så", "til", "ut",
+    "var", "vi", "være",
+];
+
+const FINNISH_STOP_WORDS: &[&str] = &[
+    "ei", "ja", "jos", "kanssa", "kuin", "kun", "mutta", "ne", "niin", "nyt", "ole", "on", "ovat",
+    "se", "sekä", "siinä", "tai", "tämä", "että", "hän", "he", "me", "minä", "mikä", "mitä",
+    "myös", "olla", "oli", "sinä", "te", "vain",
+];
+
+const RUSSIAN_STOP_WORDS: &[&str] = &[
+    "а",
+    "без",
+    "бы",
+    "был",
+    "была",
+    "были",
+    "было",
+    "в",
+    "вам",
+    "вас",
+    "во",
+    "вот",
+    "все",
+    "вы",
+    "где",
+    "да",
+    "для",
+    "до",
+    "его",
+    "ее",
+    "если",
+    "есть",
+    "еще",
+    "же",
+    "за",
+    "и",
+    "из",
+    "или",
+    "им",
+    "их",
+    "к",
+    "как",
+    "ко",
+    "когда",
+    "кто",
+    "ли",
+    "мне",
+    "мы",
+    "на",
+    "нам",
+    "нас",
+    "не",
+    "нет",
+    "ни",
+    "но",
+    "о",
+    "об",
+    "он",
+    "она",
+    "они",
+    "оно",
+    "от",
+    "по",
+    "при",
+    "с",
+    "со",
+    "так",
+    "также",
+    "то",
+    "только",
+    "у",
+    "уже",
+    "что",
+    "это",
+    "я",
+];
+
+/// The bundled stop words of `language`, if there is a list for it.
+pub fn stop_words(language: &str) -> Option<&'static [&'static str]> {
+    Some(match language {
+        "english" => ENGLISH_STOP_WORDS,
+        "french" => FRENCH_STOP_WORDS,
+        "german" => GERMAN_STOP_WORDS,
+        "spanish" => SPANISH_STOP_WORDS,
+        "italian" => ITALIAN_STOP_WORDS,
+        "portuguese" => PORTUGUESE_STOP_WORDS,
+        "dutch" => DUTCH_STOP_WORDS,
+        "swedish" => SWEDISH_STOP_WORDS,
+        "danish" => DANISH_STOP_WORDS,
+        "norwegian" => NORWEGIAN_STOP_WORDS,
+        "finnish" => FINNISH_STOP_WORDS,
+        "russian" => RUSSIAN_STOP_WORDS,
+        _ => return None,
+    })
+}
+
+/// The supported language `text` is written in, when the detection is
+/// confident enough.
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let info = whatlang::detect(text).filter(|info| info.is_reliable())?;
+    Some(match info.lang() {
+        Lang::Ara => "arabic",
+        Lang::Dan => "danish",
+        Lang::Nld => "dutch",
+        Lang::Eng => "english",
+        Lang::Fin => "finnish",
+        Lang::Fra => "french",
+        Lang::Deu => "german",
+        Lang::Ell => "greek",
+        Lang::Hun => "hungarian",
+        Lang::Ita => "italian",
+        Lang::Nob => "norwegian",
+        Lang::Por => "portuguese",
+        Lang::Ron => "romanian",
+        Lang::Rus => "russian",
+        Lang::Spa => "spanish",
+        Lang::Swe => "swedish",
+        Lang::Tam => "tamil",
+        Lang::Tur => "turkish",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::query_service::QueryService;
+    use crate::index::documents_store::DocumentStore;
+    use crate::index::settings::IndexSettings;
+    use crate::test_support::text_doc;
+
+    #[test]
+    fn per_field_language_and_detected_document_language() {
+        let mut settings = IndexSettings::default();
+        settings.set_language("titre", "french");
+        let mut store = DocumentStore::with_settings(settings);
+        store.add_document("1", &text_doc("titre", "Les chanteurs de la ville"), None);
+        let qs = QueryService::new(&store);
+
+        assert_eq!(qs.analyze(Some("titre"), "Les chanteurs"), ["chanteur"]);
+        assert_eq!(qs.match_text("titre", "chanteur"), ["1"]);
+        assert!(qs.match_text("titre", "les").is_empty());
+
+        let mut settings = IndexSettings {
+            language_detection: true,
+            ..Default::default()
+        };
+        settings.set_language("sku", "english");
+        assert!(settings.validate().is_ok());
+        let mut store = DocumentStore::with_settings(settings);
+        store.add_document(
+            "fr",
+            &text_doc(
+                "description",
+                "Les chanteurs chantaient des chansons dans les rues de la ville",
+            ),
+            None,
+        );
+        store.add_document(
+            "de",
+            &text_doc(
+                "description",
+                "Die alten Häuser stehen seit vielen Jahren in der kleinen Stadt",
+            ),
+            None,
+        );
+        let qs = QueryService::new(&store);
+
+        assert_eq!(
+            store.forward_index.get("fr").unwrap().language.as_deref(),
+            Some("french")
+        );
+        assert_eq!(
+            store.forward_index.get("de").unwrap().language.as_deref(),
+            Some("german")
+        );
+        assert_eq!(qs.match_text("description", "chanteur"), ["fr"]);
+        assert_eq!(qs.match_text("description", "Haus"), ["de"]);
+
+        store.delete_index("de");
+        let qs = QueryService::new(&store);
+        assert!(qs.match_text("description", "Haus").is_empty());
+
+        let mut settings = IndexSettings::default();
+        settings.set_language("titre", "klingon");
+        assert!(
+            settings
+                .validate()
+                .unwrap_err()
+                .contains("unsupported language")
+        );
+    }
+}
@@ -0,0 +1,6 @@
+pub mod analyzer;
+pub mod char_filters;
+pub mod languages;
+pub mod synonyms;
+pub mod token_filters;
+pub mod tokenizers;
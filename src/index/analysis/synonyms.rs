@@ -0,0 +1,396 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::index::analysis::analyzer::Analyzer;
+use crate::index::analysis::token_filters::TokenFilter;
+
+/// Layout of a synonym file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SynonymFormat {
+    /// One rule per line: `a, b, c` (equivalent) or `a, b => c` (one-way).
+    #[default]
+    Solr,
+    /// WordNet prolog (`wn_s.pl`): words sharing a synset are equivalent.
+    Wordnet,
+}
+
+/// Synonym rules given inline, in a file, or both.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SynonymConfig {
+    /// Inline rules, always in Solr syntax.
+    #[serde(default)]
+    pub synonyms: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub synonyms_path: Option<String>,
+    #[serde(default)]
+    pub format: SynonymFormat,
+}
+
+impl SynonymConfig {
+    pub fn load(&self) -> Result<SynonymSet, String> {
+        let mut set = SynonymSet::default();
+        for rule in &self.synonyms {
+            set.add_solr_rule(rule)?;
+        }
+        if let Some(path) = &self.synonyms_path {
+            let text = fs::read_to_string(path)
+                .map_err(|e| format!("cannot read synonyms '{}': {}", path, e))?;
+            match self.format {
+                SynonymFormat::Solr => set.add_solr(&text)?,
+                SynonymFormat::Wordnet => set.add_wordnet(&text)?,
+            }
+        }
+        Ok(set)
+    }
+}
+
+fn default_synonym_weight() -> f64 {
+    0.5
+}
+
+/// Synonyms applied only to queries, so the rules can change without
+/// reindexing. Matches on a synonym score `weight` times an original match.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchSynonyms {
+    #[serde(flatten)]
+    pub rules: SynonymConfig,
+    #[serde(default = "default_synonym_weight")]
+    pub weight: f64,
+}
+
+/// One way to match a span of the query: its tokens and their score weight.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alternative {
+    pub tokens: Vec<String>,
+    pub weight: f64,
+}
+
+// a phrase is its lowercased words: "Electric Type" -> ["electric", "type"]
+fn phrase(text: &str) -> Vec<String> {
+    text.split_whitespace().map(str::to_lowercase).collect()
+}
+
+fn phrases(list: &str) -> Vec<Vec<String>> {
+    list.split(',')
+        .map(phrase)
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+/// Parsed synonym rules, keyed by the (possibly multi-word) phrase they
+/// rewrite.
+#[derive(Debug, Clone, Default)]
+pub struct SynonymSet {
+    // phrase -> phrases it is indexed as (itself included for equivalences)
+    rules: HashMap<Vec<String>, Vec<Vec<String>>>,
+    longest: usize,
+}
+
+impl SynonymSet {
+    fn add(&mut self, from: Vec<String>, to: &[Vec<String>]) {
+        self.longest = self.longest.max(from.len());
+        let targets = self.rules.entry(from).or_default();
+        for target in to {
+            if !targets.contains(target) {
+                targets.push(target.clone());
+            }
+        }
+    }
+
+    pub fn add_solr_rule(&mut self, rule: &str) -> Result<(), String> {
+        match rule.split_once("=>") {
+            Some((from, to)) => {
+                let (from, to) = (phrases(from), phrases(to));
+                if from.is_empty() || to.is_empty() {
+                    return Err(format!("synonym rule '{}' needs words on both sides", rule));
+                }
+                for phrase in from {
+                    self.add(phrase, &to);
+                }
+            }
+            None => {
+                let group = phrases(rule);
+                for phrase in &group {
+                    self.add(phrase.clone(), &group);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn add_solr(&mut self, text: &str) -> Result<(), String> {
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            self.add_solr_rule(line)
+                .map_err(|e| format!("line {}: {}", n + 1, e))?;
+        }
+        Ok(())
+    }
+
+    /// Read `s(synset_id, w_num, 'word', ss_type, sense, tag_count).` lines.
+    pub fn add_wordnet(&mut self, text: &str) -> Result<(), String> {
+        let mut synsets: BTreeMap<&str, Vec<Vec<String>>> = BTreeMap::new();
+
+        for (n, line) in text.lines().enumerate() {
+            let Some(args) = line.trim().strip_prefix("s(") else {
+                continue;
+            };
+            let word = Self::wordnet_word(args)
+                .ok_or_else(|| format!("line {}: malformed wordnet entry", n + 1))?;
+            let id = args.split(',').next().unwrap_or_default();
+            synsets.entry(id).or_default().push(phrase(&word));
+        }
+
+        for group in synsets.into_values() {
+            for phrase in &group {
+                self.add(phrase.clone(), &group);
+            }
+        }
+        Ok(())
+    }
+
+    // the quoted third argument, where '' stands for an apostrophe
+    fn wordnet_word(args: &str) -> Option<String> {
+        let start = args.find('\'')?;
+        let mut chars = args[start + 1..].chars().peekable();
+        let mut word = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '\'' if chars.peek() == Some(&'\'') => {
+                    chars.next();
+                    word.push('\'');
+                }
+                '\'' => return Some(word),
+                c => word.push(c),
+            }
+        }
+        None
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The same rules with every phrase on either side run through
+    /// `analyzer`, so they are written in the terms it produces. Phrases it
+    /// drops entirely, such as stop words, are left out.
+    pub fn analyzed(&self, analyzer: &dyn Analyzer) -> SynonymSet {
+        let analyze = |phrase: &[String]| analyzer.analyze(&phrase.join(" "));
+        let mut set = SynonymSet::default();
+        for (from, targets) in &self.rules {
+            let from = analyze(from);
+            let targets: Vec<Vec<String>> = targets
+                .iter()
+                .map(|target| analyze(target))
+                .filter(|target| !target.is_empty())
+                .collect();
+            if !from.is_empty() && !targets.is_empty() {
+                set.add(from, &targets);
+            }
+        }
+        set
+    }
+
+    // the longest rule phrase starting at `tokens[0]`
+    fn longest_match(&self, tokens: &[String]) -> Option<(usize, &Vec<Vec<String>>)> {
+        (1..=self.longest.min(tokens.len()))
+            .rev()
+            .find_map(|n| self.rules.get(&tokens[..n]).map(|targets| (n, targets)))
+    }
+
+    /// Split query tokens into spans, each with the ways it can match: the
+    /// original tokens at weight 1, then every synonym at `weight`.
+    pub fn expand(&self, tokens: &[String], weight: f64) -> Vec<Vec<Alternative>> {
+        let mut spans = Vec::new();
+        let mut i = 0;
+
+        while i < tokens.len() {
+            let (len, targets) = match self.longest_match(&tokens[i..]) {
+                Some((len, targets)) => (len, targets.as_slice()),
+                None => (1, &[][..]),
+            };
+            let original = tokens[i..i + len].to_vec();
+
+            let mut span = vec![Alternative {
+                tokens: original.clone(),
+                weight: 1.0,
+            }];
+            span.extend(
+                targets
+                    .iter()
+                    .filter(|target| **target != original)
+                    .map(|target| Alternative {
+                        tokens: target.clone(),
+                        weight,
+                    }),
+            );
+            spans.push(span);
+            i += len;
+        }
+
+        spans
+    }
+}
+
+/// Index-time synonyms: a matched phrase is replaced by the words of every
+/// phrase it maps to, which for an equivalence includes itself.
+pub struct SynonymFilter {
+    set: SynonymSet,
+}
+
+impl SynonymFilter {
+    pub fn new(set: SynonymSet) -> Self {
+        Self { set }
+    }
+}
+
+impl TokenFilter for SynonymFilter {
+    fn filter(&self, tokens: Vec<String>) -> Vec<String> {
+        let mut out = Vec::with_capacity(tokens.len());
+        let mut i = 0;
+
+        while i < tokens.len() {
+            match self.set.longest_match(&tokens[i..]) {
+                Some((len, targets)) => {
+                    out.extend(targets.iter().flatten().cloned());
+                    i += len;
+                }
+                None => {
+                    out.push(tokens[i].clone());
+                    i += 1;
+                }
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::engine::query_service::QueryService;
+    use crate::index::settings::IndexSettings;
+    use crate::test_support::text_store;
+
+    fn words(text: &str) -> Vec<String> {
+        text.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn synonym_files_and_query_time_expansion() {
+        let dir = tempdir().unwrap();
+        let solr = dir.path().join("synonyms.txt");
+        fs::write(
+            &solr,
+            "# pokemon slang\n\nelectric type, thunder\nmon => monster\n",
+        )
+        .unwrap();
+        let wordnet = dir.path().join("wn_s.pl");
+        fs::write(
+            &wordnet,
+            "s(100001,1,'rock',n,1,0).\ns(100001,2,'stone',n,1,0).\ns(100002,1,'o''clock',n,1,0).\n",
+        )
+        .unwrap();
+
+        let filter = SynonymFilter::new(
+            SynonymConfig {
+                synonyms_path: Some(solr.to_string_lossy().into()),
+                ..Default::default()
+            }
+            .load()
+            .unwrap(),
+        );
+        assert_eq!(
+            filter.filter(words("an electric type mon")),
+            ["an", "electric", "type", "thunder", "monster"]
+        );
+        let set = SynonymConfig {
+            synonyms_path: Some(wordnet.to_string_lossy().into()),
+            format: SynonymFormat::Wordnet,
+            ..Default::default()
+        }
+        .load()
+        .unwrap();
+        assert_eq!(set.expand(&words("rock"), 0.5)[0][1].tokens, ["stone"]);
+        assert!(set.rules.contains_key(&words("o'clock")));
+
+        // the index knows nothing of the synonyms, only the queries do
+        let mut settings = IndexSettings {
+            search_synonyms: Some(SearchSynonyms {
+                rules: SynonymConfig {
+                    synonyms: vec!["electric type, thunder".into()],
+                    ..Default::default()
+                },
+                weight: 0.5,
+            }),
+            ..Default::default()
+        };
+        assert!(settings.validate().is_ok());
+        let store = text_store(
+            settings.clone(),
+            "description",
+            [("1", "thunder stone"), ("2", "an electric type pokemon")],
+        );
+        let qs = QueryService::new(&store);
+
+        let mut ids = qs.match_text("description", "thunder");
+        ids.sort();
+        assert_eq!(ids, ["1", "2"]);
+        assert_eq!(qs.match_text("description", "electric type stone"), ["1"]);
+
+        let ranked = qs.search("thunder", 1.2, 0.75, 10);
+        assert_eq!(ranked.len(), 2);
+        assert_eq!(ranked[0].0, "1");
+        assert!(ranked[1].1 < ranked[0].1);
+
+        settings.search_synonyms.as_mut().unwrap().weight = 2.0;
+        assert!(settings.validate().unwrap_err().contains("weight"));
+    }
+
+    #[test]
+    fn search_synonyms_meet_stemmed_terms() {
+        let settings = IndexSettings {
+            use_stemming: true,
+            search_synonyms: Some(SearchSynonyms {
+                rules: SynonymConfig {
+                    synonyms: vec!["electric type, thunder".into()],
+                    ..Default::default()
+                },
+                weight: 0.5,
+            }),
+            ..Default::default()
+        };
+        let store = text_store(
+            settings,
+            "description",
+            [("1", "thunder stone"), ("2", "an electric type pokemon")],
+        );
+        let qs = QueryService::new(&store);
+
+        // "electric" is indexed as "electr": the rule has to be stemmed too
+        let mut ids = qs.match_text("description", "thunder");
+        ids.sort();
+        assert_eq!(ids, ["1", "2"]);
+        let mut ids = qs.match_text("description", "electrical types");
+        ids.sort();
+        assert_eq!(ids, ["1", "2"]);
+
+        let full = qs.search("electric type", 1.2, 0.75, 10);
+        let ranked = qs.search("thunder", 1.2, 0.75, 10);
+        assert_eq!(ranked.len(), 2);
+        let synonym = ranked.iter().find(|(id, _)| id == "2").unwrap().1;
+        let direct = full.iter().find(|(id, _)| id == "2").unwrap().1;
+        assert!((synonym - direct * 0.5).abs() < 1e-9);
+    }
+}
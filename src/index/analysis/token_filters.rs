@@ -0,0 +1,192 @@
+use std::collections::HashSet;
+
+use rust_stemmers::{Algorithm, Stemmer};
+use serde::{Deserialize, Serialize};
+
+use crate::index::analysis::languages::stop_words;
+use crate::index::analysis::synonyms::{SynonymConfig, SynonymFilter};
+
+/// Transforms, drops or adds tokens after the tokenizer has run.
+pub trait TokenFilter: Send + Sync {
+    fn filter(&self, tokens: Vec<String>) -> Vec<String>;
+}
+
+fn default_language() -> String {
+    "english".to_string()
+}
+
+fn default_max_length() -> usize {
+    usize::MAX
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TokenFilterConfig {
+    Lowercase,
+    /// Drop the listed words, or the bundled stop words of `language` when
+    /// none are given.
+    Stop {
+        #[serde(default)]
+        words: Vec<String>,
+        #[serde(default = "default_language")]
+        language: String,
+    },
+    /// Snowball stemmer for `language` (e.g. `english`, `french`, `german`).
+    Stemmer {
+        #[serde(default = "default_language")]
+        language: String,
+    },
+    /// Solr-style rules: `"a, b, c"` makes the phrases equivalent,
+    /// `"a, b => c"` replaces `a` and `b` by `c`. More rules can be read from
+    /// `synonyms_path`, in Solr or WordNet format.
+    Synonym(SynonymConfig),
+    /// Fold accented Latin letters to ASCII (`é` -> `e`, `ß` -> `ss`).
+    AsciiFolding,
+    /// Keep tokens of `min..=max` characters.
+    Length {
+        #[serde(default)]
+        min: usize,
+        #[serde(default = "default_max_length")]
+        max: usize,
+    },
+}
+
+impl TokenFilterConfig {
+    pub fn build(&self) -> Result<Box<dyn TokenFilter>, String> {
+        Ok(match self {
+            TokenFilterConfig::Lowercase => Box::new(LowercaseFilter),
+            TokenFilterConfig::Stop { words, language } if words.is_empty() => {
+                let words = stop_words(language)
+                    .ok_or_else(|| format!("no stop words bundled for '{}'", language))?;
+                Box::new(StopFilter::new(words.iter().copied()))
+            }
+            TokenFilterConfig::Stop { words, .. } => {
+                Box::new(StopFilter::new(words.iter().map(String::as_str)))
+            }
+            TokenFilterConfig::Stemmer { language } => Box::new(StemmerFilter::new(language)?),
+            TokenFilterConfig::Synonym(config) => Box::new(SynonymFilter::new(config.load()?)),
+            TokenFilterConfig::AsciiFolding => Box::new(AsciiFoldingFilter),
+            TokenFilterConfig::Length { min, max } => {
+                if min > max {
+                    return Err(format!("length filter: min {} is above max {}", min, max));
+                }
+                Box::new(LengthFilter {
+                    min: *min,
+                    max: *max,
+                })
+            }
+        })
+    }
+}
+
+pub struct LowercaseFilter;
+
+impl TokenFilter for LowercaseFilter {
+    fn filter(&self, tokens: Vec<String>) -> Vec<String> {
+        tokens.into_iter().map(|t| t.to_lowercase()).collect()
+    }
+}
+
+pub struct StopFilter {
+    words: HashSet<String>,
+}
+
+impl StopFilter {
+    pub fn new<'w>(words: impl IntoIterator<Item = &'w str>) -> Self {
+        Self {
+            words: words.into_iter().map(str::to_lowercase).collect(),
+        }
+    }
+}
+
+impl TokenFilter for StopFilter {
+    fn filter(&self, mut tokens: Vec<String>) -> Vec<String> {
+        tokens.retain(|t| !self.words.contains(t));
+        tokens
+    }
+}
+
+/// Snowball algorithm for a language name.
+pub fn stemmer_algorithm(language: &str) -> Result<Algorithm, String> {
+    Ok(match language.to_lowercase().as_str() {
+        "arabic" => Algorithm::Arabic,
+        "danish" => Algorithm::Danish,
+        "dutch" => Algorithm::Dutch,
+        "english" => Algorithm::English,
+        "finnish" => Algorithm::Finnish,
+        "french" => Algorithm::French,
+        "german" => Algorithm::German,
+        "greek" => Algorithm::Greek,
+        "hungarian" => Algorithm::Hungarian,
+        "italian" => Algorithm::Italian,
+        "norwegian" => Algorithm::Norwegian,
+        "portuguese" => Algorithm::Portuguese,
+        "romanian" => Algorithm::Romanian,
+        "russian" => Algorithm::Russian,
+        "spanish" => Algorithm::Spanish,
+        "swedish" => Algorithm::Swedish,
+        "tamil" => Algorithm::Tamil,
+        "turkish" => Algorithm::Turkish,
+        other => return Err(format!("no stemmer for language '{}'", other)),
+    })
+}
+
+pub struct StemmerFilter {
+    stemmer: Stemmer,
+}
+
+impl StemmerFilter {
+    pub fn new(language: &str) -> Result<Self, String> {
+        Ok(Self {
+            stemmer: Stemmer::create(stemmer_algorithm(language)?),
+        })
+    }
+}
+
+impl TokenFilter for StemmerFilter {
+    fn filter(&self, tokens: Vec<String>) -> Vec<String> {
+        tokens
+            .into_iter()
+            .map(|t| self.stemmer.stem(&t).into_owned())
+            .collect()
+    }
+}
+
+pub struct AsciiFoldingFilter;
+
+fn is_latin(c: char) -> bool {
+    matches!(c, '\u{00C0}'..='\u{024F}' | '\u{1E00}'..='\u{1EFF}')
+}
+
+impl TokenFilter for AsciiFoldingFilter {
+    fn filter(&self, tokens: Vec<String>) -> Vec<String> {
+        tokens
+            .into_iter()
+            .map(|token| {
+                if token.is_ascii() {
+                    return token;
+                }
+                let mut folded = String::with_capacity(token.len());
+                for c in token.chars() {
+                    match deunicode::deunicode_char(c) {
+                        Some(ascii) if is_latin(c) => folded.push_str(ascii),
+                        _ => folded.push(c),
+                    }
+                }
+                folded
+            })
+            .collect()
+    }
+}
+
+pub struct LengthFilter {
+    min: usize,
+    max: usize,
+}
+
+impl TokenFilter for LengthFilter {
+    fn filter(&self, mut tokens: Vec<String>) -> Vec<String> {
+        tokens.retain(|t| (self.min..=self.max).contains(&t.chars().count()));
+        tokens
+    }
+}
@@ -0,0 +1,165 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Splits (filtered) text into tokens.
+pub trait TextTokenizer: Send + Sync {
+    fn tokenize(&self, text: &str) -> Vec<String>;
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TokenizerKind {
+    /// Words by the Unicode word boundary rules (UAX #29), in any script.
+    #[default]
+    Standard,
+    Whitespace,
+    /// The whole text as a single token.
+    Keyword,
+    /// Split wherever the regular expression matches.
+    Pattern {
+        pattern: String,
+    },
+}
+
+impl TokenizerKind {
+    pub fn build(&self) -> Result<Box<dyn TextTokenizer>, String> {
+        Ok(match self {
+            TokenizerKind::Standard => Box::new(StandardTokenizer),
+            TokenizerKind::Whitespace => Box::new(WhitespaceTokenizer),
+            TokenizerKind::Keyword => Box::new(KeywordTokenizer),
+            TokenizerKind::Pattern { pattern } => Box::new(PatternTokenizer::new(pattern)?),
+        })
+    }
+}
+
+/// Splits on UAX #29 word boundaries and keeps the segments that contain a
+/// letter or digit, so "pokémon", "don't" and "3.5" stay whole, and
+/// ideographs ("東京") become one token each.
+pub struct StandardTokenizer;
+
+impl TextTokenizer for StandardTokenizer {
+    fn tokenize(&self, text: &str) -> Vec<String> {
+        text.unicode_words().map(str::to_string).collect()
+    }
+}
+
+pub struct WhitespaceTokenizer;
+
+impl TextTokenizer for WhitespaceTokenizer {
+    fn tokenize(&self, text: &str) -> Vec<String> {
+        text.split_whitespace().map(str::to_string).collect()
+    }
+}
+
+pub struct KeywordTokenizer;
+
+impl TextTokenizer for KeywordTokenizer {
+    fn tokenize(&self, text: &str) -> Vec<String> {
+        let text = text.trim();
+        if text.is_empty() {
+            Vec::new()
+        } else {
+            vec![text.to_string()]
+        }
+    }
+}
+
+pub struct PatternTokenizer {
+    separator: Regex,
+}
+
+impl PatternTokenizer {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let separator =
+            Regex::new(pattern).map_err(|e| format!("invalid tokenizer pattern: {}", e))?;
+        Ok(Self { separator })
+    }
+}
+
+impl TextTokenizer for PatternTokenizer {
+    fn tokenize(&self, text: &str) -> Vec<String> {
+        self.separator
+            .split(text)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::query_service::QueryService;
+    use crate::index::analysis::analyzer::{Analyzer, AnalyzerConfig};
+    use crate::index::analysis::token_filters::TokenFilterConfig;
+    use crate::index::settings::IndexSettings;
+    use crate::index::tokenizer::tokenizer::{Tokenizer, TokenizerConfig};
+    use crate::language::language::run_query;
+    use crate::test_support::text_store;
+
+    #[test]
+    fn standard_tokenizer_segments_every_script() {
+        let fixtures = [
+            ("Pokémon Évolution!", vec!["Pokémon", "Évolution"]),
+            (
+                "Die Straße, don't panic: 3.5",
+                vec!["Die", "Straße", "don't", "panic", "3.5"],
+            ),
+            ("東京タワー", vec!["東", "京", "タワー"]),
+            ("Пикачу и Бульбазавр", vec!["Пикачу", "и", "Бульбазавр"]),
+            ("Πίκατσου — ピカチュウ", vec!["Πίκατσου", "ピカチュウ"]),
+        ];
+        for (text, expected) in fixtures {
+            assert_eq!(StandardTokenizer.tokenize(text), expected, "{}", text);
+        }
+
+        let folding = AnalyzerConfig {
+            filters: vec![
+                TokenFilterConfig::Lowercase,
+                TokenFilterConfig::AsciiFolding,
+            ],
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        assert_eq!(
+            folding.analyze("Pokémon Straße Ærø 東京"),
+            ["pokemon", "strasse", "aero", "東", "京"]
+        );
+
+        // n-gram sizes count characters, a 4-gram of "été" does not exist
+        let tokenizer = Tokenizer::new(TokenizerConfig {
+            use_stemming: false,
+            min_ngram: Some(2),
+            max_ngram: Some(4),
+        });
+        let (_, grams) = tokenizer.tokenize("été", true);
+        assert_eq!(grams.unwrap()[0].ngrams, ["ét", "té", "été"]);
+    }
+
+    #[test]
+    fn non_ascii_documents_are_searchable_and_deletable() {
+        let settings = IndexSettings {
+            min_ngram: Some(2),
+            max_ngram: Some(3),
+            ..Default::default()
+        };
+        let mut store = text_store(
+            settings,
+            "name",
+            [("1", "Pokémon"), ("2", "東京"), ("3", "Пикачу")],
+        );
+
+        let qs = QueryService::new(&store);
+        assert_eq!(run_query("name = pokémon", &qs).unwrap(), ["1"]);
+        assert_eq!(run_query("name = \"京\"", &qs).unwrap(), ["2"]);
+        assert_eq!(run_query("name = ПИКАЧУ", &qs).unwrap(), ["3"]);
+        let fuzzy = qs.ngram_bm25("пикач", 1.2, 0.75, 0.6, 0.4, 5);
+        assert_eq!(fuzzy[0].0, "3");
+
+        store.delete_index("3");
+        let qs = QueryService::new(&store);
+        assert!(run_query("name = пикачу", &qs).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::index::keyword_index::path_matches;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct BoolPostings {
+    true_docs: HashSet<String>,
+    false_docs: HashSet<String>,
+}
+
+impl BoolPostings {
+    fn docs(&self, value: bool) -> &HashSet<String> {
+        if value {
+            &self.true_docs
+        } else {
+            &self.false_docs
+        }
+    }
+
+    fn docs_mut(&mut self, value: bool) -> &mut HashSet<String> {
+        if value {
+            &mut self.true_docs
+        } else {
+            &mut self.false_docs
+        }
+    }
+}
+
+/// Filter index for boolean fields: field_path -> docs holding true / false.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct BoolIndex {
+    fields: HashMap<String, BoolPostings>,
+}
+
+impl BoolIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, field_path: &str, value: bool, doc_id: &str) {
+        self.fields
+            .entry(field_path.to_string())
+            .or_default()
+            .docs_mut(value)
+            .insert(doc_id.to_string());
+    }
+
+    pub fn remove(&mut self, field_path: &str, value: bool, doc_id: &str) {
+        if let Some(postings) = self.fields.get_mut(field_path) {
+            postings.docs_mut(value).remove(doc_id);
+            if postings.true_docs.is_empty() && postings.false_docs.is_empty() {
+                self.fields.remove(field_path);
+            }
+        }
+    }
+
+    /// Documents where `field` (full path or dotted suffix) equals `value`.
+    pub fn query(&self, field: &str, value: bool) -> Vec<String> {
+        let mut result: HashSet<&String> = HashSet::new();
+
+        for (path, postings) in &self.fields {
+            if path_matches(path, field) {
+                result.extend(postings.docs(value));
+            }
+        }
+
+        result.into_iter().cloned().collect()
+    }
+}
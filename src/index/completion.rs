@@ -0,0 +1,233 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::OnceLock;
+
+use fst::automaton::{Levenshtein, Str};
+use fst::{Automaton, IntoStreamer, Map, MapBuilder, Streamer};
+use serde::{Deserialize, Serialize};
+
+use crate::index::keyword_index::path_matches;
+
+/// Largest edit distance a fuzzy completion accepts.
+pub const MAX_FUZZINESS: u32 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub text: String,
+    /// Sum of the weights of the documents the suggestion comes from.
+    pub weight: f64,
+    pub doc_ids: Vec<String>,
+}
+
+/// Completion inputs of one field, with the FST over them.
+#[derive(Default, Clone, Serialize, Deserialize)]
+struct CompletionField {
+    // input -> doc id -> weight the document gives it
+    inputs: BTreeMap<String, HashMap<String, f64>>,
+    // input -> total weight (as f64 bits), built on the first lookup after a
+    // change
+    #[serde(skip)]
+    fst: OnceLock<Map<Vec<u8>>>,
+}
+
+impl CompletionField {
+    fn fst(&self) -> &Map<Vec<u8>> {
+        self.fst.get_or_init(|| {
+            let mut builder = MapBuilder::memory();
+            for (input, docs) in &self.inputs {
+                let weight: f64 = docs.values().sum();
+                builder
+                    .insert(input, weight.to_bits())
+                    .expect("a BTreeMap yields sorted, distinct keys");
+            }
+            builder.into_map()
+        })
+    }
+
+    // every input the automaton accepts, with its total weight
+    fn matches<A: Automaton>(&self, automaton: A) -> Vec<(String, f64)> {
+        let mut stream = self.fst().search(automaton).into_stream();
+        let mut found = Vec::new();
+        while let Some((key, weight)) = stream.next() {
+            found.push((
+                String::from_utf8_lossy(key).into_owned(),
+                f64::from_bits(weight),
+            ));
+        }
+        found
+    }
+}
+
+impl fmt::Debug for CompletionField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompletionField")
+            .field("inputs", &self.inputs.len())
+            .finish()
+    }
+}
+
+/// Completion suggester: per field, the analysed terms of its values in an
+/// FST, each weighted by the documents it appears in.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct CompletionIndex {
+    fields: HashMap<String, CompletionField>,
+}
+
+impl CompletionIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, field_path: &str, inputs: &[String], doc_id: &str, weight: f64) {
+        let field = self.fields.entry(field_path.to_string()).or_default();
+        for input in inputs {
+            field
+                .inputs
+                .entry(input.clone())
+                .or_default()
+                .insert(doc_id.to_string(), weight);
+        }
+        field.fst.take();
+    }
+
+    pub fn remove(&mut self, field_path: &str, inputs: &[String], doc_id: &str) {
+        let Some(field) = self.fields.get_mut(field_path) else {
+            return;
+        };
+        for input in inputs {
+            if let Some(docs) = field.inputs.get_mut(input) {
+                docs.remove(doc_id);
+                if docs.is_empty() {
+                    field.inputs.remove(input);
+                }
+            }
+        }
+        field.fst.take();
+        if field.inputs.is_empty() {
+            self.fields.remove(field_path);
+        }
+    }
+
+    /// Top `size` completions of `prefix` for `field` (full path or dotted
+    /// suffix). With `fuzziness` > 0 the prefix may be up to that many edits
+    /// away; exact prefix matches still rank first, then heavier ones.
+    pub fn suggest(
+        &self,
+        field: &str,
+        prefix: &str,
+        fuzziness: u32,
+        size: usize,
+    ) -> Result<Vec<Suggestion>, String> {
+        if fuzziness > MAX_FUZZINESS {
+            return Err(format!(
+                "fuzziness {} is above the maximum of {}",
+                fuzziness, MAX_FUZZINESS
+            ));
+        }
+        let prefix = prefix.to_lowercase();
+        let mut found: HashMap<String, Suggestion> = HashMap::new();
+
+        for (path, completion) in &self.fields {
+            if !path_matches(path, field) {
+                continue;
+            }
+            let matches = if fuzziness == 0 {
+                completion.matches(Str::new(&prefix).starts_with())
+            } else {
+                let automaton = Levenshtein::new(&prefix, fuzziness)
+                    .map_err(|e| format!("cannot complete '{}': {}", prefix, e))?;
+                completion.matches(automaton.starts_with())
+            };
+
+            for (text, weight) in matches {
+                let suggestion = found.entry(text.clone()).or_insert_with(|| Suggestion {
+                    text: text.clone(),
+                    weight: 0.0,
+                    doc_ids: Vec::new(),
+                });
+                suggestion.weight += weight;
+                suggestion
+                    .doc_ids
+                    .extend(completion.inputs[&text].keys().cloned());
+            }
+        }
+
+        let mut suggestions: Vec<Suggestion> = found.into_values().collect();
+        for suggestion in &mut suggestions {
+            suggestion.doc_ids.sort();
+            suggestion.doc_ids.dedup();
+        }
+        suggestions.sort_by(|a, b| {
+            b.text
+                .starts_with(&prefix)
+                .cmp(&a.text.starts_with(&prefix))
+                .then_with(|| b.weight.total_cmp(&a.weight))
+                .then_with(|| a.text.cmp(&b.text))
+        });
+        suggestions.truncate(size);
+        Ok(suggestions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::query_service::QueryService;
+    use crate::index::documents_store::DocumentStore;
+    use crate::index::settings::IndexSettings;
+    use crate::index::value::Value;
+    use crate::test_support::store_with;
+
+    #[test]
+    fn weighted_fuzzy_completions_with_doc_payloads() {
+        let mut settings = IndexSettings::default();
+        settings.complete_field("name", Some("popularity"));
+        assert!(settings.validate().is_ok());
+
+        let mut store = store_with(
+            settings,
+            [
+                ("1", "Pikachu", 90.0),
+                ("2", "Pichu", 40.0),
+                ("3", "Pikachu Libre", 20.0),
+                ("4", "Pidgey", 10.0),
+            ]
+            .map(|(id, name, popularity)| {
+                let fields = vec![
+                    ("name", Value::Text(name.into())),
+                    ("popularity", Value::Number(popularity)),
+                    ("note", Value::Text("pikablu".into())),
+                ];
+                (id, fields)
+            }),
+        );
+        let qs = QueryService::new(&store);
+
+        let top = qs.complete("name", "Pi", 0, 2).unwrap();
+        assert_eq!(top[0].text, "pikachu");
+        assert_eq!(top[0].weight, 110.0);
+        assert_eq!(top[0].doc_ids, ["1", "3"]);
+        assert_eq!(top[1].text, "pichu");
+
+        // "pikc" is one edit from the prefixes "pika" and "pich"
+        let fuzzy: Vec<String> = qs
+            .complete("name", "pikc", 1, 10)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.text)
+            .collect();
+        assert_eq!(fuzzy, ["pikachu", "pichu"]);
+        assert!(qs.complete("name", "pi", 3, 10).is_err());
+        assert!(qs.complete("note", "pi", 0, 10).unwrap().is_empty());
+
+        store.delete_index("1");
+        let mut restored = DocumentStore::with_settings(store.settings.clone());
+        restored.load_snapshot(store.to_snapshot());
+        let qs = QueryService::new(&restored);
+        let top = qs.complete("name", "pika", 0, 5).unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!(
+            (top[0].weight, top[0].doc_ids.clone()),
+            (20.0, vec!["3".to_string()])
+        );
+    }
+}
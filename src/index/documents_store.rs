@@ -1,13 +1,20 @@
+use crate::index::analysis::analyzer::AnalysisRegistry;
+use crate::index::analysis::languages::detect_language;
+use crate::index::bool_index::BoolIndex;
+use crate::index::completion::CompletionIndex;
 use crate::index::documents_store;
 use crate::index::forward_indexer::{ForwardDoc, ForwardIndex};
+use crate::index::geo_index::GeoIndex;
 use crate::index::inverted_index::inverted_index::InvertedIndex;
-use crate::index::n_gram::n_gram_index::NgramIndex;
+use crate::index::keyword_index::{KeywordIndex, path_matches};
+use crate::index::n_gram::n_gram_store::NgramStore;
 use crate::index::n_gram::n_gram_trie::NgramTrie;
+use crate::index::settings::{FieldType, IndexSettings};
 use crate::index::tokenizer::tokenizer::{Tokenizer, TokenizerConfig};
 use crate::index::value::Value;
 use crate::index::value_tree::b_tree::ValueTreeIndex;
 use crate::snapshots::snapshot_manager::Snapshot;
-use crate::utils::date_normalizer::{self, normalize_date};
+use crate::utils::date_normalizer::format_day;
 use crate::utils::random_id::random_id;
 use serde::{Deserialize, Serialize};
 use std::collections::{BinaryHeap, HashMap, HashSet};
@@ -29,47 +36,165 @@ pub struct Document {
     pub data: HashMap<String, Value>,
 }
 
+/// Hidden sub-documents for one nested field: each array element is a
+/// document of its own in `store`, keyed `<parent>#<path>#<index>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NestedBlock {
+    pub store: DocumentStore,
+    // sub-document id -> parent document id
+    pub parents: HashMap<String, String>,
+    // parent document id -> its sub-document ids, rebuilt from `parents`
+    #[serde(skip)]
+    children: HashMap<String, Vec<String>>,
+}
+
+impl NestedBlock {
+    fn new(settings: IndexSettings) -> Self {
+        Self {
+            store: DocumentStore::with_settings(settings),
+            parents: HashMap::new(),
+            children: HashMap::new(),
+        }
+    }
+
+    fn add(&mut self, sub_id: String, parent: &str) {
+        self.children
+            .entry(parent.to_string())
+            .or_default()
+            .push(sub_id.clone());
+        self.parents.insert(sub_id, parent.to_string());
+    }
+
+    /// Drop the sub-documents of `parent`.
+    fn remove_parent(&mut self, parent: &str) {
+        for sub_id in self.children.remove(parent).unwrap_or_default() {
+            self.store.delete_index(&sub_id);
+            self.parents.remove(&sub_id);
+        }
+    }
+
+    fn rebuild_children(&mut self) {
+        self.children.clear();
+        for (sub_id, parent) in &self.parents {
+            self.children
+                .entry(parent.clone())
+                .or_default()
+                .push(sub_id.clone());
+        }
+    }
+
+    /// Map sub-document ids back to their (distinct) parent ids.
+    pub fn parent_ids(&self, sub_ids: &[String]) -> Vec<String> {
+        let parents: HashSet<&String> = sub_ids
+            .iter()
+            .filter_map(|id| self.parents.get(id))
+            .collect();
+        parents.into_iter().cloned().collect()
+    }
+}
+
+/// What `extract_text` collects for one document: (text, field_path) pairs for
+/// the inverted index and the forward doc.
+struct Extraction {
+    terms: Vec<(String, String)>,
+    forward: ForwardDoc,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DocumentStore {
     pub store: HashMap<String, Document>,
+    #[serde(default)]
+    pub settings: IndexSettings,
     #[serde(skip)]
     pub tokenizer: Tokenizer,
+    #[serde(skip)]
+    pub analysis: AnalysisRegistry,
     pub allow_ngram: bool,
     pub normal_index: InvertedIndex,
-    pub n_gram_index: Option<NgramIndex>,
-    pub n_gram_trie: Option<NgramTrie>,
+    // n-gram -> terms holding it, in the structure the settings pick; both
+    // it and the term trie are rebuilt from the inverted index on load
+    #[serde(skip)]
+    pub n_grams: Option<Box<dyn NgramStore>>,
+    // every indexed term under its own characters, for prefix lookups
+    #[serde(skip)]
+    pub term_trie: NgramTrie,
+    pub completion: CompletionIndex,
     pub value_tree: ValueTreeIndex,
     pub forward_index: ForwardIndex,
+    pub keyword_index: KeywordIndex,
+    pub bool_index: BoolIndex,
+    pub geo_index: GeoIndex,
+    // nested field path -> its sub-documents
+    pub nested: HashMap<String, NestedBlock>,
 }
 
 impl DocumentStore {
     pub fn new(config: Option<TokenizerConfig>) -> Self {
-        // Determine tokenizer config
-        let tokenizer_config = config.unwrap_or_default();
+        Self::with_settings(IndexSettings::from_tokenizer_config(
+            config.unwrap_or_default(),
+        ))
+    }
 
+    pub fn with_settings(settings: IndexSettings) -> Self {
         // Determine allow_ngram: true if min_ngram or max_ngram is Some
-        let allow_ngram =
-            tokenizer_config.min_ngram.is_some() || tokenizer_config.max_ngram.is_some();
+        let allow_ngram = settings.allow_ngram();
+        let n_grams = allow_ngram.then(|| settings.ngram_store.build());
+        let (tokenizer, analysis) = Self::analysis_for(&settings);
 
         Self {
             store: HashMap::new(),
             allow_ngram,
-            tokenizer: Tokenizer::new(tokenizer_config),
+            tokenizer,
+            analysis,
+            settings,
             normal_index: InvertedIndex::new(),
-            n_gram_index: if allow_ngram {
-                Some(NgramIndex::new())
-            } else {
-                None
-            },
-            n_gram_trie: if allow_ngram {
-                Some(NgramTrie::new())
-            } else {
-                None
-            },
+            n_grams,
+            term_trie: NgramTrie::new(),
+            completion: CompletionIndex::new(),
             value_tree: ValueTreeIndex::new(),
             forward_index: ForwardIndex {
                 docs: HashMap::new(),
             },
+            keyword_index: KeywordIndex::new(),
+            bool_index: BoolIndex::new(),
+            geo_index: GeoIndex::new(),
+            nested: HashMap::new(),
+        }
+    }
+
+    // settings are validated before an index is opened; the built-in
+    // analyzers are a safe fallback for stores built from unchecked settings
+    fn analysis_for(settings: &IndexSettings) -> (Tokenizer, AnalysisRegistry) {
+        let analysis = AnalysisRegistry::from_settings(settings).unwrap_or_default();
+        let tokenizer =
+            Tokenizer::with_analyzer(settings.tokenizer_config(), analysis.default_analyzer());
+        (tokenizer, analysis)
+    }
+
+    /// Rebuild what is not serialized, the analyzers from the settings and
+    /// the term trie and n-grams from the inverted index, for this store and
+    /// its nested blocks (e.g. after loading a snapshot).
+    pub fn restore_unserialized(&mut self) {
+        (self.tokenizer, self.analysis) = Self::analysis_for(&self.settings);
+        self.term_trie = NgramTrie::new();
+        self.n_grams = self.allow_ngram.then(|| self.settings.ngram_store.build());
+        self.normal_index.rebuild_term_paths();
+
+        // one reference per occurrence, as indexing adds them
+        for term in self.normal_index.terms() {
+            let occurrences = self.normal_index.total_term_freq(term);
+            self.term_trie.add(term, term, occurrences);
+            if let Some(n_grams) = self.n_grams.as_mut() {
+                for n in self.tokenizer.ngram_sizes() {
+                    for gram in Tokenizer::ngram_tokenize(term, n) {
+                        n_grams.add(&gram, term, occurrences);
+                    }
+                }
+            }
+        }
+        for block in self.nested.values_mut() {
+            block.store.restore_unserialized();
+            block.rebuild_children();
         }
     }
 
@@ -79,7 +204,7 @@ impl DocumentStore {
         mut data: &HashMap<String, Value>,
         max_depth: Option<usize>,
     ) {
-        let max_depth = max_depth.unwrap_or(4);
+        let max_depth = max_depth.unwrap_or(self.settings.max_depth);
 
         let mut normalized = data.clone(); // clone only once
         for (_, value) in normalized.iter_mut() {
@@ -111,10 +236,16 @@ impl DocumentStore {
             Value::Text(t) => {
                 *t = t.to_lowercase().trim().to_string();
             }
-            Value::Number(_) => { /* nothing to do */ }
-            Value::Date(s) => {
-                if let Some(v) = normalize_date(s) {
-                    *s = v;
+            Value::Number(_)
+            | Value::Integer(_)
+            | Value::Date(_)
+            | Value::Bool(_)
+            | Value::GeoPoint(_)
+            | Value::Null => { /* nothing to do */ }
+            Value::Array(items) => {
+                // elements live at the same depth as the array itself
+                for v in items.iter_mut() {
+                    Self::normalize_value_rec(v, current_depth, max_depth);
                 }
             }
             Value::Object(obj) => {
@@ -131,30 +262,92 @@ impl DocumentStore {
         data: &HashMap<String, Value>,
         max_depth: usize,
     ) {
-        let mut texts = Vec::new();
-        let mut forwards = ForwardDoc::new();
-        self.extract_text(data, "", 0, max_depth, &mut texts, &mut forwards, doc_id);
-        self.forward_index.add_doc(doc_id, forwards);
-        for (pos, (text, field_path)) in texts.iter().enumerate() {
+        let mut out = Extraction {
+            terms: Vec::new(),
+            forward: ForwardDoc::new(),
+        };
+        self.extract_text(data, "", 0, max_depth, &mut out, doc_id);
+        if self.settings.language_detection {
+            out.forward.language = self.detect_document_language(&out.forward);
+        }
+        let language = out.forward.language.clone();
+        self.forward_index.add_doc(doc_id, out.forward);
+        let mut indexed_terms: HashMap<String, Vec<String>> = HashMap::new();
+        for (pos, (text, field_path)) in out.terms.iter().enumerate() {
+            let analyzer = self
+                .analysis
+                .for_document_field(field_path, language.as_deref());
             let (tokenized_words, tokenized_ngrams) =
-                self.tokenizer.tokenize(text, self.allow_ngram);
+                self.tokenizer
+                    .tokenize_with(analyzer, text, self.allow_ngram);
 
             for w in &tokenized_words {
                 self.normal_index.add_term(w, doc_id, pos, &field_path);
+                self.term_trie.insert(w, w);
+            }
+            if let Some(weight) = self.completion_weight(field_path, doc_id) {
+                self.completion
+                    .add(field_path, &tokenized_words, doc_id, weight);
             }
 
             if let Some(ref word_ngrams) = tokenized_ngrams {
-                if let Some(ref mut n_index) = self.n_gram_trie {
+                if let Some(n_grams) = self.n_grams.as_mut() {
                     for wn in word_ngrams {
                         for gram in &wn.ngrams {
-                            n_index.insert(gram, &wn.word);
+                            n_grams.add(gram, &wn.word, 1);
                         }
                     }
                 }
             }
+            indexed_terms
+                .entry(field_path.clone())
+                .or_default()
+                .extend(tokenized_words);
+        }
+        if let Some(forward) = self.forward_index.docs.get_mut(doc_id) {
+            forward.indexed_terms = indexed_terms;
         }
     }
 
+    // Weight of the document's completion suggestions from `field_path`, or
+    // None when the field does not feed the completion suggester.
+    fn completion_weight(&self, field_path: &str, doc_id: &str) -> Option<f64> {
+        let completion = self
+            .settings
+            .mappings
+            .get(field_path)?
+            .completion
+            .as_ref()?;
+        let weight = completion.weight_field.as_ref().and_then(|weight_field| {
+            let forward = self.forward_index.get(doc_id)?;
+            let integers = forward
+                .integer_fields
+                .iter()
+                .filter(|(path, _)| path_matches(path, weight_field))
+                .flat_map(|(_, values)| values.iter().map(|i| *i as f64));
+            forward
+                .numeric_fields
+                .iter()
+                .filter(|(path, _)| path_matches(path, weight_field))
+                .flat_map(|(_, values)| values.iter().copied())
+                .chain(integers)
+                .reduce(f64::max)
+        });
+        Some(weight.unwrap_or(1.0))
+    }
+
+    // Detect on the text fields that have no analyzer or language of their
+    // own, those are the ones the detected language applies to.
+    fn detect_document_language(&self, forward: &ForwardDoc) -> Option<String> {
+        let text: Vec<&str> = forward
+            .text_fields
+            .iter()
+            .filter(|(path, _)| !self.analysis.has_field_analyzer(path))
+            .flat_map(|(_, values)| values.iter().map(String::as_str))
+            .collect();
+        detect_language(&text.join(" ")).map(str::to_string)
+    }
+
     // Recursively extract text for indexing
     // fn extract_text(
     //     &mut self,
@@ -198,8 +391,7 @@ impl DocumentStore {
         prefix: &str,
         current_depth: usize,
         max_depth: usize,
-        out_terms: &mut Vec<(String, String)>,
-        forward: &mut ForwardDoc,
+        out: &mut Extraction,
         doc_id: &str,
     ) {
         if current_depth > max_depth {
@@ -212,77 +404,252 @@ impl DocumentStore {
                 format!("{}.{}", prefix, key)
             };
 
-            match value {
-                Value::Text(t) => {
-                    // store into forward doc
-                    forward.text_fields.insert(field_path.clone(), t.clone());
+            // nested elements become sub-documents and are still flattened
+            // into the parent, so plain queries keep matching them
+            if self.settings.field_type(&field_path) == Some(FieldType::Nested)
+                && self.settings.is_indexed(&field_path)
+            {
+                self.index_nested(&field_path, value, max_depth - current_depth, doc_id);
+            }
 
-                    // also push to inverted index extraction
-                    out_terms.push((t.clone(), field_path));
+            self.extract_value(&field_path, value, current_depth, max_depth, out, doc_id);
+        }
+    }
+
+    // Index one value found at `field_path`. Array elements all index under
+    // the array's own path, so `tags: ["a", "b"]` is a multi-valued `tags`.
+    fn extract_value(
+        &mut self,
+        field_path: &str,
+        value: &Value,
+        current_depth: usize,
+        max_depth: usize,
+        out: &mut Extraction,
+        doc_id: &str,
+    ) {
+        // fields mapped with `index: false` are kept in the forward doc only
+        let indexed = self.settings.is_indexed(field_path);
+        let field_path = field_path.to_string();
+
+        match value {
+            Value::Text(t) if self.settings.field_type(&field_path) == Some(FieldType::Keyword) => {
+                // keyword: the whole value is one term, nothing is analyzed
+                if indexed {
+                    self.keyword_index.add(&field_path, t, doc_id);
+                }
+                out.forward
+                    .keyword_fields
+                    .entry(field_path)
+                    .or_default()
+                    .push(t.clone());
+            }
+
+            Value::Text(t) => {
+                if indexed && self.settings.has_keyword_subfield(&field_path) {
+                    let keyword_path = format!("{}.keyword", field_path);
+                    self.keyword_index.add(&keyword_path, t, doc_id);
+                    out.forward
+                        .keyword_fields
+                        .entry(keyword_path)
+                        .or_default()
+                        .push(t.clone());
+                }
+
+                // store into forward doc
+                out.forward
+                    .text_fields
+                    .entry(field_path.clone())
+                    .or_default()
+                    .push(t.clone());
+
+                // also push to inverted index extraction
+                if indexed {
+                    out.terms.push((t.clone(), field_path));
                 }
+            }
 
-                Value::Number(n) => {
-                    forward.numeric_fields.insert(field_path.clone(), *n);
+            Value::Number(n) => {
+                out.forward
+                    .numeric_fields
+                    .entry(field_path.clone())
+                    .or_default()
+                    .push(*n);
 
+                if indexed {
                     self.value_tree.add_index(&field_path, value, doc_id);
-                    out_terms.push((n.to_string(), field_path));
+                    out.terms.push((n.to_string(), field_path));
                 }
+            }
 
-                Value::Date(d) => {
-                    forward.date_fields.insert(field_path.clone(), d.clone());
+            Value::Integer(i) => {
+                out.forward
+                    .integer_fields
+                    .entry(field_path.clone())
+                    .or_default()
+                    .push(*i);
 
+                if indexed {
                     self.value_tree.add_index(&field_path, value, doc_id);
-                    out_terms.push((d.clone(), field_path));
+                    out.terms.push((i.to_string(), field_path));
                 }
+            }
+
+            Value::Date(d) => {
+                out.forward
+                    .date_fields
+                    .entry(field_path.clone())
+                    .or_default()
+                    .push(*d);
 
-                Value::Object(obj) => {
-                    Self::extract_text(
-                        self,
-                        obj,
-                        &field_path,
-                        current_depth + 1,
-                        max_depth,
-                        out_terms,
-                        forward,
-                        doc_id,
-                    );
+                if indexed {
+                    self.value_tree.add_index(&field_path, value, doc_id);
+                    // the calendar day stays searchable as words ("2024", "03")
+                    out.terms.push((format_day(*d), field_path));
                 }
             }
+
+            Value::Bool(b) => {
+                if indexed {
+                    self.bool_index.add(&field_path, *b, doc_id);
+                }
+                out.forward
+                    .bool_fields
+                    .entry(field_path)
+                    .or_default()
+                    .push(*b);
+            }
+
+            Value::GeoPoint(point) => {
+                if indexed {
+                    self.geo_index.add(&field_path, *point, doc_id);
+                }
+                out.forward
+                    .geo_fields
+                    .entry(field_path)
+                    .or_default()
+                    .push(*point);
+            }
+
+            Value::Null => {
+                // not indexed, only remembered for exists / missing
+                out.forward.null_fields.insert(field_path);
+            }
+
+            Value::Array(items) => {
+                for item in items {
+                    self.extract_value(&field_path, item, current_depth, max_depth, out, doc_id);
+                }
+            }
+
+            Value::Object(obj) => {
+                Self::extract_text(
+                    self,
+                    obj,
+                    &field_path,
+                    current_depth + 1,
+                    max_depth,
+                    out,
+                    doc_id,
+                );
+            }
+        }
+    }
+
+    fn index_nested(&mut self, path: &str, value: &Value, max_depth: usize, doc_id: &str) {
+        let elements: Vec<&HashMap<String, Value>> = match value {
+            Value::Object(obj) => vec![obj],
+            Value::Array(items) => items
+                .iter()
+                .filter_map(|item| match item {
+                    Value::Object(obj) => Some(obj),
+                    _ => None,
+                })
+                .collect(),
+            _ => return,
+        };
+
+        if !self.nested.contains_key(path) {
+            let block = NestedBlock::new(self.settings.nested_settings(path));
+            self.nested.insert(path.to_string(), block);
+        }
+        let block = self.nested.get_mut(path).unwrap();
+
+        for (i, element) in elements.into_iter().enumerate() {
+            let sub_id = format!("{}#{}#{}", doc_id, path, i);
+            block.store.add_document(&sub_id, element, Some(max_depth));
+            block.add(sub_id, doc_id);
         }
     }
 
     pub fn delete_index(&mut self, doc_id: &str) {
+        for block in self.nested.values_mut() {
+            block.remove_parent(doc_id);
+        }
+
         // 1️⃣ Get forward document
         let Some(forward_doc) = self.forward_index.get(doc_id).cloned() else {
             return; // nothing to delete
         };
 
-        for (_field_path, text_value) in forward_doc.text_fields {
-            let (words, ngrams_opt) = self.tokenizer.tokenize(&text_value, self.allow_ngram);
-
-            for w in &words {
-                self.normal_index.remove_by_id(doc_id);
+        // every term the document was indexed under, as recorded then: the
+        // analysis may have changed since (a new synonym file)
+        let mut doc_terms = Vec::new();
+        for (field_path, words) in &forward_doc.indexed_terms {
+            for w in words {
+                self.term_trie.remove(w, w, 1);
             }
-
-            if let Some(ref mut trie) = self.n_gram_trie {
-                if let Some(ngrams_list) = ngrams_opt {
-                    for word_grams in ngrams_list {
-                        for gram in word_grams.ngrams {
-                            trie.remove_word(&gram, &word_grams.word);
-                        }
+            self.completion.remove(field_path, words, doc_id);
+
+            // release the references this document's words hold, so
+            // terms other documents still use stay
+            if let Some(n_grams) = self.n_grams.as_mut() {
+                for word_grams in self.tokenizer.word_ngrams(words) {
+                    for gram in word_grams.ngrams {
+                        n_grams.remove(&gram, &word_grams.word, 1);
                     }
                 }
             }
+            doc_terms.extend(words.iter().cloned());
         }
+        self.normal_index.remove_doc_terms(doc_id, &doc_terms);
 
-        for (field_path, num_value) in &forward_doc.numeric_fields {
-            self.value_tree
-                .remove_index(field_path, &Value::Number(*num_value), doc_id);
+        for (field_path, num_values) in &forward_doc.numeric_fields {
+            for num_value in num_values {
+                self.value_tree
+                    .remove_index(field_path, &Value::Number(*num_value), doc_id);
+            }
         }
 
-        for (field_path, date_value) in forward_doc.date_fields {
-            self.value_tree
-                .remove_index(&field_path, &Value::Date(date_value.to_string()), doc_id);
+        for (field_path, int_values) in &forward_doc.integer_fields {
+            for int_value in int_values {
+                self.value_tree
+                    .remove_index(field_path, &Value::Integer(*int_value), doc_id);
+            }
+        }
+
+        for (field_path, date_values) in forward_doc.date_fields {
+            for date_value in date_values {
+                self.value_tree
+                    .remove_index(&field_path, &Value::Date(date_value), doc_id);
+            }
+        }
+
+        for (field_path, keywords) in &forward_doc.keyword_fields {
+            for keyword in keywords {
+                self.keyword_index.remove(field_path, keyword, doc_id);
+            }
+        }
+
+        for (field_path, values) in &forward_doc.bool_fields {
+            for value in values {
+                self.bool_index.remove(field_path, *value, doc_id);
+            }
+        }
+
+        for (field_path, points) in &forward_doc.geo_fields {
+            for point in points {
+                self.geo_index.remove(field_path, *point, doc_id);
+            }
         }
         self.forward_index.remove(doc_id);
         self.store.remove(doc_id);
@@ -292,9 +659,13 @@ impl DocumentStore {
         self.normal_index = snap.normal_index;
         self.value_tree = snap.value_tree;
         self.forward_index = snap.forward_index;
-        self.n_gram_index = snap.n_gram_index;
-        self.n_gram_trie = snap.n_gram_trie;
+        self.keyword_index = snap.keyword_index;
+        self.bool_index = snap.bool_index;
+        self.geo_index = snap.geo_index;
+        self.nested = snap.nested;
+        self.completion = snap.completion;
         self.allow_ngram = snap.allow_ngram;
+        self.restore_unserialized();
     }
 
     pub fn to_snapshot(&self) -> Snapshot {
@@ -302,8 +673,11 @@ impl DocumentStore {
             normal_index: self.normal_index.clone(),
             value_tree: self.value_tree.clone(),
             forward_index: self.forward_index.clone(),
-            n_gram_index: self.n_gram_index.clone(),
-            n_gram_trie: self.n_gram_trie.clone(),
+            keyword_index: self.keyword_index.clone(),
+            bool_index: self.bool_index.clone(),
+            geo_index: self.geo_index.clone(),
+            nested: self.nested.clone(),
+            completion: self.completion.clone(),
             allow_ngram: self.allow_ngram.clone(),
             last_commit_id: (self.store.len() + 1).to_string(),
         }
@@ -313,13 +687,23 @@ impl Clone for DocumentStore {
     fn clone(&self) -> Self {
         DocumentStore {
             store: self.store.clone(),
-            tokenizer: Tokenizer::new(Default::default()),
+            settings: self.settings.clone(),
+            tokenizer: Tokenizer::with_analyzer(
+                self.settings.tokenizer_config(),
+                self.analysis.default_analyzer(),
+            ),
+            analysis: self.analysis.clone(),
             allow_ngram: self.allow_ngram,
             normal_index: self.normal_index.clone(),
-            n_gram_index: self.n_gram_index.clone(),
-            n_gram_trie: self.n_gram_trie.clone(),
+            n_grams: self.n_grams.as_ref().map(|n_grams| n_grams.clone_box()),
+            term_trie: self.term_trie.clone(),
+            completion: self.completion.clone(),
             value_tree: self.value_tree.clone(),
             forward_index: self.forward_index.clone(),
+            keyword_index: self.keyword_index.clone(),
+            bool_index: self.bool_index.clone(),
+            geo_index: self.geo_index.clone(),
+            nested: self.nested.clone(),
         }
     }
 }
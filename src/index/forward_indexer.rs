@@ -1,12 +1,30 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use serde::{Deserialize, Serialize};
 
+use crate::index::geo_index::GeoPoint;
+use crate::index::keyword_index::path_matches;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+/// Per-document field values. Every field is multi-valued: a scalar holds one
+/// entry, an array one entry per element.
 pub struct ForwardDoc {
-    pub text_fields: HashMap<String, String>,
-    pub numeric_fields: HashMap<String, f64>,
-    pub date_fields: HashMap<String, String>,
+    pub text_fields: HashMap<String, Vec<String>>,
+    pub numeric_fields: HashMap<String, Vec<f64>>,
+    /// Whole numbers, exact.
+    pub integer_fields: HashMap<String, Vec<i64>>,
+    /// Epoch milliseconds.
+    pub date_fields: HashMap<String, Vec<i64>>,
+    pub keyword_fields: HashMap<String, Vec<String>>,
+    pub bool_fields: HashMap<String, Vec<bool>>,
+    pub geo_fields: HashMap<String, Vec<GeoPoint>>,
+    /// Paths that were explicitly null in the source document.
+    pub null_fields: HashSet<String>,
+    /// Language detected for the document, when detection is on.
+    pub language: Option<String>,
+    /// The terms each field went into the inverted index under, as its
+    /// analyzer produced them then, so a delete removes exactly those.
+    pub indexed_terms: HashMap<String, Vec<String>>,
 }
 
 impl ForwardDoc {
@@ -14,9 +32,35 @@ impl ForwardDoc {
         Self {
             text_fields: HashMap::new(),
             numeric_fields: HashMap::new(),
+            integer_fields: HashMap::new(),
             date_fields: HashMap::new(),
+            keyword_fields: HashMap::new(),
+            bool_fields: HashMap::new(),
+            geo_fields: HashMap::new(),
+            null_fields: HashSet::new(),
+            language: None,
+            indexed_terms: HashMap::new(),
         }
     }
+
+    /// Whether the document has a non-null value at `field` (full path,
+    /// dotted suffix, or an object containing such paths).
+    pub fn has_value(&self, field: &str) -> bool {
+        // the path itself or any object on the way to it
+        let matches = |path: &String| {
+            std::iter::once(path.as_str())
+                .chain(path.match_indices('.').map(|(dot, _)| &path[..dot]))
+                .any(|prefix| path_matches(prefix, field))
+        };
+
+        self.text_fields.keys().any(matches)
+            || self.numeric_fields.keys().any(matches)
+            || self.integer_fields.keys().any(matches)
+            || self.date_fields.keys().any(matches)
+            || self.keyword_fields.keys().any(matches)
+            || self.bool_fields.keys().any(matches)
+            || self.geo_fields.keys().any(matches)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
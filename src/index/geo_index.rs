@@ -0,0 +1,365 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::index::keyword_index::path_matches;
+
+const BASE32: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+/// Full precision geohash, roughly 4cm x 2cm cells.
+const MAX_PRECISION: usize = 12;
+/// Upper bound on the cells a box query expands into before it falls back
+/// to a coarser precision.
+const MAX_CELLS: usize = 64;
+const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GeoPoint {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl GeoPoint {
+    pub fn new(lat: f64, lon: f64) -> Self {
+        Self { lat, lon }
+    }
+
+    /// Parse the `"lat,lon"` string form.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (lat, lon) = text.split_once(',')?;
+        let point = Self::new(lat.trim().parse().ok()?, lon.trim().parse().ok()?);
+        point.validate().ok()?;
+        Some(point)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(-90.0..=90.0).contains(&self.lat) {
+            return Err(format!("latitude {} is out of range", self.lat));
+        }
+        if !(-180.0..=180.0).contains(&self.lon) {
+            return Err(format!("longitude {} is out of range", self.lon));
+        }
+        Ok(())
+    }
+
+    /// Great-circle distance in metres (haversine).
+    pub fn distance_to(&self, other: &GeoPoint) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.lon - self.lon).to_radians();
+
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+    }
+}
+
+/// Axis-aligned box; `left > right` means the box crosses the antimeridian.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoBox {
+    pub top: f64,
+    pub left: f64,
+    pub bottom: f64,
+    pub right: f64,
+}
+
+impl GeoBox {
+    pub fn new(top_left: GeoPoint, bottom_right: GeoPoint) -> Self {
+        Self {
+            top: top_left.lat,
+            left: top_left.lon,
+            bottom: bottom_right.lat,
+            right: bottom_right.lon,
+        }
+    }
+
+    pub fn contains(&self, point: &GeoPoint) -> bool {
+        let lon_ok = if self.left <= self.right {
+            (self.left..=self.right).contains(&point.lon)
+        } else {
+            point.lon >= self.left || point.lon <= self.right
+        };
+        lon_ok && (self.bottom..=self.top).contains(&point.lat)
+    }
+
+    /// Smallest box around the circle of `radius_m` metres around `center`.
+    fn around(center: GeoPoint, radius_m: f64) -> Self {
+        let d_lat = (radius_m / EARTH_RADIUS_M).to_degrees();
+        let top = (center.lat + d_lat).min(90.0);
+        let bottom = (center.lat - d_lat).max(-90.0);
+
+        // near the poles every longitude can be within reach
+        let max_lat = top.abs().max(bottom.abs());
+        if max_lat >= 90.0 || d_lat >= 90.0 {
+            return Self {
+                top,
+                left: -180.0,
+                bottom,
+                right: 180.0,
+            };
+        }
+        let d_lon = (d_lat / max_lat.to_radians().cos()).min(180.0);
+        if d_lon >= 180.0 {
+            return Self {
+                top,
+                left: -180.0,
+                bottom,
+                right: 180.0,
+            };
+        }
+
+        let wrap = |lon: f64| {
+            if lon > 180.0 {
+                lon - 360.0
+            } else if lon < -180.0 {
+                lon + 360.0
+            } else {
+                lon
+            }
+        };
+        Self {
+            top,
+            left: wrap(center.lon - d_lon),
+            bottom,
+            right: wrap(center.lon + d_lon),
+        }
+    }
+
+    /// Split a box crossing the antimeridian into two plain boxes.
+    fn split(&self) -> Vec<GeoBox> {
+        if self.left <= self.right {
+            vec![*self]
+        } else {
+            vec![
+                GeoBox {
+                    right: 180.0,
+                    ..*self
+                },
+                GeoBox {
+                    left: -180.0,
+                    ..*self
+                },
+            ]
+        }
+    }
+}
+
+/// Distance such as `10km`, `500m` or `2.5mi`, in metres.
+pub fn distance_in_meters(amount: f64, unit: &str) -> Option<f64> {
+    let factor = match unit.to_lowercase().as_str() {
+        "" | "m" => 1.0,
+        "km" => 1_000.0,
+        "cm" => 0.01,
+        "mi" => 1_609.344,
+        "yd" => 0.9144,
+        "ft" => 0.3048,
+        "nmi" => 1_852.0,
+        _ => return None,
+    };
+    Some(amount * factor)
+}
+
+/// Standard base32 geohash of `point` with `precision` characters.
+pub fn geohash(point: &GeoPoint, precision: usize) -> String {
+    let (mut lat_range, mut lon_range) = ((-90.0, 90.0), (-180.0, 180.0));
+    let mut hash = String::with_capacity(precision);
+    let mut bits = 0u8;
+    let mut bit_count = 0;
+    let mut even = true;
+
+    while hash.len() < precision {
+        let (range, value) = if even {
+            (&mut lon_range, point.lon)
+        } else {
+            (&mut lat_range, point.lat)
+        };
+        let mid = (range.0 + range.1) / 2.0;
+        bits <<= 1;
+        if value >= mid {
+            bits |= 1;
+            range.0 = mid;
+        } else {
+            range.1 = mid;
+        }
+        even = !even;
+
+        bit_count += 1;
+        if bit_count == 5 {
+            hash.push(BASE32[bits as usize] as char);
+            bits = 0;
+            bit_count = 0;
+        }
+    }
+
+    hash
+}
+
+/// Cell size in degrees (lat, lon) of a geohash with `precision` characters.
+fn cell_size(precision: usize) -> (f64, f64) {
+    let total_bits = 5 * precision as i32;
+    let lon_bits = (total_bits + 1) / 2;
+    let lat_bits = total_bits / 2;
+    (180.0 / 2f64.powi(lat_bits), 360.0 / 2f64.powi(lon_bits))
+}
+
+/// Geohash prefixes covering `bbox` (which must not cross the antimeridian),
+/// at the finest precision that needs at most `MAX_CELLS` cells. An empty
+/// result means the box is too large for any prefix and all points qualify.
+fn covering_cells(bbox: &GeoBox) -> Vec<String> {
+    for precision in (1..=MAX_PRECISION).rev() {
+        let (cell_lat, cell_lon) = cell_size(precision);
+        let lat_cells = ((bbox.top + 90.0) / cell_lat).floor()
+            - ((bbox.bottom + 90.0) / cell_lat).floor()
+            + 1.0;
+        let lon_cells = ((bbox.right + 180.0) / cell_lon).floor()
+            - ((bbox.left + 180.0) / cell_lon).floor()
+            + 1.0;
+        if lat_cells * lon_cells > MAX_CELLS as f64 {
+            continue;
+        }
+
+        let mut cells = HashSet::new();
+        let first_lat = ((bbox.bottom + 90.0) / cell_lat).floor();
+        let first_lon = ((bbox.left + 180.0) / cell_lon).floor();
+        for i in 0..lat_cells as usize {
+            for j in 0..lon_cells as usize {
+                // centre of the cell, clamped so edge cells stay on the globe
+                let lat = ((first_lat + i as f64 + 0.5) * cell_lat - 90.0).clamp(-90.0, 90.0);
+                let lon = ((first_lon + j as f64 + 0.5) * cell_lon - 180.0).clamp(-180.0, 180.0);
+                cells.insert(geohash(&GeoPoint::new(lat, lon), precision));
+            }
+        }
+        return cells.into_iter().collect();
+    }
+
+    Vec::new()
+}
+
+/**
+ * Geo-point index. Each field keeps its points ordered by full-precision
+ * geohash, so a region query only scans the key ranges of the geohash cells
+ * covering it and then checks the exact coordinates.
+ */
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct GeoIndex {
+    // field_path -> geohash -> (doc_id, point)
+    fields: HashMap<String, BTreeMap<String, Vec<(String, GeoPoint)>>>,
+}
+
+impl GeoIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, field_path: &str, point: GeoPoint, doc_id: &str) {
+        self.fields
+            .entry(field_path.to_string())
+            .or_default()
+            .entry(geohash(&point, MAX_PRECISION))
+            .or_default()
+            .push((doc_id.to_string(), point));
+    }
+
+    pub fn remove(&mut self, field_path: &str, point: GeoPoint, doc_id: &str) {
+        let hash = geohash(&point, MAX_PRECISION);
+
+        if let Some(cells) = self.fields.get_mut(field_path) {
+            if let Some(points) = cells.get_mut(&hash) {
+                points.retain(|(d, p)| !(d == doc_id && *p == point));
+                if points.is_empty() {
+                    cells.remove(&hash);
+                }
+            }
+            if cells.is_empty() {
+                self.fields.remove(field_path);
+            }
+        }
+    }
+
+    /// Every (doc_id, point) of `field` whose geohash starts with one of the
+    /// cells covering `bbox`.
+    fn candidates<'a>(&'a self, field: &str, bbox: &GeoBox) -> Vec<(&'a String, &'a GeoPoint)> {
+        let mut result = Vec::new();
+
+        for (path, cells) in &self.fields {
+            if !path_matches(path, field) {
+                continue;
+            }
+
+            for part in bbox.split() {
+                let prefixes = covering_cells(&part);
+                if prefixes.is_empty() {
+                    result.extend(cells.values().flatten().map(|(d, p)| (d, p)));
+                    continue;
+                }
+                for prefix in prefixes {
+                    let entries = cells
+                        .range(prefix.clone()..)
+                        .take_while(|(hash, _)| hash.starts_with(&prefix));
+                    result
+                        .extend(entries.flat_map(|(_, points)| points.iter().map(|(d, p)| (d, p))));
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Documents with a point of `field` inside `bbox`.
+    pub fn bounding_box(&self, field: &str, bbox: &GeoBox) -> Vec<String> {
+        let ids: HashSet<&String> = self
+            .candidates(field, bbox)
+            .into_iter()
+            .filter(|(_, point)| bbox.contains(point))
+            .map(|(doc_id, _)| doc_id)
+            .collect();
+        ids.into_iter().cloned().collect()
+    }
+
+    /// Documents with a point of `field` within `radius_m` metres of `center`.
+    pub fn distance(&self, field: &str, center: GeoPoint, radius_m: f64) -> Vec<String> {
+        let bbox = GeoBox::around(center, radius_m);
+        let ids: HashSet<&String> = self
+            .candidates(field, &bbox)
+            .into_iter()
+            .filter(|(_, point)| center.distance_to(point) <= radius_m)
+            .map(|(doc_id, _)| doc_id)
+            .collect();
+        ids.into_iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn geohash_cells_and_distance_queries() {
+        // reference value from the geohash specification
+        assert_eq!(
+            geohash(&GeoPoint::new(57.64911, 10.40744), 11),
+            "u4pruydqqvj"
+        );
+
+        let mut index = GeoIndex::new();
+        let paris = GeoPoint::new(48.8566, 2.3522);
+        index.add("home", paris, "paris");
+        index.add("home", GeoPoint::new(48.8049, 2.1204), "versailles");
+        index.add("home", GeoPoint::new(51.5074, -0.1278), "london");
+        index.add("home", GeoPoint::new(-17.7134, 178.065), "fiji");
+
+        let mut near = index.distance("home", paris, 20_000.0);
+        near.sort();
+        assert_eq!(near, ["paris", "versailles"]);
+        assert_eq!(index.distance("home", paris, 1_000.0), ["paris"]);
+
+        let europe = GeoBox::new(GeoPoint::new(60.0, -5.0), GeoPoint::new(45.0, 10.0));
+        assert_eq!(index.bounding_box("home", &europe).len(), 3);
+
+        // boxes may cross the antimeridian
+        let pacific = GeoBox::new(GeoPoint::new(0.0, 170.0), GeoPoint::new(-30.0, -170.0));
+        assert_eq!(index.bounding_box("home", &pacific), ["fiji"]);
+
+        index.remove("home", paris, "paris");
+        assert!(index.distance("home", paris, 1_000.0).is_empty());
+        assert_eq!(distance_in_meters(10.0, "km"), Some(10_000.0));
+    }
+}
@@ -1,17 +1,51 @@
 use std::collections::{HashMap, HashSet};
 
 use serde::{Deserialize, Serialize};
+
+use crate::index::keyword_index::path_matches;
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Posting {
     pub positions: Vec<usize>,
     pub term_freq: usize,
     pub field_paths: HashSet<String>,
 }
+/// Collection-level statistics used by BM25. Computed from a single index by
+/// `collection_stats`, or merged across several indices so that scores stay
+/// comparable when a query fans out.
+#[derive(Debug, Clone, Default)]
+pub struct CollectionStats {
+    pub n_docs: usize,
+    pub total_doc_len: usize,
+    pub doc_freqs: HashMap<String, usize>,
+}
+
+impl CollectionStats {
+    pub fn avg_doc_len(&self) -> f64 {
+        if self.n_docs == 0 {
+            return 0.0;
+        }
+        self.total_doc_len as f64 / self.n_docs as f64
+    }
+
+    pub fn merge(&mut self, other: &CollectionStats) {
+        self.n_docs += other.n_docs;
+        self.total_doc_len += other.total_doc_len;
+        for (term, df) in &other.doc_freqs {
+            *self.doc_freqs.entry(term.clone()).or_insert(0) += df;
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct InvertedIndex {
     index: HashMap<String, HashMap<String, Posting>>,
     deleted_docs: HashSet<String>,
     doc_lengths: HashMap<String, usize>,
+    // term -> field path -> documents holding the term in that path, so
+    // field-scoped expansions skip other fields' terms without reading
+    // postings. Rebuilt from the postings on load.
+    #[serde(skip)]
+    term_paths: HashMap<String, HashMap<String, usize>>,
 }
 /**
  * Todo Create bm 25 here
@@ -22,6 +56,7 @@ impl InvertedIndex {
             index: HashMap::new(),
             deleted_docs: HashSet::new(),
             doc_lengths: HashMap::new(),
+            term_paths: HashMap::new(),
         }
     }
 
@@ -44,7 +79,14 @@ impl InvertedIndex {
             });
 
         posting.positions.push(pos); // push position
-        posting.field_paths.insert(field_path.to_owned()); // insert field path
+        if posting.field_paths.insert(field_path.to_owned()) {
+            *self
+                .term_paths
+                .entry(term.to_owned())
+                .or_default()
+                .entry(field_path.to_owned())
+                .or_insert(0) += 1;
+        }
         posting.term_freq += 1; // increment frequency
         self.doc_lengths
             .entry(doc_id.to_owned())
@@ -93,6 +135,97 @@ impl InvertedIndex {
         }
     }
 
+    /// Whether `term` (as indexed, not lowercased) still has a posting.
+    pub fn has_term(&self, term: &str) -> bool {
+        self.get_postings(term)
+            .is_some_and(|mut postings| postings.next().is_some())
+    }
+
+    /// Whether `term` (as indexed) occurs in a field `field` names, as a
+    /// full path or a dotted suffix of one.
+    pub fn has_term_in_field(&self, term: &str, field: &str) -> bool {
+        self.term_paths
+            .get(term)
+            .is_some_and(|paths| paths.keys().any(|path| path_matches(path, field)))
+    }
+
+    /// Recompute the field paths of every term from the postings.
+    pub fn rebuild_term_paths(&mut self) {
+        self.term_paths.clear();
+        for (term, postings) in &self.index {
+            for posting in postings.values() {
+                Self::count_paths(&mut self.term_paths, term, posting);
+            }
+        }
+    }
+
+    fn count_paths(
+        term_paths: &mut HashMap<String, HashMap<String, usize>>,
+        term: &str,
+        posting: &Posting,
+    ) {
+        let paths = term_paths.entry(term.to_owned()).or_default();
+        for path in &posting.field_paths {
+            *paths.entry(path.clone()).or_insert(0) += 1;
+        }
+    }
+
+    // forget the field paths of a posting that is dropped
+    fn release_paths(
+        term_paths: &mut HashMap<String, HashMap<String, usize>>,
+        term: &str,
+        posting: &Posting,
+    ) {
+        let Some(paths) = term_paths.get_mut(term) else {
+            return;
+        };
+        for path in &posting.field_paths {
+            if let Some(docs) = paths.get_mut(path) {
+                *docs = docs.saturating_sub(1);
+                if *docs == 0 {
+                    paths.remove(path);
+                }
+            }
+        }
+        if paths.is_empty() {
+            term_paths.remove(term);
+        }
+    }
+
+    /// Every term that still has a posting.
+    pub fn terms(&self) -> impl Iterator<Item = &String> {
+        self.index.keys().filter(|term| self.has_term(term))
+    }
+
+    /// The posting of `term` (as indexed) for `doc_id`.
+    pub fn posting(&self, term: &str, doc_id: &str) -> Option<&Posting> {
+        if self.deleted_docs.contains(doc_id) {
+            return None;
+        }
+        self.index.get(term)?.get(doc_id)
+    }
+
+    /// Terms indexed for `doc_id`, its length for BM25.
+    pub fn doc_length(&self, doc_id: &str) -> usize {
+        self.doc_lengths.get(doc_id).copied().unwrap_or(1)
+    }
+
+    pub fn bm25_idf(n_docs: usize, df: usize) -> f64 {
+        let (n_docs, df) = (n_docs as f64, df as f64);
+        ((n_docs - df + 0.5) / (df + 0.5) + 1.0).ln()
+    }
+
+    pub fn bm25_score(tf: f64, doc_len: usize, idf: f64, k1: f64, b: f64, avg_doc_len: f64) -> f64 {
+        let denom = tf + k1 * (1.0 - b + b * doc_len as f64 / avg_doc_len);
+        idf * (tf * (k1 + 1.0)) / denom
+    }
+
+    /// Occurrences of `term` (as indexed) over every document.
+    pub fn total_term_freq(&self, term: &str) -> usize {
+        self.get_postings(term)
+            .map_or(0, |postings| postings.map(|(_, p)| p.term_freq).sum())
+    }
+
     pub fn doc_freq(&self, term: &str) -> usize {
         self.search_term(&[term]).len()
     }
@@ -104,39 +237,96 @@ impl InvertedIndex {
     pub fn is_deleted(&self, doc_id: &str) -> bool {
         self.deleted_docs.contains(doc_id)
     }
+
+    /// Remove `doc_id` from the postings of `terms`, the terms it was indexed
+    /// under, and from the length statistics. Only those postings are
+    /// touched; terms no other document holds are dropped.
+    pub fn remove_doc_terms(&mut self, doc_id: &str, terms: &[String]) {
+        for term in terms {
+            if let Some(postings) = self.index.get_mut(term) {
+                if let Some(posting) = postings.remove(doc_id) {
+                    Self::release_paths(&mut self.term_paths, term, &posting);
+                }
+                if postings.is_empty() {
+                    self.index.remove(term);
+                }
+            }
+        }
+        self.doc_lengths.remove(doc_id);
+        self.deleted_docs.remove(doc_id);
+    }
+
+    /// Remove `doc_id` without knowing its terms, by scanning every term.
     pub fn remove_by_id(&mut self, doc_id: &str) {
         self.deleted_docs.insert(doc_id.to_string());
         self.delete_index();
     }
 
+    /// Purge the documents marked by `remove_document`.
     pub fn delete_index(&mut self) {
-        for postings in self.index.values_mut() {
-            for doc_id in &self.deleted_docs {
-                postings.remove(doc_id);
-            }
+        let deleted = std::mem::take(&mut self.deleted_docs);
+        let term_paths = &mut self.term_paths;
+        self.index.retain(|term, postings| {
+            postings.retain(|doc_id, posting| {
+                let keep = !deleted.contains(doc_id);
+                if !keep {
+                    Self::release_paths(term_paths, term, posting);
+                }
+                keep
+            });
+            !postings.is_empty()
+        });
+        for doc_id in &deleted {
+            self.doc_lengths.remove(doc_id);
+        }
+    }
+
+    pub fn collection_stats(&self, terms: &[&str]) -> CollectionStats {
+        let live_lengths = self
+            .doc_lengths
+            .iter()
+            .filter(|(doc_id, _)| !self.deleted_docs.contains(*doc_id))
+            .map(|(_, len)| len);
+        CollectionStats {
+            n_docs: live_lengths.clone().count(),
+            total_doc_len: live_lengths.sum(),
+            doc_freqs: terms
+                .iter()
+                .map(|term| (term.to_string(), self.doc_freq(term)))
+                .collect(),
         }
-        self.deleted_docs.clear();
     }
 
     pub fn bm25_search(&self, query: &[&str], k1: f64, b: f64) -> HashMap<String, f64> {
+        let stats = self.collection_stats(query);
+        self.bm25_search_with_stats(query, k1, b, &stats)
+    }
+
+    /// BM25 over this index's postings, but with idf and length normalisation
+    /// taken from `stats` (e.g. statistics merged over every index an alias
+    /// points to).
+    pub fn bm25_search_with_stats(
+        &self,
+        query: &[&str],
+        k1: f64,
+        b: f64,
+        stats: &CollectionStats,
+    ) -> HashMap<String, f64> {
         let mut scores: HashMap<String, f64> = HashMap::new();
-        let n_docs = self.doc_lengths.len() as f64;
-        let avg_doc_len = self.doc_lengths.values().sum::<usize>() as f64 / n_docs;
+        let avg_doc_len = stats.avg_doc_len();
 
         for &term in query {
             if let Some(postings) = self.index.get(term) {
-                let df = postings.len() as f64;
-                let idf = ((n_docs - df + 0.5) / (df + 0.5) + 1.0).ln();
+                let df = stats.doc_freqs.get(term).copied().unwrap_or(postings.len());
+                let idf = Self::bm25_idf(stats.n_docs, df);
 
                 for (doc_id, posting) in postings {
                     if self.deleted_docs.contains(doc_id) {
                         continue;
                     }
                     let tf = posting.term_freq as f64;
-                    let doc_len = self.doc_lengths.get(doc_id).copied().unwrap_or(1) as f64;
-
-                    let denom = tf + k1 * (1.0 - b + b * doc_len / avg_doc_len);
-                    let score = idf * (tf * (k1 + 1.0)) / denom;
+                    let doc_len = self.doc_length(doc_id);
+                    let score = Self::bm25_score(tf, doc_len, idf, k1, b, avg_doc_len);
 
                     *scores.entry(doc_id.clone()).or_insert(0.0) += score;
                 }
@@ -200,3 +390,90 @@ impl InvertedIndex {
             .unwrap_or_default()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::index::analysis::analyzer::AnalysisRegistry;
+    use crate::index::documents_store::DocumentStore;
+    use crate::index::settings::IndexSettings;
+    use crate::index::value::Value;
+    use crate::test_support::{store_with, text_doc};
+
+    fn pokemon_store(docs: &[(&str, &str, f64)]) -> DocumentStore {
+        let settings = IndexSettings {
+            min_ngram: Some(2),
+            max_ngram: Some(3),
+            ..Default::default()
+        };
+        let docs = docs.iter().map(|&(id, name, hp)| {
+            (
+                id,
+                vec![
+                    ("name", Value::Text(name.into())),
+                    ("hp", Value::Number(hp)),
+                ],
+            )
+        });
+        store_with(settings, docs)
+    }
+
+    #[test]
+    fn deletes_keep_collection_stats_exact() {
+        let docs = [
+            ("1", "pikachu thunder shock", 35.0),
+            ("2", "raichu thunder", 60.0),
+            ("3", "onix rock tomb rock", 35.0),
+        ];
+        let mut store = pokemon_store(&docs);
+        store.delete_index("3");
+        store.delete_index("missing");
+        let expected = pokemon_store(&docs[..2]);
+
+        let query = ["thunder", "rock", "35"];
+        let (stats, fresh) = (
+            store.normal_index.collection_stats(&query),
+            expected.normal_index.collection_stats(&query),
+        );
+        assert_eq!(stats.n_docs, 2);
+        assert_eq!(
+            (stats.n_docs, stats.total_doc_len, &stats.doc_freqs),
+            (fresh.n_docs, fresh.total_doc_len, &fresh.doc_freqs)
+        );
+        assert_eq!(
+            store.normal_index.bm25_search(&query, 1.2, 0.75),
+            expected.normal_index.bm25_search(&query, 1.2, 0.75)
+        );
+
+        // terms only the deleted document held are gone, shared ones stay
+        let index = &store.normal_index;
+        assert!(!index.index.contains_key("rock") && !index.index.contains_key("onix"));
+        assert_eq!(index.index["35"].len(), 1);
+        assert!(!index.doc_lengths.contains_key("3"));
+
+        // the id can be indexed again, and deleting everything empties it
+        store.add_document("3", &text_doc("name", "onix"), None);
+        assert_eq!(store.normal_index.search_term(&["onix"]), ["3"]);
+        for id in ["1", "2", "3"] {
+            store.delete_index(id);
+        }
+        assert!(store.normal_index.index.is_empty());
+        assert!(store.normal_index.doc_lengths.is_empty());
+        assert!(store.term_trie.get_terms_with_prefix("").is_empty());
+    }
+
+    #[test]
+    fn deletes_remove_the_terms_indexed_even_after_analysis_changes() {
+        let mut store = pokemon_store(&[("1", "running thunder", 35.0)]);
+        // the index now stems, "running" would analyze to "run"
+        let stemming = IndexSettings {
+            use_stemming: true,
+            ..Default::default()
+        };
+        store.analysis = AnalysisRegistry::from_settings(&stemming).unwrap();
+
+        store.delete_index("1");
+        assert!(store.normal_index.index.is_empty());
+        assert!(store.term_trie.get_terms_with_prefix("").is_empty());
+        assert!(store.n_grams.as_ref().unwrap().terms("ru").is_empty());
+    }
+}
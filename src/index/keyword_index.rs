@@ -0,0 +1,192 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+/// A query field matches an indexed path when it is the full path or a
+/// dotted suffix of it (`hometown` matches `trainer.hometown`).
+pub fn path_matches(full_path: &str, field: &str) -> bool {
+    full_path == field
+        || (full_path.len() > field.len()
+            && full_path.ends_with(field)
+            && full_path.as_bytes()[full_path.len() - field.len() - 1] == b'.')
+}
+
+/// Normalisation applied to keyword values at index and query time: the
+/// whole string is one term, only case and surrounding/duplicate whitespace
+/// are folded.
+pub fn normalize_keyword(value: &str) -> String {
+    value
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/**
+ * Non-analyzed index for keyword fields. Each field keeps its terms ordered so
+ * the same structure serves exact `term` filters, terms aggregations and
+ * sorting.
+ */
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct KeywordIndex {
+    // field_path -> term -> doc ids
+    fields: HashMap<String, BTreeMap<String, HashSet<String>>>,
+}
+
+impl KeywordIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, field_path: &str, value: &str, doc_id: &str) {
+        self.fields
+            .entry(field_path.to_string())
+            .or_default()
+            .entry(normalize_keyword(value))
+            .or_default()
+            .insert(doc_id.to_string());
+    }
+
+    pub fn remove(&mut self, field_path: &str, value: &str, doc_id: &str) {
+        let term = normalize_keyword(value);
+
+        if let Some(terms) = self.fields.get_mut(field_path) {
+            if let Some(docs) = terms.get_mut(&term) {
+                docs.remove(doc_id);
+                if docs.is_empty() {
+                    terms.remove(&term);
+                }
+            }
+            if terms.is_empty() {
+                self.fields.remove(field_path);
+            }
+        }
+    }
+
+    pub fn has_field(&self, field: &str) -> bool {
+        self.fields.keys().any(|path| path_matches(path, field))
+    }
+
+    fn matching_fields<'a>(
+        &'a self,
+        field: &'a str,
+    ) -> impl Iterator<Item = &'a BTreeMap<String, HashSet<String>>> + 'a {
+        self.fields
+            .iter()
+            .filter(move |(path, _)| path_matches(path, field))
+            .map(|(_, terms)| terms)
+    }
+
+    /// Documents whose `field` holds exactly `value` (after normalisation).
+    pub fn term_query(&self, field: &str, value: &str) -> Vec<String> {
+        let term = normalize_keyword(value);
+        let mut result: HashSet<&String> = HashSet::new();
+
+        for terms in self.matching_fields(field) {
+            if let Some(docs) = terms.get(&term) {
+                result.extend(docs);
+            }
+        }
+
+        result.into_iter().cloned().collect()
+    }
+
+    /// Count documents per distinct term of `field`, most frequent first.
+    /// Only `candidates` are counted when given.
+    pub fn terms_aggregation(
+        &self,
+        field: &str,
+        candidates: Option<&HashSet<String>>,
+    ) -> Vec<(String, usize)> {
+        let mut counts: BTreeMap<&String, HashSet<&String>> = BTreeMap::new();
+
+        for terms in self.matching_fields(field) {
+            for (term, docs) in terms {
+                let bucket = counts.entry(term).or_default();
+                for doc_id in docs {
+                    if candidates.is_none_or(|c| c.contains(doc_id)) {
+                        bucket.insert(doc_id);
+                    }
+                }
+            }
+        }
+
+        let mut buckets: Vec<(String, usize)> = counts
+            .into_iter()
+            .filter(|(_, docs)| !docs.is_empty())
+            .map(|(term, docs)| (term.clone(), docs.len()))
+            .collect();
+        // stable sort keeps equal counts in term order
+        buckets.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        buckets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::engine::query_service::{QueryService, SortField};
+    use crate::index::documents_store::DocumentStore;
+    use crate::index::settings::{FieldType, IndexSettings};
+    use crate::index::value::Value;
+    use crate::test_support::doc;
+
+    fn trainer(hometown: &str, sku: &str) -> HashMap<String, Value> {
+        doc(vec![
+            ("hometown", Value::Text(hometown.into())),
+            ("sku", Value::Text(sku.into())),
+        ])
+    }
+
+    #[test]
+    fn keyword_fields_match_whole_value() {
+        let mut settings = IndexSettings::default();
+        settings.map_field("sku", FieldType::Keyword);
+        settings
+            .mappings
+            .entry("hometown".into())
+            .or_default()
+            .keyword_subfield = true;
+
+        let mut store = DocumentStore::with_settings(settings);
+        store.add_document("1", &trainer("Pallet Town", "AB-1234"), None);
+        store.add_document("2", &trainer("Pallet  Town ", "AB-9999"), None);
+        store.add_document("3", &trainer("Cerulean City", "CD-1234"), None);
+
+        let qs = QueryService::new(&store);
+
+        assert_eq!(qs.term("sku", "ab-1234"), vec!["1".to_string()]);
+        // keyword values are not analyzed into words
+        assert!(qs.get_words(vec!["1234"]).is_empty());
+
+        let mut pallet = qs.term("hometown.keyword", "pallet town");
+        pallet.sort();
+        assert_eq!(pallet, vec!["1".to_string(), "2".to_string()]);
+        // the analyzed text field is still searchable word by word
+        assert_eq!(qs.get_words(vec!["cerulean"]), vec!["3".to_string()]);
+
+        assert_eq!(
+            qs.terms_aggregation("hometown.keyword", None),
+            vec![
+                ("pallet town".to_string(), 2),
+                ("cerulean city".to_string(), 1)
+            ]
+        );
+
+        let sorted = qs.sort_docs_2(
+            vec!["1".into(), "2".into(), "3".into()],
+            &[SortField::new("sku", false)],
+        );
+        assert_eq!(sorted, vec!["3", "2", "1"]);
+
+        // sorted on the normalized value: "pallet  town" is "pallet town"
+        store.add_document("4", &trainer("Pallet Tovn", "EF-1"), None);
+        let qs = QueryService::new(&store);
+        let sorted = qs.sort_docs_2(
+            vec!["1".into(), "2".into(), "3".into(), "4".into()],
+            &[SortField::new("hometown.keyword", true)],
+        );
+        assert_eq!(sorted[..2], ["3", "4"]);
+    }
+}
@@ -1,7 +1,13 @@
+pub mod analysis;
+pub mod bool_index;
+pub mod completion;
 pub mod documents_store;
 pub mod forward_indexer;
+pub mod geo_index;
 pub mod inverted_index;
+pub mod keyword_index;
 pub mod n_gram;
+pub mod settings;
 pub mod tokenizer;
 pub mod value;
 pub mod value_tree;
@@ -1,2 +1,3 @@
 pub mod n_gram_index;
+pub mod n_gram_store;
 pub mod n_gram_trie;
@@ -1,10 +1,15 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::index::n_gram::n_gram_store::NgramStore;
+
+/// N-gram store keeping every gram in a flat hash map: quicker lookups than
+/// the trie, at the cost of storing each gram in full.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct NgramIndex {
-    map: HashMap<String, HashSet<String>>,
+    // gram -> term -> references
+    map: HashMap<String, HashMap<String, usize>>,
 }
 
 impl NgramIndex {
@@ -13,18 +18,41 @@ impl NgramIndex {
             map: HashMap::new(),
         }
     }
+}
 
-    pub fn insert(&mut self, word: &str, term: &str) {
-        self.map
-            .entry(word.to_string())
-            .or_insert_with(HashSet::new)
-            .insert(term.to_string());
+impl NgramStore for NgramIndex {
+    fn add(&mut self, gram: &str, term: &str, count: usize) {
+        *self
+            .map
+            .entry(gram.to_string())
+            .or_default()
+            .entry(term.to_string())
+            .or_insert(0) += count;
     }
 
-    pub fn get_terms(&self, word: &str) -> Vec<String> {
+    fn remove(&mut self, gram: &str, term: &str, count: usize) {
+        let Some(terms) = self.map.get_mut(gram) else {
+            return;
+        };
+        if let Some(refs) = terms.get_mut(term) {
+            *refs = refs.saturating_sub(count);
+            if *refs == 0 {
+                terms.remove(term);
+            }
+        }
+        if terms.is_empty() {
+            self.map.remove(gram);
+        }
+    }
+
+    fn terms(&self, gram: &str) -> Vec<&str> {
         self.map
-            .get(word)
-            .map(|set| set.iter().cloned().collect())
+            .get(gram)
+            .map(|terms| terms.keys().map(String::as_str).collect())
             .unwrap_or_default()
     }
+
+    fn clone_box(&self) -> Box<dyn NgramStore> {
+        Box::new(self.clone())
+    }
 }
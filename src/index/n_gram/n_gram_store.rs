@@ -0,0 +1,101 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::index::n_gram::{n_gram_index::NgramIndex, n_gram_trie::NgramTrie};
+
+/// Maps the n-grams of indexed words back to the words, so that fuzzy
+/// lookups can gather candidate terms. Each indexed occurrence of a word adds
+/// a reference from every one of its n-grams, and deleting it releases them;
+/// a term only leaves a gram once no document holds it any more.
+pub trait NgramStore: fmt::Debug {
+    /// Add `count` references from `gram` to `term`.
+    fn add(&mut self, gram: &str, term: &str, count: usize);
+
+    /// Release `count` references from `gram` to `term`.
+    fn remove(&mut self, gram: &str, term: &str, count: usize);
+
+    /// Terms `gram` still references.
+    fn terms(&self, gram: &str) -> Vec<&str>;
+
+    fn clone_box(&self) -> Box<dyn NgramStore>;
+}
+
+/// Which `NgramStore` an index keeps its n-grams in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NgramStoreKind {
+    /// Grams share their common prefixes in a trie.
+    #[default]
+    Trie,
+    /// Every gram is a key of a hash map.
+    HashMap,
+}
+
+impl NgramStoreKind {
+    pub fn build(self) -> Box<dyn NgramStore> {
+        match self {
+            NgramStoreKind::Trie => Box::new(NgramTrie::new()),
+            NgramStoreKind::HashMap => Box::new(NgramIndex::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::query_service::{MAX_SUGGEST_EDITS, QueryService};
+    use crate::index::documents_store::DocumentStore;
+    use crate::index::settings::IndexSettings;
+    use crate::test_support::text_store;
+
+    fn terms(store: &DocumentStore, gram: &str) -> Vec<String> {
+        let mut terms: Vec<String> = store
+            .n_grams
+            .as_ref()
+            .unwrap()
+            .terms(gram)
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        terms.sort();
+        terms
+    }
+
+    #[test]
+    fn shared_terms_survive_deletes_in_every_store() {
+        for kind in [NgramStoreKind::Trie, NgramStoreKind::HashMap] {
+            let settings = IndexSettings {
+                min_ngram: Some(2),
+                max_ngram: Some(3),
+                ngram_store: kind,
+                ..Default::default()
+            };
+            let mut store = text_store(
+                settings,
+                "name",
+                [("1", "pikachu"), ("2", "pikachu pichu pichu")],
+            );
+            assert_eq!(terms(&store, "chu"), ["pichu", "pikachu"]);
+
+            // "pikachu" is still in document 2
+            store.delete_index("1");
+            assert_eq!(terms(&store, "ka"), ["pikachu"]);
+            let suggestions =
+                QueryService::new(&store).suggest_terms("pikachuu", MAX_SUGGEST_EDITS, 1);
+            assert_eq!(suggestions[0].term, "pikachu");
+
+            // the counts are rebuilt from the index on load
+            let mut restored = DocumentStore::with_settings(store.settings.clone());
+            restored.load_snapshot(store.to_snapshot());
+            assert_eq!(terms(&restored, "chu"), ["pichu", "pikachu"]);
+
+            for store in [&mut store, &mut restored] {
+                store.delete_index("2");
+                assert!(terms(store, "chu").is_empty());
+                assert!(terms(store, "pi").is_empty());
+                assert!(store.term_trie.get_terms_with_prefix("p").is_empty());
+            }
+        }
+    }
+}
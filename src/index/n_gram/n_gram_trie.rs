@@ -1,11 +1,14 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use serde::{Deserialize, Serialize};
 
+use crate::index::n_gram::n_gram_store::NgramStore;
+
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct TrieNode {
     children: HashMap<char, TrieNode>,
-    terms: HashSet<String>,
+    // term -> references to it from this word
+    terms: HashMap<String, usize>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
@@ -21,11 +24,7 @@ impl NgramTrie {
     }
 
     pub fn insert(&mut self, word: &str, term: &str) {
-        let mut node = &mut self.root;
-        for ch in word.chars() {
-            node = node.children.entry(ch).or_insert_with(TrieNode::default);
-        }
-        node.terms.insert(term.to_string());
+        self.add(word, term, 1);
     }
 
     pub fn get_terms<'a>(&'a self, word: &str) -> Vec<&'a str> {
@@ -36,7 +35,7 @@ impl NgramTrie {
                 None => return Vec::new(),
             }
         }
-        node.terms.iter().map(|s| s.as_str()).collect()
+        node.terms.keys().map(|s| s.as_str()).collect()
     }
 
     pub fn get_terms_with_prefix(&self, prefix: &str) -> Vec<String> {
@@ -53,8 +52,36 @@ impl NgramTrie {
         result.into_iter().collect()
     }
 
+    /// Terms under the words starting with `prefix`, shortest words first
+    /// and in character order among words of a length. Lazy, so a caller
+    /// that needs a few terms stops without walking the whole branch.
+    pub fn iter_terms_with_prefix<'a>(&'a self, prefix: &str) -> impl Iterator<Item = &'a str> {
+        let mut node = Some(&self.root);
+        for ch in prefix.chars() {
+            node = node.and_then(|node| node.children.get(&ch));
+        }
+        let mut queue: VecDeque<&TrieNode> = node.into_iter().collect();
+        let mut pending: Vec<&str> = Vec::new();
+
+        std::iter::from_fn(move || {
+            loop {
+                if let Some(term) = pending.pop() {
+                    return Some(term);
+                }
+                let node = queue.pop_front()?;
+                // reversed, as terms are popped off the end
+                pending = node.terms.keys().map(String::as_str).collect();
+                pending.sort_unstable_by(|a, b| b.cmp(a));
+
+                let mut children: Vec<(&char, &TrieNode)> = node.children.iter().collect();
+                children.sort_unstable_by_key(|(ch, _)| **ch);
+                queue.extend(children.into_iter().map(|(_, child)| child));
+            }
+        })
+    }
+
     fn collect_terms(node: &TrieNode, result: &mut HashSet<String>) {
-        for term in &node.terms {
+        for term in node.terms.keys() {
             result.insert(term.clone());
         }
         for child in node.children.values() {
@@ -62,20 +89,74 @@ impl NgramTrie {
         }
     }
 
+    /// Terms stored under the words that start with `prefix` and whose rest
+    /// an automaton accepts. From `start`, `step` moves the automaton over a
+    /// character, returning `None` once nothing further can be accepted so
+    /// the whole branch is skipped; `accept` says what an accepted word is
+    /// reported with.
+    pub fn search<S, T: Clone>(
+        &self,
+        prefix: &str,
+        start: S,
+        step: impl Fn(&S, char) -> Option<S>,
+        accept: impl Fn(&S) -> Option<T>,
+    ) -> Vec<(&str, T)> {
+        let mut node = &self.root;
+        for ch in prefix.chars() {
+            match node.children.get(&ch) {
+                Some(next) => node = next,
+                None => return Vec::new(),
+            }
+        }
+        let mut found = Vec::new();
+        Self::search_rec(node, start, &step, &accept, &mut found);
+        found
+    }
+
+    fn search_rec<'a, S, T: Clone>(
+        node: &'a TrieNode,
+        state: S,
+        step: &impl Fn(&S, char) -> Option<S>,
+        accept: &impl Fn(&S) -> Option<T>,
+        found: &mut Vec<(&'a str, T)>,
+    ) {
+        if let Some(value) = accept(&state) {
+            found.extend(node.terms.keys().map(|term| (term.as_str(), value.clone())));
+        }
+        for (&ch, child) in &node.children {
+            if let Some(next) = step(&state, ch) {
+                Self::search_rec(child, next, step, accept, found);
+            }
+        }
+    }
+
     pub fn remove_word(&mut self, word: &str, term: &str) -> bool {
-        Self::remove_rec(&mut self.root, word, term, 0)
+        let chars: Vec<char> = word.chars().collect();
+        Self::remove_rec(&mut self.root, &chars, term, 1, 0)
     }
 
-    fn remove_rec(node: &mut TrieNode, word: &str, term: &str, idx: usize) -> bool {
+    // release `count` references; true when `node` is left empty
+    fn remove_rec(
+        node: &mut TrieNode,
+        word: &[char],
+        term: &str,
+        count: usize,
+        idx: usize,
+    ) -> bool {
         if idx == word.len() {
-            node.terms.remove(term);
+            if let Some(refs) = node.terms.get_mut(term) {
+                *refs = refs.saturating_sub(count);
+                if *refs == 0 {
+                    node.terms.remove(term);
+                }
+            }
             return node.terms.is_empty() && node.children.is_empty();
         }
 
-        let ch = word.chars().nth(idx).unwrap();
+        let ch = word[idx];
 
         if let Some(child) = node.children.get_mut(&ch) {
-            let should_delete_child = Self::remove_rec(child, word, term, idx + 1);
+            let should_delete_child = Self::remove_rec(child, word, term, count, idx + 1);
 
             if should_delete_child {
                 node.children.remove(&ch);
@@ -85,3 +166,26 @@ impl NgramTrie {
         node.terms.is_empty() && node.children.is_empty()
     }
 }
+
+impl NgramStore for NgramTrie {
+    fn add(&mut self, gram: &str, term: &str, count: usize) {
+        let mut node = &mut self.root;
+        for ch in gram.chars() {
+            node = node.children.entry(ch).or_default();
+        }
+        *node.terms.entry(term.to_string()).or_insert(0) += count;
+    }
+
+    fn remove(&mut self, gram: &str, term: &str, count: usize) {
+        let chars: Vec<char> = gram.chars().collect();
+        Self::remove_rec(&mut self.root, &chars, term, count, 0);
+    }
+
+    fn terms(&self, gram: &str) -> Vec<&str> {
+        self.get_terms(gram)
+    }
+
+    fn clone_box(&self) -> Box<dyn NgramStore> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,404 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::index::analysis::analyzer::{AnalysisRegistry, AnalyzerConfig};
+use crate::index::analysis::synonyms::SearchSynonyms;
+use crate::index::n_gram::n_gram_store::NgramStoreKind;
+use crate::index::tokenizer::tokenizer::TokenizerConfig;
+
+pub const DEFAULT_ANALYZER: &str = "standard";
+pub const DEFAULT_MAX_DEPTH: usize = 4;
+
+/// The type a field path is indexed as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    Text,
+    /// Text indexed as a single, non-analyzed term.
+    Keyword,
+    Number,
+    Date,
+    Boolean,
+    /// Latitude / longitude, from `{"lat": .., "lon": ..}` or `"lat,lon"`.
+    GeoPoint,
+    Object,
+    /// Array of objects whose elements are also indexed as hidden
+    /// sub-documents, so conditions can be required to hold within one element.
+    Nested,
+}
+
+impl FieldType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FieldType::Text => "text",
+            FieldType::Keyword => "keyword",
+            FieldType::Number => "number",
+            FieldType::Date => "date",
+            FieldType::Boolean => "boolean",
+            FieldType::GeoPoint => "geo_point",
+            FieldType::Object => "object",
+            FieldType::Nested => "nested",
+        }
+    }
+
+    /// Whether a value of type `found` can be stored in a field of this type
+    /// as is.
+    pub fn accepts(&self, found: FieldType) -> bool {
+        *self == found
+            || (*self == FieldType::Keyword && found == FieldType::Text)
+            || (*self == FieldType::Nested && found == FieldType::Object)
+    }
+}
+
+/// How documents with fields missing from the mappings are handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MappingMode {
+    /// Unknown fields are accepted and their type is recorded on first sight.
+    #[default]
+    Dynamic,
+    /// Only declared fields are accepted.
+    Strict,
+}
+
+/// Per-field options recorded in the index settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldMapping {
+    /// `None` until the field is declared or first seen in a document.
+    #[serde(default)]
+    pub field_type: Option<FieldType>,
+    /// When false the value is kept in the document and forward index but is
+    /// not searchable.
+    #[serde(default = "default_true")]
+    pub index: bool,
+    /// For text fields: also index the whole value as a keyword under
+    /// `<path>.keyword`, for exact matching, sorting and aggregations.
+    #[serde(default)]
+    pub keyword_subfield: bool,
+    /// For text fields: analyzer used instead of the index default, by name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub analyzer: Option<String>,
+    /// For text fields: language (e.g. `french`) whose stemmer and stop
+    /// words analyze the field. Shorthand for that language's analyzer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// For text fields: also feed the analysed terms to the completion
+    /// suggester.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion: Option<CompletionMapping>,
+}
+
+/// How a field's completion suggestions are weighted.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CompletionMapping {
+    /// Numeric field whose value is the weight of the document's suggestions
+    /// (the largest one if it has several); without it every document
+    /// weighs 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight_field: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for FieldMapping {
+    fn default() -> Self {
+        Self {
+            field_type: None,
+            index: true,
+            keyword_subfield: false,
+            analyzer: None,
+            language: None,
+            completion: None,
+        }
+    }
+}
+
+impl FieldMapping {
+    pub fn typed(field_type: FieldType) -> Self {
+        Self {
+            field_type: Some(field_type),
+            ..Default::default()
+        }
+    }
+}
+
+/**
+ * Everything that decides how documents are turned into index terms. These are
+ * persisted next to the index on first open and every later open is checked
+ * against them: reopening with a different analyzer or n-gram range would
+ * silently produce terms that no longer match what is already indexed.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexSettings {
+    /// Name of the analyzer used for fields without one of their own.
+    pub analyzer: String,
+    /// Custom analyzers by name, usable next to the built-in ones.
+    #[serde(default)]
+    pub analyzers: HashMap<String, AnalyzerConfig>,
+    pub use_stemming: bool,
+    pub min_ngram: Option<usize>,
+    pub max_ngram: Option<usize>,
+    /// Structure the n-grams are kept in. It is rebuilt from the indexed
+    /// terms whenever the index is opened, so it can change at any time.
+    #[serde(default)]
+    pub ngram_store: NgramStoreKind,
+    pub max_depth: usize,
+    #[serde(default)]
+    pub mapping_mode: MappingMode,
+    /// Convert mismatched values (e.g. `"35"` into a number field) instead of
+    /// rejecting the document.
+    #[serde(default = "default_true")]
+    pub coerce: bool,
+    /// Extra chrono formats (e.g. `%d %b %Y`) tried before the built-in
+    /// ISO-8601 / RFC 2822 parsing when reading date strings.
+    #[serde(default)]
+    pub date_formats: Vec<String>,
+    /// Map new string fields that parse as a date to the date type.
+    #[serde(default = "default_true")]
+    pub date_detection: bool,
+    /// Detect the language of each document and analyze its text fields
+    /// without an analyzer of their own with that language's analyzer.
+    #[serde(default)]
+    pub language_detection: bool,
+    /// Synonyms applied to queries only, so editing them needs no reindex.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search_synonyms: Option<SearchSynonyms>,
+    #[serde(default)]
+    pub mappings: HashMap<String, FieldMapping>,
+}
+
+impl Default for IndexSettings {
+    fn default() -> Self {
+        Self::from_tokenizer_config(TokenizerConfig::default())
+    }
+}
+
+impl IndexSettings {
+    pub fn from_tokenizer_config(config: TokenizerConfig) -> Self {
+        Self {
+            analyzer: DEFAULT_ANALYZER.to_string(),
+            analyzers: HashMap::new(),
+            use_stemming: config.use_stemming,
+            min_ngram: config.min_ngram,
+            max_ngram: config.max_ngram,
+            ngram_store: NgramStoreKind::default(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            mapping_mode: MappingMode::default(),
+            coerce: true,
+            date_formats: Vec::new(),
+            date_detection: true,
+            language_detection: false,
+            search_synonyms: None,
+            mappings: HashMap::new(),
+        }
+    }
+
+    pub fn tokenizer_config(&self) -> TokenizerConfig {
+        TokenizerConfig {
+            use_stemming: self.use_stemming,
+            min_ngram: self.min_ngram,
+            max_ngram: self.max_ngram,
+        }
+    }
+
+    pub fn set_tokenizer_config(&mut self, config: TokenizerConfig) {
+        self.use_stemming = config.use_stemming;
+        self.min_ngram = config.min_ngram;
+        self.max_ngram = config.max_ngram;
+    }
+
+    pub fn allow_ngram(&self) -> bool {
+        self.min_ngram.is_some() || self.max_ngram.is_some()
+    }
+
+    /// Declare the type of `field_path`.
+    pub fn map_field(&mut self, field_path: &str, field_type: FieldType) -> &mut Self {
+        self.mappings
+            .entry(field_path.to_string())
+            .or_default()
+            .field_type = Some(field_type);
+        self
+    }
+
+    /// Analyze the text field `field_path` with the analyzer named `analyzer`.
+    pub fn analyze_field(&mut self, field_path: &str, analyzer: &str) -> &mut Self {
+        self.mappings
+            .entry(field_path.to_string())
+            .or_default()
+            .analyzer = Some(analyzer.to_string());
+        self
+    }
+
+    /// Analyze the text field `field_path` as `language` (e.g. `german`).
+    pub fn set_language(&mut self, field_path: &str, language: &str) -> &mut Self {
+        self.mappings
+            .entry(field_path.to_string())
+            .or_default()
+            .language = Some(language.to_string());
+        self
+    }
+
+    /// Feed the terms of the text field `field_path` to the completion
+    /// suggester, weighting each document by its `weight_field`.
+    pub fn complete_field(&mut self, field_path: &str, weight_field: Option<&str>) -> &mut Self {
+        self.mappings
+            .entry(field_path.to_string())
+            .or_default()
+            .completion = Some(CompletionMapping {
+            weight_field: weight_field.map(str::to_string),
+        });
+        self
+    }
+
+    pub fn field_type(&self, field_path: &str) -> Option<FieldType> {
+        self.mappings.get(field_path).and_then(|m| m.field_type)
+    }
+
+    /// Settings for the sub-documents of the nested field `path`: same
+    /// analysis, with the mappings below `path` re-rooted at the element.
+    pub fn nested_settings(&self, path: &str) -> IndexSettings {
+        let prefix = format!("{}.", path);
+        let mut settings = self.clone();
+        settings.mapping_mode = MappingMode::Dynamic;
+        settings.mappings = self
+            .mappings
+            .iter()
+            .filter_map(|(field, mapping)| {
+                field
+                    .strip_prefix(&prefix)
+                    .map(|rest| (rest.to_string(), mapping.clone()))
+            })
+            .collect();
+        settings
+    }
+
+    pub fn has_keyword_subfield(&self, field_path: &str) -> bool {
+        self.mappings
+            .get(field_path)
+            .is_some_and(|m| m.keyword_subfield)
+    }
+
+    pub fn is_indexed(&self, field_path: &str) -> bool {
+        self.mappings
+            .get(field_path)
+            .map(|m| m.index)
+            .unwrap_or(true)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        AnalysisRegistry::from_settings(self)?;
+        if let (Some(min_n), Some(max_n)) = (self.min_ngram, self.max_ngram)
+            && min_n > max_n
+        {
+            return Err("min_ngram should be <= max_ngram".into());
+        }
+        if self.min_ngram == Some(0) || self.max_ngram == Some(0) {
+            return Err("n-gram sizes must be at least 1".into());
+        }
+        if self.max_depth == 0 {
+            return Err("max_depth must be at least 1".into());
+        }
+        if self.date_formats.iter().any(|f| f.trim().is_empty()) {
+            return Err("date formats cannot be empty".into());
+        }
+        for (field, mapping) in &self.mappings {
+            if mapping.completion.is_some()
+                && !matches!(mapping.field_type, None | Some(FieldType::Text))
+            {
+                return Err(format!("field '{}': completion needs a text field", field));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that an index created with `self` can be opened with `requested`.
+    /// Mappings for fields not seen before may be added, everything else has to
+    /// match exactly.
+    pub fn check_compatible(&self, requested: &IndexSettings) -> Result<(), String> {
+        let mut conflicts = Vec::new();
+
+        if self.analyzer != requested.analyzer {
+            conflicts.push(format!(
+                "analyzer: index uses '{}', requested '{}'",
+                self.analyzer, requested.analyzer
+            ));
+        }
+        for (name, config) in &requested.analyzers {
+            if let Some(existing) = self.analyzers.get(name)
+                && existing != config
+            {
+                conflicts.push(format!("analyzer '{}' is defined differently", name));
+            }
+        }
+        if self.use_stemming != requested.use_stemming {
+            conflicts.push(format!(
+                "use_stemming: index uses {}, requested {}",
+                self.use_stemming, requested.use_stemming
+            ));
+        }
+        if self.language_detection != requested.language_detection {
+            conflicts.push(format!(
+                "language_detection: index uses {}, requested {}",
+                self.language_detection, requested.language_detection
+            ));
+        }
+        if (self.min_ngram, self.max_ngram) != (requested.min_ngram, requested.max_ngram) {
+            conflicts.push(format!(
+                "n-gram range: index uses {:?}..{:?}, requested {:?}..{:?}",
+                self.min_ngram, self.max_ngram, requested.min_ngram, requested.max_ngram
+            ));
+        }
+        if self.max_depth != requested.max_depth {
+            conflicts.push(format!(
+                "max_depth: index uses {}, requested {}",
+                self.max_depth, requested.max_depth
+            ));
+        }
+        for (field, mapping) in &requested.mappings {
+            if let Some(existing) = self.mappings.get(field)
+                && existing != mapping
+            {
+                conflicts.push(format!(
+                    "mapping for '{}': index uses {:?}, requested {:?}",
+                    field, existing, mapping
+                ));
+            }
+        }
+
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "incompatible index settings: {}",
+                conflicts.join("; ")
+            ))
+        }
+    }
+
+    /// Add the mappings and analyzers from `other` that this index does not
+    /// know yet and take over its mapping mode, coercion policy and date
+    /// parsing, which only affect documents added from now on, and its search
+    /// synonyms if it has any.
+    pub fn merge_mappings(&mut self, other: &IndexSettings) {
+        self.mapping_mode = other.mapping_mode;
+        self.coerce = other.coerce;
+        self.date_formats = other.date_formats.clone();
+        self.date_detection = other.date_detection;
+        if other.search_synonyms.is_some() {
+            self.search_synonyms = other.search_synonyms.clone();
+        }
+        for (name, config) in &other.analyzers {
+            self.analyzers
+                .entry(name.clone())
+                .or_insert_with(|| config.clone());
+        }
+        for (field, mapping) in &other.mappings {
+            self.mappings
+                .entry(field.clone())
+                .or_insert_with(|| mapping.clone());
+        }
+    }
+}
@@ -1,8 +1,10 @@
-use regex::Regex;
-use rust_stemmers::{Algorithm, Stemmer};
-use std::{clone, fmt};
+use serde::{Deserialize, Serialize};
+use std::{fmt, ops::RangeInclusive, sync::Arc};
 
-#[derive(Debug, Clone)]
+use crate::index::analysis::analyzer::{Analyzer, builtin_analyzer};
+use crate::index::settings::DEFAULT_ANALYZER;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TokenizerConfig {
     pub use_stemming: bool,
     pub min_ngram: Option<usize>,
@@ -33,37 +35,38 @@ impl fmt::Debug for Tokenizer {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Tokenizer")
             .field("config", &self.config)
-            .field(
-                "stemmer",
-                &if self.stemmer.is_some() {
-                    "Some(Stemmer)"
-                } else {
-                    "None"
-                },
-            )
             .finish()
     }
 }
 
-#[derive(Default)]
+/// Runs an analyzer and builds the n-grams of the resulting words. Plain
+/// `tokenize` uses the index's default analyzer.
 pub struct Tokenizer {
     config: TokenizerConfig,
-    stemmer: Option<Stemmer>,
+    analyzer: Arc<dyn Analyzer>,
+}
+
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Self::new(TokenizerConfig::default())
+    }
 }
 
 impl Tokenizer {
     pub fn new(config: TokenizerConfig) -> Self {
+        let analyzer = builtin_analyzer(DEFAULT_ANALYZER, config.use_stemming)
+            .and_then(|a| a.build().ok())
+            .expect("built-in analyzers are valid");
+        Self::with_analyzer(config, Arc::new(analyzer))
+    }
+
+    pub fn with_analyzer(config: TokenizerConfig, analyzer: Arc<dyn Analyzer>) -> Self {
         if let (Some(min_n), Some(max_n)) = (config.min_ngram, config.max_ngram) {
             if min_n > max_n {
                 panic!("min_ngram should be <= max_ngram");
             }
         }
-        let stemmer = if config.use_stemming {
-            Some(Stemmer::create(Algorithm::English))
-        } else {
-            None
-        };
-        Self { config, stemmer }
+        Self { config, analyzer }
     }
 
     pub fn tokenize(
@@ -71,50 +74,52 @@ impl Tokenizer {
         text: &str,
         allow_ngram: bool,
     ) -> (Vec<String>, Option<Vec<WordNgrams>>) {
-        // 1. Word split
-        let re = Regex::new(r"[A-Za-z0-9]+").unwrap();
-        let mut words: Vec<String> = re
-            .find_iter(&text.to_lowercase())
-            .map(|m| m.as_str().to_string())
-            .collect();
-
-        // 2. Optional stemming
-        if let Some(stemmer) = &self.stemmer {
-            words = words
-                .into_iter()
-                .map(|w| stemmer.stem(&w).into_owned())
-                .collect();
-        }
+        self.tokenize_with(self.analyzer.as_ref(), text, allow_ngram)
+    }
 
-        // 3. Build n-grams per word
-        let word_ngrams = if allow_ngram {
-            let min_n = self.config.min_ngram.unwrap_or(1);
-            let max_n = self.config.max_ngram.unwrap_or(min_n);
-
-            let mut results = Vec::new();
-            for word in &words {
-                let mut ngrams = Vec::new();
-                for n in min_n..=max_n {
-                    ngrams.extend(Self::ngram_tokenize(word, n));
-                }
-                results.push(WordNgrams {
-                    word: word.clone(),
-                    ngrams,
-                });
-            }
-            Some(results)
-        } else {
-            None
-        };
+    /// The n-gram sizes words are split into, in characters.
+    pub fn ngram_sizes(&self) -> RangeInclusive<usize> {
+        let min_n = self.config.min_ngram.unwrap_or(1);
+        let max_n = self.config.max_ngram.unwrap_or(min_n);
+        min_n..=max_n
+    }
+
+    /// `tokenize` with a specific analyzer, e.g. the one mapped to a field.
+    pub fn tokenize_with(
+        &self,
+        analyzer: &dyn Analyzer,
+        text: &str,
+        allow_ngram: bool,
+    ) -> (Vec<String>, Option<Vec<WordNgrams>>) {
+        // 1. Char filters, word split and token filters
+        let words = analyzer.analyze(text);
+
+        // 2. Build n-grams per word
+        let word_ngrams = allow_ngram.then(|| self.word_ngrams(&words));
 
         (words, word_ngrams)
     }
 
-    fn ngram_tokenize(word: &str, n: usize) -> Vec<String> {
-        if word.len() < n {
+    /// The n-grams of every analyzed word.
+    pub fn word_ngrams(&self, words: &[String]) -> Vec<WordNgrams> {
+        words
+            .iter()
+            .map(|word| WordNgrams {
+                word: word.clone(),
+                ngrams: self
+                    .ngram_sizes()
+                    .flat_map(|n| Self::ngram_tokenize(word, n))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    // n counts characters, not bytes
+    pub fn ngram_tokenize(word: &str, n: usize) -> Vec<String> {
+        let chars: Vec<char> = word.chars().collect();
+        if chars.len() < n {
             return Vec::new();
         }
-        let chars: Vec<char> = word.chars().collect();
         (0..=chars.len() - n)
             .map(|i| chars[i..i + n].iter().collect())
             .collect()
@@ -2,20 +2,49 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
+use crate::index::geo_index::GeoPoint;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Value {
     Text(String),
     Number(f64),
-    Date(String),
+    /// Whole number, kept exact past the 2^53 a float holds.
+    Integer(i64),
+    /// Milliseconds since the Unix epoch, UTC.
+    Date(i64),
+    Bool(bool),
+    GeoPoint(GeoPoint),
+    Null,
+    /// Multi-valued field: every element is indexed under the same path.
+    Array(Vec<Value>),
     Object(HashMap<String, Value>),
 }
 
 impl Value {
+    /// The value of a number, rounded to a float for integers.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::Integer(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+
     pub fn validate(&self) -> Result<(), String> {
         match self {
             Value::Number(_) => Ok(()), // valid
-            Value::Text(_) => Ok(()),   // valid
+            Value::Integer(_) => Ok(()),
+            Value::Text(_) => Ok(()), // valid
             Value::Date(_) => Ok(()),
+            Value::Bool(_) | Value::Null => Ok(()),
+            Value::GeoPoint(point) => point.validate(),
+
+            Value::Array(items) => {
+                for item in items {
+                    item.validate()?;
+                }
+                Ok(())
+            }
 
             Value::Object(map) => {
                 for (key, val) in map {
@@ -1,108 +1,117 @@
+use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashMap, HashSet};
-use std::ops::RangeInclusive;
+use std::ops::Bound;
 
 use serde::{Deserialize, Serialize};
 
+use crate::index::keyword_index::path_matches;
 use crate::index::value::Value;
+use crate::index::value_tree::sortable::{
+    cmp_i64_f64, decode_f64, decode_i64, encode_f64, encode_i64, integer_bound,
+};
 
+// encoded value -> Vec<(doc_id, field_path)>
+type Tree = BTreeMap<u64, Vec<(String, String)>>;
+
+/**
+ * Ordered index over numeric and date fields. Numbers (f64), integers (i64)
+ * and dates (epoch millis, i64) live in separate trees, each keyed by a
+ * lossless order-preserving `u64` encoding, so range bounds are exact.
+ *
+ * No tree has a serde default: a snapshot from before one existed fails to
+ * load, and the commit log is replayed instead of serving empty ranges.
+ */
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValueTreeIndex {
-    // field_path -> BTreeMap<normalized_value, Vec<(doc_id, field_path)>>
-    pub data: HashMap<String, BTreeMap<i64, Vec<(String, String)>>>,
+    // field_path -> encoded f64 -> docs
+    pub numbers: HashMap<String, Tree>,
+    // field_path -> encoded i64 -> docs
+    pub integers: HashMap<String, Tree>,
+    // field_path -> encoded epoch millis -> docs
+    pub dates: HashMap<String, Tree>,
+}
+
+/// Encode a bound with `encode`; `None` when no key can satisfy it (NaN).
+fn encode_bound<T: Copy>(bound: Bound<T>, encode: impl Fn(T) -> Option<u64>) -> Option<Bound<u64>> {
+    Some(match bound {
+        Bound::Included(v) => Bound::Included(encode(v)?),
+        Bound::Excluded(v) => Bound::Excluded(encode(v)?),
+        Bound::Unbounded => Bound::Unbounded,
+    })
+}
+
+/// `BTreeMap::range` panics on empty or inverted ranges; check first.
+fn is_empty_range(min: &Bound<u64>, max: &Bound<u64>) -> bool {
+    match (min, max) {
+        (Bound::Included(a), Bound::Included(b)) => a > b,
+        (Bound::Included(a), Bound::Excluded(b))
+        | (Bound::Excluded(a), Bound::Included(b))
+        | (Bound::Excluded(a), Bound::Excluded(b)) => a >= b,
+        _ => false,
+    }
 }
 
 impl ValueTreeIndex {
     pub fn new() -> Self {
         Self {
-            data: HashMap::new(),
+            numbers: HashMap::new(),
+            integers: HashMap::new(),
+            dates: HashMap::new(),
         }
     }
 
-    fn normalize_numeric(value: &Value) -> Option<i64> {
+    fn tree_and_key(&mut self, value: &Value) -> Option<(&mut HashMap<String, Tree>, u64)> {
         match value {
-            Value::Number(n) => Some((*n * 1000.0) as i64), // allow decimals
-            Value::Date(s) => Self::date_to_key(s),
+            Value::Number(n) => Some((&mut self.numbers, encode_f64(*n)?)),
+            Value::Integer(i) => Some((&mut self.integers, encode_i64(*i))),
+            Value::Date(millis) => Some((&mut self.dates, encode_i64(*millis))),
             _ => None,
         }
     }
 
-    fn date_to_key(date: &str) -> Option<i64> {
-        // Expected "YYYY-MM-DD"
-        let parts: Vec<&str> = date.split('-').collect();
-        if parts.len() != 3 {
-            return None;
-        }
-
-        let y = parts[0].parse::<i64>().ok()?;
-        let m = parts[1].parse::<i64>().ok()?;
-        let d = parts[2].parse::<i64>().ok()?;
-
-        Some(y * 10000 + m * 100 + d)
-    }
-
     pub fn add_index(&mut self, field_path: &str, value: &Value, doc_id: &str) {
-        if let Some(key) = Self::normalize_numeric(value) {
-            let tree = self
-                .data
+        if let Some((trees, key)) = self.tree_and_key(value) {
+            let docs = trees
                 .entry(field_path.to_string())
-                .or_insert_with(BTreeMap::new);
-            tree.entry(key)
-                .or_insert_with(Vec::new)
-                .push((doc_id.to_string(), field_path.to_string()));
+                .or_default()
+                .entry(key)
+                .or_default();
+            // an array can repeat a value; one entry per doc is enough. A
+            // document's values all go in while it is indexed, so a repeat
+            // can only be the last entry.
+            if docs.last().is_none_or(|(d, _)| d != doc_id) {
+                docs.push((doc_id.to_string(), field_path.to_string()));
+            }
         }
     }
 
-    pub fn range_query<'a>(
-        &'a self,
-        field_path: &str,
-        min: i64,
-        max: i64,
-    ) -> Vec<(&'a String, &'a String)> {
-        let min = min * 1000;
-        let max = max * 1000;
-
-        if let Some(tree) = self.data.get(field_path) {
-            tree.range(min..=max)
-                .flat_map(|(_, docs)| docs.iter().map(|(doc_id, field)| (doc_id, field)))
-                .collect()
-        } else {
-            Vec::new()
-        }
+    /// Whether dates were indexed at `leaf_field` (full path or dotted
+    /// suffix).
+    pub fn has_date_field(&self, leaf_field: &str) -> bool {
+        self.dates.keys().any(|path| path_matches(path, leaf_field))
     }
 
-    pub fn range_query_with_exclude<'a>(
-        &'a self,
+    fn collect_range<'a>(
+        trees: &'a HashMap<String, Tree>,
         leaf_field: &str,
-        min: Option<i64>,
-        max: Option<i64>,
-        exclude_values: Option<&[i64]>,
+        min: Bound<u64>,
+        max: Bound<u64>,
+        exclude: &HashSet<u64>,
     ) -> Vec<(&'a String, &'a String)> {
-        let given_min = min.map(|v| v * 1000).unwrap_or(i64::MIN);
-        let given_max = max.map(|v| v * 1000).unwrap_or(i64::MAX);
-
-        if given_min > given_max {
+        if is_empty_range(&min, &max) {
             return Vec::new();
         }
 
-        let exclude_set: HashSet<i64> = exclude_values
-            .map(|vals| vals.iter().map(|v| v * 1000).collect())
-            .unwrap_or_default();
-
         let mut results = Vec::new();
 
-        for (field_path, tree) in &self.data {
-            let matches = field_path
-                .rsplit('.')
-                .next()
-                .map(|leaf| leaf == leaf_field)
-                .unwrap_or(false);
-
-            if !matches {
+        for (field_path, tree) in trees {
+            // leaf name or any dotted suffix of the full path
+            if !path_matches(field_path, leaf_field) {
                 continue;
             }
 
-            for (value, docs) in tree.range(given_min..=given_max) {
-                if exclude_set.contains(value) {
+            for (value, docs) in tree.range((min, max)) {
+                if exclude.contains(value) {
                     continue;
                 }
 
@@ -113,12 +122,108 @@ impl ValueTreeIndex {
 
         results
     }
+
+    /// Documents whose number at `field_path` (exact path) is in `min..=max`.
+    pub fn range_query<'a>(
+        &'a self,
+        field_path: &str,
+        min: f64,
+        max: f64,
+    ) -> Vec<(&'a String, &'a String)> {
+        let (min, max) = (Bound::Included(min), Bound::Included(max));
+        let mut results = Vec::new();
+        for (trees, min, max) in [
+            (
+                &self.numbers,
+                encode_bound(min, encode_f64),
+                encode_bound(max, encode_f64),
+            ),
+            (
+                &self.integers,
+                integer_bound(min, true),
+                integer_bound(max, false),
+            ),
+        ] {
+            let (Some(tree), Some(min), Some(max)) = (trees.get(field_path), min, max) else {
+                continue;
+            };
+            if !is_empty_range(&min, &max) {
+                results.extend(
+                    tree.range((min, max))
+                        .flat_map(|(_, docs)| docs.iter().map(|(doc_id, field)| (doc_id, field))),
+                );
+            }
+        }
+        results
+    }
+
+    /// Documents whose number at `leaf_field` (full path or dotted suffix)
+    /// lies within the bounds, skipping the values in `exclude_values`.
+    pub fn range_query_with_exclude<'a>(
+        &'a self,
+        leaf_field: &str,
+        min: Bound<f64>,
+        max: Bound<f64>,
+        exclude_values: Option<&[f64]>,
+    ) -> Vec<(&'a String, &'a String)> {
+        let exclude_values = exclude_values.unwrap_or_default();
+        let mut results = Vec::new();
+
+        if let (Some(min), Some(max)) =
+            (encode_bound(min, encode_f64), encode_bound(max, encode_f64))
+        {
+            let exclude: HashSet<u64> = exclude_values
+                .iter()
+                .filter_map(|v| encode_f64(*v))
+                .collect();
+            results.extend(Self::collect_range(
+                &self.numbers,
+                leaf_field,
+                min,
+                max,
+                &exclude,
+            ));
+        }
+
+        // integers answer the same float bounds, exactly
+        if let (Some(min), Some(max)) = (integer_bound(min, true), integer_bound(max, false)) {
+            let exclude: HashSet<u64> = exclude_values
+                .iter()
+                .filter(|v| v.fract() == 0.0 && integer_bound(Bound::Included(**v), true).is_some())
+                .map(|v| encode_i64(*v as i64))
+                .collect();
+            results.extend(Self::collect_range(
+                &self.integers,
+                leaf_field,
+                min,
+                max,
+                &exclude,
+            ));
+        }
+
+        results
+    }
+
+    /// Documents whose date at `leaf_field` (full path or dotted suffix) lies
+    /// within the inclusive epoch-millis bounds.
+    pub fn date_range_query<'a>(
+        &'a self,
+        leaf_field: &str,
+        min: Option<i64>,
+        max: Option<i64>,
+    ) -> Vec<(&'a String, &'a String)> {
+        let min = min.map_or(Bound::Unbounded, |v| Bound::Included(encode_i64(v)));
+        let max = max.map_or(Bound::Unbounded, |v| Bound::Included(encode_i64(v)));
+
+        Self::collect_range(&self.dates, leaf_field, min, max, &HashSet::new())
+    }
+
     pub fn remove_index(&mut self, field_path: &str, value: &Value, doc_id: &str) {
-        let Some(key) = Self::normalize_numeric(value) else {
+        let Some((trees, key)) = self.tree_and_key(value) else {
             return;
         };
 
-        if let Some(tree) = self.data.get_mut(field_path) {
+        if let Some(tree) = trees.get_mut(field_path) {
             if let Some(vec) = tree.get_mut(&key) {
                 vec.retain(|(d, _)| d != doc_id);
 
@@ -128,7 +233,7 @@ impl ValueTreeIndex {
             }
 
             if tree.is_empty() {
-                self.data.remove(field_path);
+                trees.remove(field_path);
             }
         }
     }
@@ -141,36 +246,80 @@ impl ValueTreeIndex {
     ) -> Vec<String> {
         let mut result = Vec::new();
 
-        let Some(tree) = self.data.get(field_path) else {
-            return result;
+        let ordered = self.numeric_order(field_path);
+        let ordered = if ordered.is_empty() {
+            self.dates
+                .get(field_path)
+                .map(|tree| tree.values().collect())
+                .unwrap_or_default()
+        } else {
+            ordered
         };
 
-        // Forward iteration = ASC
-        if ascending {
-            for (_value, docs) in tree.iter() {
-                for (doc_id, _) in docs {
-                    if let Some(filter) = candidates {
-                        if !filter.contains(doc_id) {
-                            continue;
-                        }
-                    }
-                    result.push(doc_id.clone());
-                }
-            }
+        // Forward iteration = ASC, reverse = DESC
+        let ordered: Box<dyn Iterator<Item = &Vec<(String, String)>>> = if ascending {
+            Box::new(ordered.into_iter())
         } else {
-            // Reverse iteration = DESC
-            for (_value, docs) in tree.iter().rev() {
-                for (doc_id, _) in docs {
-                    if let Some(filter) = candidates {
-                        if !filter.contains(doc_id) {
-                            continue;
-                        }
+            Box::new(ordered.into_iter().rev())
+        };
+        for docs in ordered {
+            for (doc_id, _) in docs {
+                if let Some(filter) = candidates {
+                    if !filter.contains(doc_id) {
+                        continue;
                     }
-                    result.push(doc_id.clone());
                 }
+                result.push(doc_id.clone());
             }
         }
 
         result
     }
+
+    // the float and integer entries of `field_path`, merged in value order
+    fn numeric_order(&self, field_path: &str) -> Vec<&Vec<(String, String)>> {
+        let mut floats = (self.numbers.get(field_path).into_iter().flatten())
+            .map(|(key, docs)| (decode_f64(*key), docs))
+            .peekable();
+        let mut integers = (self.integers.get(field_path).into_iter().flatten())
+            .map(|(key, docs)| (decode_i64(*key), docs))
+            .peekable();
+
+        let mut ordered = Vec::new();
+        loop {
+            let integer_first = match (integers.peek(), floats.peek()) {
+                (Some((integer, _)), Some((float, _))) => {
+                    cmp_i64_f64(*integer, *float) != Some(Ordering::Greater)
+                }
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+            let docs = if integer_first {
+                integers.next().map(|(_, docs)| docs)
+            } else {
+                floats.next().map(|(_, docs)| docs)
+            };
+            ordered.extend(docs);
+        }
+        ordered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshots_without_every_tree_fail_to_load() {
+        // the layout before numbers, integers and dates had trees of their own
+        let old = r#"{"data": {"hp": {"35000": [["1", "hp"]]}}}"#;
+        assert!(serde_json::from_str::<ValueTreeIndex>(old).is_err());
+
+        let mut index = ValueTreeIndex::new();
+        index.add_index("hp", &Value::Integer(35), "1");
+        let json = serde_json::to_string(&index).unwrap();
+        let loaded: ValueTreeIndex = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.range_query("hp", 35.0, 35.0).len(), 1);
+    }
 }
@@ -1 +1,2 @@
 pub mod b_tree;
+pub mod sortable;
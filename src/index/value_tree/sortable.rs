@@ -0,0 +1,156 @@
+//! Order-preserving `u64` encodings for the value tree keys.
+//!
+//! Both encodings are lossless and compare as unsigned integers in the same
+//! order as the original values, so a `BTreeMap<u64, _>` range over encoded
+//! bounds is an exact range over the values themselves.
+
+use std::cmp::Ordering;
+use std::ops::Bound;
+
+const SIGN_BIT: u64 = 1 << 63;
+
+// 2^63, the first float past `i64::MAX`
+const I64_END: f64 = 9_223_372_036_854_775_808.0;
+
+/// Encode a float. Positive numbers get the sign bit set, negative numbers
+/// have all bits flipped so larger magnitudes sort first. `-0.0` is folded
+/// into `0.0`; NaN has no place in the order and is rejected.
+pub fn encode_f64(value: f64) -> Option<u64> {
+    if value.is_nan() {
+        return None;
+    }
+    let bits = if value == 0.0 { 0 } else { value.to_bits() };
+
+    Some(if bits & SIGN_BIT == 0 {
+        bits | SIGN_BIT
+    } else {
+        !bits
+    })
+}
+
+pub fn decode_f64(key: u64) -> f64 {
+    let bits = if key & SIGN_BIT != 0 {
+        key & !SIGN_BIT
+    } else {
+        !key
+    };
+    f64::from_bits(bits)
+}
+
+/// Encode an integer by flipping the sign bit (two's complement offset).
+pub fn encode_i64(value: i64) -> u64 {
+    (value as u64) ^ SIGN_BIT
+}
+
+pub fn decode_i64(key: u64) -> i64 {
+    (key ^ SIGN_BIT) as i64
+}
+
+/// The integer bound selecting the same integers as a float bound, encoded.
+/// `lower` tells which end of a range it is. `None` when no integer can
+/// satisfy it.
+pub fn integer_bound(bound: Bound<f64>, lower: bool) -> Option<Bound<u64>> {
+    let v = match bound {
+        Bound::Included(v) | Bound::Excluded(v) => v,
+        Bound::Unbounded => return Some(Bound::Unbounded),
+    };
+    if v.is_nan() {
+        return None;
+    }
+    // round towards the inside of the range
+    let value = if lower { v.ceil() } else { v.floor() };
+    // past either end of the integers
+    if value >= I64_END {
+        return (!lower).then_some(Bound::Unbounded);
+    }
+    if value < -I64_END {
+        return lower.then_some(Bound::Unbounded);
+    }
+
+    let key = encode_i64(value as i64);
+    Some(match bound {
+        Bound::Excluded(_) if value == v => Bound::Excluded(key),
+        _ => Bound::Included(key),
+    })
+}
+
+/// Compare an integer with a float without rounding the integer.
+pub fn cmp_i64_f64(integer: i64, float: f64) -> Option<Ordering> {
+    if float.is_nan() {
+        return None;
+    }
+    if float >= I64_END {
+        return Some(Ordering::Less);
+    }
+    if float < -I64_END {
+        return Some(Ordering::Greater);
+    }
+    let whole = float.floor();
+    Some(match integer.cmp(&(whole as i64)) {
+        Ordering::Equal if float > whole => Ordering::Less,
+        ordering => ordering,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodings_round_trip_and_keep_order() {
+        let floats = [
+            f64::NEG_INFINITY,
+            -1e300,
+            -10.001,
+            -10.0,
+            -f64::MIN_POSITIVE,
+            0.0,
+            f64::MIN_POSITIVE,
+            9.99,
+            10.0,
+            10.001,
+            f64::INFINITY,
+        ];
+        for pair in floats.windows(2) {
+            assert!(encode_f64(pair[0]) < encode_f64(pair[1]), "{:?}", pair);
+        }
+        for v in floats {
+            assert_eq!(decode_f64(encode_f64(v).unwrap()), v);
+        }
+        assert_eq!(encode_f64(-0.0), encode_f64(0.0));
+        assert_eq!(encode_f64(f64::NAN), None);
+
+        let ints = [i64::MIN, -1_000, -1, 0, 1, 1_700_000_000_000, i64::MAX];
+        for pair in ints.windows(2) {
+            assert!(encode_i64(pair[0]) < encode_i64(pair[1]));
+        }
+        for v in ints {
+            assert_eq!(decode_i64(encode_i64(v)), v);
+        }
+    }
+
+    #[test]
+    fn integers_meet_float_bounds_exactly() {
+        let key = |v: i64| Bound::Included(encode_i64(v));
+        assert_eq!(integer_bound(Bound::Included(2.5), true), Some(key(3)));
+        assert_eq!(integer_bound(Bound::Excluded(2.5), false), Some(key(2)));
+        assert_eq!(
+            integer_bound(Bound::Excluded(3.0), true),
+            Some(Bound::Excluded(encode_i64(3)))
+        );
+        assert_eq!(integer_bound(Bound::Included(1e19), true), None);
+        assert_eq!(
+            integer_bound(Bound::Included(1e19), false),
+            Some(Bound::Unbounded)
+        );
+        assert_eq!(integer_bound(Bound::Included(f64::NAN), true), None);
+
+        // 2^53 + 1 has no float of its own
+        let big = (1_i64 << 53) + 1;
+        assert_eq!(cmp_i64_f64(big, big as f64), Some(Ordering::Greater));
+        assert_eq!(cmp_i64_f64(3, 3.5), Some(Ordering::Less));
+        assert_eq!(cmp_i64_f64(-3, -3.5), Some(Ordering::Greater));
+        assert_eq!(cmp_i64_f64(i64::MAX, I64_END), Some(Ordering::Less));
+        assert_eq!(cmp_i64_f64(0, f64::NAN), None);
+    }
+}
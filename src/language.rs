@@ -2,25 +2,36 @@ pub mod language {
 
     use crate::engine::query_service::QueryService;
     use crate::query_lang::ast::Expr;
-    use crate::query_lang::exec::execute;
+    use crate::query_lang::exec::{execute, execute_profiled};
     use crate::query_lang::parser::Parser;
+    use crate::query_lang::profile::Profile;
     use crate::query_lang::token::Token;
     pub fn run_query(query: &str, qs: &QueryService) -> Result<Vec<String>, String> {
-        let tokens = tokenize(query);
+        execute(&parse_query(query)?, qs)
+    }
+
+    /// `run_query` with a profile of every clause of the query.
+    pub fn run_query_profiled(
+        query: &str,
+        qs: &QueryService,
+    ) -> Result<(Vec<String>, Profile), String> {
+        execute_profiled(&parse_query(query)?, qs)
+    }
+
+    fn parse_query(query: &str) -> Result<Expr, String> {
+        let tokens = tokenize(query)?;
 
         let parser = Parser::new(tokens);
-        let expr = parser
+        parser
             .parse()
-            .map_err(|e| format!("Query parse error: {:?}", e))?;
-
-        Ok(execute(&expr, qs))
+            .map_err(|e| format!("Query parse error: {:?}", e))
     }
 
-    pub fn tokenize(input: &str) -> Vec<Token> {
+    pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
         let mut tokens = Vec::new();
-        let mut chars = input.chars().peekable();
+        let mut chars = input.char_indices().peekable();
 
-        while let Some(c) = chars.next() {
+        while let Some((pos, c)) = chars.next() {
             match c {
                 // skip whitespace
                 c if c.is_whitespace() => {}
@@ -32,16 +43,14 @@ pub mod language {
                 // operators
                 '=' => tokens.push(Token::Eq),
                 '>' => {
-                    if chars.peek() == Some(&'=') {
-                        chars.next();
+                    if chars.next_if(|(_, ch)| *ch == '=').is_some() {
                         tokens.push(Token::Gte);
                     } else {
                         tokens.push(Token::Gt);
                     }
                 }
                 '<' => {
-                    if chars.peek() == Some(&'=') {
-                        chars.next();
+                    if chars.next_if(|(_, ch)| *ch == '=').is_some() {
                         tokens.push(Token::Lte);
                     } else {
                         tokens.push(Token::Lt);
@@ -49,28 +58,119 @@ pub mod language {
                 }
                 ',' => tokens.push(Token::Comma),
 
-                // numbers
-                c if c.is_ascii_digit() => {
+                // fuzziness: `~` alone allows two edits
+                '~' => {
+                    let mut digits = String::new();
+                    while let Some((_, n)) = chars.next_if(|(_, n)| n.is_ascii_digit()) {
+                        digits.push(n);
+                    }
+                    let edits = if digits.is_empty() {
+                        2
+                    } else {
+                        digits.parse().map_err(|_| {
+                            format!("invalid fuzziness '~{}' at position {}", digits, pos)
+                        })?
+                    };
+                    tokens.push(Token::Tilde(edits));
+                }
+
+                // quoted strings, with \ escapes
+                '"' | '\'' => {
+                    let mut text = String::new();
+                    let mut closed = false;
+                    while let Some((_, ch)) = chars.next() {
+                        match ch {
+                            '\\' => match chars.next() {
+                                Some((_, escaped)) => text.push(escaped),
+                                None => break,
+                            },
+                            ch if ch == c => {
+                                closed = true;
+                                break;
+                            }
+                            ch => text.push(ch),
+                        }
+                    }
+                    if !closed {
+                        return Err(format!("unterminated string starting at position {}", pos));
+                    }
+                    tokens.push(Token::Str(text));
+                }
+
+                // regular expressions; `\/` stands for a slash, other escapes
+                // are left to the regex
+                '/' => {
+                    let mut pattern = String::new();
+                    let mut closed = false;
+                    while let Some((_, ch)) = chars.next() {
+                        match ch {
+                            '\\' if chars.next_if(|(_, n)| *n == '/').is_some() => {
+                                pattern.push('/')
+                            }
+                            '/' => {
+                                closed = true;
+                                break;
+                            }
+                            ch => pattern.push(ch),
+                        }
+                    }
+                    if !closed {
+                        return Err(format!("unterminated regex starting at position {}", pos));
+                    }
+                    tokens.push(Token::Regex(pattern));
+                }
+
+                // numbers, negative numbers and date literals
+                c if c.is_ascii_digit()
+                    || (c == '-' && chars.peek().is_some_and(|(_, n)| n.is_ascii_digit())) =>
+                {
                     let mut num = c.to_string();
-                    while let Some(n) = chars.peek() {
-                        if n.is_ascii_digit() {
-                            num.push(chars.next().unwrap());
-                        } else {
-                            break;
+                    while let Some((_, n)) = chars.next_if(|(_, n)| n.is_ascii_digit()) {
+                        num.push(n);
+                    }
+
+                    // `2024-01-01`, `2024-01-01T10:00:00+02:00`
+                    if num.len() == 4 && chars.peek().is_some_and(|(_, n)| *n == '-') {
+                        let mut date = num;
+                        while let Some((_, n)) = chars.next_if(|(_, n)| {
+                            n.is_ascii_alphanumeric() || matches!(n, '-' | ':' | '+' | '.')
+                        }) {
+                            date.push(n);
                         }
+                        tokens.push(Token::Date(date));
+                        continue;
                     }
-                    tokens.push(Token::Number(num.parse().unwrap()));
+
+                    // decimal part, only when a digit follows the dot
+                    let mut ahead = chars.clone();
+                    if ahead.next().is_some_and(|(_, n)| n == '.')
+                        && ahead.peek().is_some_and(|(_, n)| n.is_ascii_digit())
+                    {
+                        num.push(chars.next().unwrap().1);
+                        while let Some((_, n)) = chars.next_if(|(_, n)| n.is_ascii_digit()) {
+                            num.push(n);
+                        }
+                    }
+
+                    let n = num
+                        .parse()
+                        .map_err(|_| format!("invalid number '{}' at position {}", num, pos))?;
+                    tokens.push(Token::Number(n));
                 }
 
-                // identifiers / keywords
-                c if c.is_ascii_alphabetic() || c == '_' => {
+                // identifiers / keywords (dotted field paths included), in
+                // any script so `name = pokémon` needs no quotes; with `*` or
+                // `?` in them they are wildcard patterns
+                c if c.is_alphabetic() || matches!(c, '_' | '*' | '?') => {
                     let mut ident = c.to_string();
-                    while let Some(ch) = chars.peek() {
-                        if ch.is_ascii_alphanumeric() || *ch == '_' {
-                            ident.push(chars.next().unwrap());
-                        } else {
-                            break;
-                        }
+                    while let Some((_, ch)) = chars.next_if(|(_, ch)| {
+                        ch.is_alphanumeric() || matches!(ch, '_' | '.' | '*' | '?')
+                    }) {
+                        ident.push(ch);
+                    }
+                    if ident.contains(['*', '?']) {
+                        tokens.push(Token::Wildcard(ident));
+                        continue;
                     }
 
                     match ident.to_uppercase().as_str() {
@@ -83,16 +183,565 @@ pub mod language {
                         "SORT" => tokens.push(Token::Sort),
                         "BY" => tokens.push(Token::By),
 
+                        // MIN, TRUE, EXISTS, BETWEEN and the like stay
+                        // identifiers: the parser reads them as keywords only
+                        // where a field name cannot stand
                         _ => tokens.push(Token::Ident(ident)),
                     }
                 }
 
                 _ => {
-                    panic!("unexpected character: {}", c);
+                    return Err(format!("unexpected character '{}' at position {}", c, pos));
                 }
             }
         }
 
-        tokens
+        Ok(tokens)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{run_query, run_query_profiled};
+        use crate::engine::query_service::{FuzzyOptions, QueryService, SortField};
+        use crate::index::documents_store::DocumentStore;
+        use crate::index::geo_index::GeoPoint;
+        use crate::index::settings::{FieldType, IndexSettings, MappingMode};
+        use crate::index::value::Value;
+        use crate::test_support::{doc, store_with, text_store};
+        use crate::utils::date_normalizer::parse_date;
+        use crate::utils::validator::validate_document;
+
+        fn sorted(mut ids: Vec<String>) -> Vec<String> {
+            ids.sort();
+            ids
+        }
+
+        #[test]
+        fn bool_and_null_fields() {
+            let mut store = store_with(
+                IndexSettings::default(),
+                [
+                    ("1", Value::Bool(true), Value::Text("sparky".into())),
+                    ("2", Value::Bool(false), Value::Null),
+                    ("3", Value::Bool(true), Value::Null),
+                ]
+                .map(|(id, active, nickname)| {
+                    (id, vec![("is_active", active), ("nickname", nickname)])
+                }),
+            );
+            let qs = QueryService::new(&store);
+
+            assert_eq!(
+                sorted(run_query("is_active = true", &qs).unwrap()),
+                ["1", "3"]
+            );
+            assert_eq!(
+                sorted(run_query("nickname = null", &qs).unwrap()),
+                ["2", "3"]
+            );
+            assert_eq!(sorted(run_query("EXISTS(nickname)", &qs).unwrap()), ["1"]);
+            assert_eq!(
+                sorted(run_query("is_active = true AND MISSING(nickname)", &qs).unwrap()),
+                ["3"]
+            );
+            // "true" is no longer a searchable word
+            assert!(qs.get_words(vec!["true"]).is_empty());
+
+            // an object exists through any value under it
+            let stats = doc(vec![("hp", Value::Number(35.0))]);
+            let trainer = doc(vec![("stats", Value::Object(stats))]);
+            store.add_document("4", &doc(vec![("trainer", Value::Object(trainer))]), None);
+            let qs = QueryService::new(&store);
+            assert_eq!(run_query("EXISTS(stats)", &qs).unwrap(), ["4"]);
+            assert_eq!(run_query("EXISTS(trainer.stats)", &qs).unwrap(), ["4"]);
+            assert!(run_query("EXISTS(ats)", &qs).unwrap().is_empty());
+
+            // strict mode wants even a null field declared
+            let mut settings = IndexSettings {
+                mapping_mode: MappingMode::Strict,
+                ..Default::default()
+            };
+            let mut nameless = doc(vec![("nickname", Value::Null)]);
+            let err = validate_document(&mut nameless, &mut settings).unwrap_err();
+            assert!(err.contains("not declared"));
+            settings.map_field("nickname", FieldType::Text);
+            assert!(validate_document(&mut nameless, &mut settings).is_ok());
+        }
+
+        #[test]
+        fn keywords_are_field_names_outside_their_clauses() {
+            let store = store_with(
+                IndexSettings::default(),
+                [("1", 5.0, "pikachu"), ("2", 1.0, "pichu")].map(|(id, max, prefix)| {
+                    let fields = vec![
+                        ("max", Value::Number(max)),
+                        ("prefix", Value::Text(prefix.into())),
+                    ];
+                    (id, fields)
+                }),
+            );
+            let qs = QueryService::new(&store);
+
+            assert_eq!(run_query("max > 3", &qs).unwrap(), ["1"]);
+            assert_eq!(run_query("prefix = pichu", &qs).unwrap(), ["2"]);
+            assert_eq!(
+                run_query("PREFIX(prefix, pik) AND max BETWEEN 4 AND 6", &qs).unwrap(),
+                ["1"]
+            );
+            let by_max = run_query("EXISTS(max) SORT BY ((max ASC MAX))", &qs);
+            assert_eq!(by_max.unwrap(), ["2", "1"]);
+            assert!(run_query("max = null", &qs).unwrap().is_empty());
+        }
+
+        #[test]
+        fn array_fields_are_multi_valued() {
+            let store = store_with(
+                IndexSettings::default(),
+                [
+                    ("1", vec!["rust", "search"], vec![9.0, 1.0, 9.0]),
+                    ("2", vec!["python"], vec![4.0, 5.0]),
+                    ("3", vec!["rust"], vec![7.0]),
+                ]
+                .map(|(id, tags, scores)| {
+                    let tags = tags.into_iter().map(|t| Value::Text(t.into())).collect();
+                    let scores = scores.into_iter().map(Value::Number).collect();
+                    (
+                        id,
+                        vec![
+                            ("tags", Value::Array(tags)),
+                            ("scores", Value::Array(scores)),
+                        ],
+                    )
+                }),
+            );
+            let qs = QueryService::new(&store);
+
+            // every element indexes under the array's own path
+            assert!(store.forward_index.get("1").unwrap().text_fields["tags"].len() == 2);
+            assert_eq!(sorted(run_query("tags = rust", &qs).unwrap()), ["1", "3"]);
+            assert_eq!(sorted(run_query("scores > 8", &qs).unwrap()), ["1"]);
+            // a repeated element is indexed once
+            assert_eq!(store.value_tree.range_query("scores", 9.0, 9.0).len(), 1);
+
+            let by_max = run_query("tags = rust OR tags = python SORT BY ((scores DESC))", &qs);
+            assert_eq!(by_max.unwrap(), ["1", "3", "2"]);
+            let by_avg = run_query(
+                "tags = rust OR tags = python SORT BY ((scores DESC AVG))",
+                &qs,
+            );
+            assert_eq!(by_avg.unwrap(), ["3", "1", "2"]);
+        }
+
+        #[test]
+        fn nested_queries_scope_to_one_element() {
+            let mut settings = IndexSettings::default();
+            settings.map_field("team", FieldType::Nested);
+
+            let member = |kind: &str, hp: f64| {
+                let stats = doc(vec![("hp", Value::Number(hp))]);
+                Value::Object(doc(vec![
+                    ("type", Value::Text(kind.into())),
+                    ("stats", Value::Object(stats)),
+                ]))
+            };
+            let mut store = store_with(
+                settings,
+                [
+                    ("1", vec![member("electric", 35.0), member("water", 80.0)]),
+                    ("2", vec![member("electric", 90.0)]),
+                ]
+                .map(|(id, team)| (id, vec![("team", Value::Array(team))])),
+            );
+            let qs = QueryService::new(&store);
+
+            // flattened fields match across elements
+            assert_eq!(
+                sorted(run_query("team.type = electric AND team.stats.hp > 50", &qs).unwrap()),
+                ["1", "2"]
+            );
+            // nested scoping needs both conditions on the same pokemon
+            assert_eq!(
+                run_query("NESTED(team, type = electric AND stats.hp > 50)", &qs).unwrap(),
+                ["2"]
+            );
+            // only nested fields can scope a query
+            assert!(run_query("NESTED(team.stats, hp > 50)", &qs).is_err());
+            assert!(run_query("NESTED(trainer, hp > 50)", &qs).is_err());
+
+            store.delete_index("2");
+            let qs = QueryService::new(&store);
+            assert!(
+                run_query("NESTED(team, type = electric AND stats.hp > 50)", &qs)
+                    .unwrap()
+                    .is_empty()
+            );
+            assert_eq!(run_query("NESTED(team, type = water)", &qs).unwrap(), ["1"]);
+            store.delete_index("1");
+            let qs = QueryService::new(&store);
+            assert!(
+                run_query("NESTED(team, type = water)", &qs)
+                    .unwrap()
+                    .is_empty()
+            );
+        }
+
+        #[test]
+        fn date_literals_quoted_strings_and_between() {
+            let mut settings = IndexSettings::default();
+            settings.map_field("owner", FieldType::Keyword);
+            let store = store_with(
+                settings,
+                [
+                    ("1", "2024-01-01T08:00:00Z", 35.0, "Ash Ketchum"),
+                    ("2", "2024-01-15T20:00:00Z", 60.0, "Misty"),
+                    ("3", "2024-02-01T00:00:00Z", 90.0, "Ash Ketchum"),
+                ]
+                .map(|(id, caught, hp, owner)| {
+                    let caught = Value::Date(parse_date(caught, &[]).unwrap());
+                    let fields = vec![
+                        ("caught", caught),
+                        ("hp", Value::Number(hp)),
+                        ("owner", Value::Text(owner.into())),
+                    ];
+                    (id, fields)
+                }),
+            );
+            let qs = QueryService::new(&store);
+
+            assert_eq!(
+                sorted(run_query("caught >= 2024-01-15", &qs).unwrap()),
+                ["2", "3"]
+            );
+            // a quoted string against a date field is read as date math
+            assert_eq!(
+                sorted(run_query(r#"caught <= "2024-01-15||/d""#, &qs).unwrap()),
+                ["1", "2"]
+            );
+            assert_eq!(
+                run_query("caught BETWEEN 2024-01-02 AND 2024-01-31", &qs).unwrap(),
+                ["2"]
+            );
+            assert_eq!(
+                sorted(run_query("hp BETWEEN 30 AND 60 AND owner = 'Ash Ketchum'", &qs).unwrap()),
+                ["1"]
+            );
+
+            assert!(run_query("hp > -5", &qs).is_ok());
+            let err = run_query("caught >= now - 7d", &qs).unwrap_err();
+            assert!(err.contains("unexpected character '-'"));
+            assert!(run_query(r#"owner = "misty"#, &qs).is_err());
+        }
+
+        #[test]
+        fn decimal_and_negative_numeric_bounds_are_exact() {
+            let store = store_with(
+                IndexSettings::default(),
+                [("1", 9.99), ("2", 10.0), ("3", 10.001), ("4", -2.5)]
+                    .map(|(id, price)| (id, vec![("price", Value::Number(price))])),
+            );
+            let qs = QueryService::new(&store);
+
+            assert_eq!(sorted(run_query("price > 9.99", &qs).unwrap()), ["2", "3"]);
+            assert_eq!(sorted(run_query("price > 10", &qs).unwrap()), ["3"]);
+            assert_eq!(
+                sorted(run_query("price <= 10", &qs).unwrap()),
+                ["1", "2", "4"]
+            );
+            assert_eq!(run_query("price < -1", &qs).unwrap(), ["4"]);
+            assert_eq!(
+                sorted(run_query("price BETWEEN -2.5 AND 9.99", &qs).unwrap()),
+                ["1", "4"]
+            );
+            assert!(
+                run_query("price BETWEEN 11 AND 10", &qs)
+                    .unwrap()
+                    .is_empty()
+            );
+        }
+
+        #[test]
+        fn integers_stay_exact_past_float_precision() {
+            // 2^53 + 1 rounds to 2^53 as a float
+            let big = (1_i64 << 53) + 1;
+            let mut store = store_with(
+                IndexSettings::default(),
+                [
+                    ("1", Value::Integer(big)),
+                    ("2", Value::Integer(big - 1)),
+                    ("3", Value::Number(2.5)),
+                ]
+                .map(|(id, serial)| (id, vec![("serial", serial)])),
+            );
+            let qs = QueryService::new(&store);
+
+            assert_eq!(run_query("serial > 9007199254740992", &qs).unwrap(), ["1"]);
+            assert_eq!(
+                sorted(run_query("serial >= 2.5", &qs).unwrap()),
+                ["1", "2", "3"]
+            );
+            assert_eq!(run_query("serial BETWEEN 2 AND 3", &qs).unwrap(), ["3"]);
+            let by_serial = run_query("serial > 0 SORT BY ((serial DESC))", &qs);
+            assert_eq!(by_serial.unwrap(), ["1", "2", "3"]);
+
+            store.delete_index("1");
+            assert!(store.value_tree.integers["serial"].len() == 1);
+        }
+
+        #[test]
+        fn geo_distance_bounding_box_and_distance_sort() {
+            let mut settings = IndexSettings::default();
+            settings.map_field("home", FieldType::GeoPoint);
+
+            let mut docs = Vec::new();
+            for (id, home) in [
+                ("paris", Value::Text("48.8566, 2.3522".into())),
+                ("versailles", Value::Text("48.8049,2.1204".into())),
+                (
+                    "london",
+                    Value::Object(doc(vec![
+                        ("lat", Value::Number(51.5074)),
+                        ("lon", Value::Number(-0.1278)),
+                    ])),
+                ),
+            ] {
+                let mut fields = doc(vec![("home", home)]);
+                validate_document(&mut fields, &mut settings).unwrap();
+                docs.push((id, fields));
+            }
+            let mut bad = doc(vec![("home", Value::Text("91,0".into()))]);
+            assert!(validate_document(&mut bad, &mut settings).is_err());
+
+            let mut store = DocumentStore::with_settings(settings);
+            for (id, fields) in &docs {
+                store.add_document(id, fields, None);
+            }
+            let qs = QueryService::new(&store);
+
+            assert_eq!(
+                sorted(run_query("GEO_DISTANCE(home, 48.85, 2.35, 20km)", &qs).unwrap()),
+                ["paris", "versailles"]
+            );
+            assert_eq!(
+                run_query("GEO_BBOX(home, 52, -1, 50, 1)", &qs).unwrap(),
+                ["london"]
+            );
+            assert!(run_query("GEO_DISTANCE(home, 48.85, 2.35, 20parsecs)", &qs).is_err());
+
+            let from_versailles =
+                SortField::new("home", true).by_distance_from(GeoPoint::new(48.8049, 2.1204));
+            let ids = ["london", "paris", "versailles"].map(String::from).to_vec();
+            assert_eq!(
+                qs.sort_docs_2(ids, &[from_versailles]),
+                ["versailles", "paris", "london"]
+            );
+        }
+
+        #[test]
+        fn prefix_queries_and_search_as_you_type() {
+            let mut store = store_with(
+                IndexSettings::default(),
+                [
+                    ("1", "Pikachu", "electric mouse"),
+                    ("2", "Pikipek", "flying bird"),
+                    ("3", "Raichu", "electric pika"),
+                ]
+                .map(|(id, name, kind)| {
+                    let fields = vec![
+                        ("name", Value::Text(name.into())),
+                        ("kind", Value::Text(kind.into())),
+                    ];
+                    (id, fields)
+                }),
+            );
+            let qs = QueryService::new(&store);
+
+            assert_eq!(
+                sorted(run_query("PREFIX(name, \"Pik\")", &qs).unwrap()),
+                ["1", "2"]
+            );
+            assert_eq!(run_query("PREFIX(name, pika)", &qs).unwrap(), ["1"]);
+            assert_eq!(
+                sorted(run_query("PREFIX(kind, elec) AND NOT PREFIX(name, rai)", &qs).unwrap()),
+                ["1"]
+            );
+
+            let ids = |hits: Vec<(String, f64)>| -> Vec<String> {
+                sorted(hits.into_iter().map(|(id, _)| id).collect())
+            };
+            assert_eq!(ids(qs.search_as_you_type("pi", 10)), ["1", "2", "3"]);
+            assert_eq!(ids(qs.search_as_you_type("electric pik", 10)), ["1", "3"]);
+            assert_eq!(ids(qs.search_as_you_type("electric pika ", 10)), ["3"]);
+            assert!(qs.search_as_you_type("", 10).is_empty());
+
+            // a term goes once no document has it, and survives a snapshot
+            store.delete_index("1");
+            let qs = QueryService::new(&store);
+            assert!(run_query("PREFIX(name, pika)", &qs).unwrap().is_empty());
+            let mut restored = DocumentStore::with_settings(IndexSettings::default());
+            restored.load_snapshot(store.to_snapshot());
+            let qs = QueryService::new(&restored);
+            assert_eq!(run_query("PREFIX(name, pik)", &qs).unwrap(), ["2"]);
+        }
+
+        #[test]
+        fn fuzzy_term_queries() {
+            let store = text_store(
+                IndexSettings::default(),
+                "name",
+                [
+                    ("1", "Pikachu"),
+                    ("2", "Pichu"),
+                    ("3", "Raichu"),
+                    ("4", "Pikachu Libre"),
+                ],
+            );
+            let qs = QueryService::new(&store);
+
+            assert_eq!(
+                sorted(run_query("name = pikachuu~1", &qs).unwrap()),
+                ["1", "4"]
+            );
+            assert_eq!(
+                sorted(run_query("name = pikchu~", &qs).unwrap()),
+                ["1", "2", "4"]
+            );
+            assert!(run_query("name = pikchu~0", &qs).unwrap().is_empty());
+            assert!(run_query("name > pikachu~1", &qs).is_err());
+
+            // a swapped pair is one edit only with transpositions
+            let swapped = FuzzyOptions::new(1);
+            assert_eq!(
+                qs.fuzzy_terms("pikahcu", &swapped).unwrap(),
+                [("pikachu".to_string(), 1)]
+            );
+            let no_swaps = FuzzyOptions {
+                transpositions: false,
+                ..swapped
+            };
+            assert!(qs.fuzzy_terms("pikahcu", &no_swaps).unwrap().is_empty());
+
+            // the prefix must match exactly; expansions are capped, closest first
+            let fixed_prefix = FuzzyOptions {
+                prefix_length: 2,
+                ..Default::default()
+            };
+            assert_eq!(qs.fuzzy_terms("raichu", &fixed_prefix).unwrap().len(), 1);
+            assert!(qs.fuzzy_terms("ruichu", &fixed_prefix).unwrap().is_empty());
+            let capped = FuzzyOptions {
+                max_expansions: 1,
+                ..Default::default()
+            };
+            assert_eq!(
+                qs.fuzzy_terms("pichu", &capped).unwrap(),
+                [("pichu".to_string(), 0)]
+            );
+
+            let ranked = qs
+                .fuzzy_search("pikachuu libre", &FuzzyOptions::default(), 1.2, 0.75, 10)
+                .unwrap();
+            assert_eq!(ranked[0].0, "4");
+            assert!(qs.fuzzy_terms("pika", &FuzzyOptions::new(3)).is_err());
+        }
+
+        #[test]
+        fn wildcard_and_regexp_queries() {
+            let settings = IndexSettings {
+                min_ngram: Some(2),
+                max_ngram: Some(3),
+                ..Default::default()
+            };
+            let store = text_store(
+                settings,
+                "name",
+                [
+                    ("1", "Pikachu"),
+                    ("2", "Pichu"),
+                    ("3", "Raichu"),
+                    ("4", "Rapidash"),
+                    ("5", "Pikipek"),
+                ],
+            );
+            let qs = QueryService::new(&store);
+
+            assert_eq!(sorted(run_query("name = pik*", &qs).unwrap()), ["1", "5"]);
+            assert_eq!(
+                sorted(run_query("name = *chu", &qs).unwrap()),
+                ["1", "2", "3"]
+            );
+            assert_eq!(run_query("name = p?chu", &qs).unwrap(), ["2"]);
+            assert_eq!(
+                run_query("name = *i?hu AND NOT name = /r.*/", &qs).unwrap(),
+                ["2"]
+            );
+            assert_eq!(sorted(run_query("name = /pi.a.*/", &qs).unwrap()), ["1"]);
+            assert_eq!(
+                sorted(run_query("name = /(pi|ra)[ci].*/", &qs).unwrap()),
+                ["2", "3"]
+            );
+            assert!(run_query("name > pik*", &qs).is_err());
+            assert!(run_query("name = /pika", &qs).is_err());
+            assert!(qs.regexp_terms("pi(ka").is_err());
+
+            // the n-gram trie narrows `*chu` down, the term trie `pik*`
+            assert_eq!(qs.wildcard_terms("*CHU").unwrap().len(), 3);
+            assert_eq!(qs.wildcard_terms("pik*").unwrap(), qs.prefix_terms("pik"));
+        }
+
+        #[test]
+        fn profiles_every_clause() {
+            let store = store_with(
+                IndexSettings::default(),
+                [
+                    ("1", "Pikachu", 25.0),
+                    ("2", "Pichu", 10.0),
+                    ("3", "Raichu", 40.0),
+                ]
+                .map(|(id, name, level)| {
+                    let fields = vec![
+                        ("name", Value::Text(name.into())),
+                        ("level", Value::Number(level)),
+                    ];
+                    (id, fields)
+                }),
+            );
+            let qs = QueryService::new(&store);
+
+            let query = "(name = pichu OR level > 20) AND NOT name = raichu";
+            let (ids, profile) = run_query_profiled(query, &qs).unwrap();
+            assert_eq!(sorted(ids.clone()), sorted(run_query(query, &qs).unwrap()));
+            assert_eq!(sorted(ids), ["1", "2"]);
+
+            assert_eq!(profile.clause, "AND");
+            assert_eq!(profile.docs_out, 2);
+            let [or, not] = &profile.children[..] else {
+                panic!("AND should have two clauses: {:?}", profile.children);
+            };
+            assert_eq!((or.clause.as_str(), or.docs_in, or.docs_out), ("OR", 3, 3));
+            assert_eq!(or.children[0].clause, "name = \"pichu\"");
+            assert_eq!(
+                (or.children[0].docs_in, or.children[0].postings_read),
+                (3, 1)
+            );
+            assert_eq!(
+                (or.children[1].docs_out, or.children[1].postings_read),
+                (2, 2)
+            );
+            // NOT reads every document on top of its clause's postings
+            assert_eq!((not.docs_in, not.docs_out, not.postings_read), (1, 2, 4));
+            assert_eq!(profile.docs_in, 5);
+            assert_eq!(profile.postings_read, 7);
+            assert!(profile.time >= or.time);
+
+            let printed = profile.to_string();
+            assert_eq!(printed.lines().count(), 6);
+            assert!(printed.contains("\n    name = \"raichu\" ["));
+            assert!(printed.contains("docs 3 -> 3, postings 3]"));
+
+            // a presence check scans every document
+            for query in ["EXISTS(level)", "MISSING(level)"] {
+                let (_, profile) = run_query_profiled(query, &qs).unwrap();
+                assert_eq!(profile.postings_read, 3, "{}", query);
+            }
+        }
     }
 }
@@ -5,4 +5,6 @@ pub mod language;
 pub mod query_lang;
 pub mod snapshots;
 pub mod storage;
+#[cfg(test)]
+mod test_support;
 pub mod utils;
@@ -1,8 +1,16 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use mini_opensearch_api::{
-    engine::search_engine::SearchEngine,
-    index::{documents_store::DocumentStore, tokenizer::tokenizer::TokenizerConfig, value::Value},
-    language::language::{run_query, tokenize},
+    engine::{
+        highlight::HighlightOptions,
+        query_service::{FuzzyOptions, MAX_SUGGEST_EDITS, QueryService},
+        search_engine::SearchEngine,
+    },
+    index::{
+        documents_store::DocumentStore, settings::IndexSettings,
+        tokenizer::tokenizer::TokenizerConfig, value::Value,
+    },
+    language::language::{run_query, run_query_profiled, tokenize},
+    storage::local_store::LocalStore,
     utils::random_id::random_id,
 };
 use serde_json::Value as JsonValue;
@@ -14,6 +22,35 @@ use std::{collections::HashMap, env, fs};
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Smallest n-gram size (only applied when the index is created)
+    #[arg(long, global = true)]
+    min_ngram: Option<usize>,
+
+    /// Largest n-gram size (only applied when the index is created)
+    #[arg(long, global = true)]
+    max_ngram: Option<usize>,
+
+    /// Enable English stemming (only applied when the index is created)
+    #[arg(long, global = true)]
+    stemming: bool,
+}
+
+impl Cli {
+    /// Settings requested on the command line. `None` means "whatever the
+    /// index was created with", or the CLI defaults for a brand new index.
+    fn requested_settings(&self, settings_exist: bool) -> Option<IndexSettings> {
+        let given = self.min_ngram.is_some() || self.max_ngram.is_some() || self.stemming;
+        if settings_exist && !given {
+            return None;
+        }
+
+        Some(IndexSettings::from_tokenizer_config(TokenizerConfig {
+            use_stemming: self.stemming,
+            min_ngram: Some(self.min_ngram.unwrap_or(2)),
+            max_ngram: Some(self.max_ngram.unwrap_or(5)),
+        }))
+    }
 }
 
 #[derive(Subcommand)]
@@ -21,8 +58,8 @@ enum Commands {
     /// Add a JSON document
     Add {
         file: String,
-        #[arg(default_value = "4")]
-        max_depth: usize,
+        /// Defaults to the index's `max_depth` setting
+        max_depth: Option<usize>,
     },
 
     /// Query commands
@@ -42,6 +79,11 @@ enum Commands {
 enum QueryCommands {
     Lang {
         query: String,
+
+        /// Print the time, documents in and out and postings read of every
+        /// clause
+        #[arg(long)]
+        profile: bool,
     },
 
     Get {
@@ -66,6 +108,17 @@ enum QueryCommands {
         words: Vec<String>,
     },
 
+    /// Exact match on a keyword field
+    Term {
+        field: String,
+        value: String,
+    },
+
+    /// Document counts per value of a keyword field
+    Terms {
+        field: String,
+    },
+
     /// N-gram + BM25 fuzzy search
     NgramBm25 {
         query: String,
@@ -84,47 +137,214 @@ enum QueryCommands {
 
         #[arg(default_value = "10")]
         top_k: usize,
+
+        /// Print how each hit's score was computed
+        #[arg(long)]
+        explain: bool,
+
+        #[command(flatten)]
+        highlight: HighlightArgs,
+    },
+
+    /// BM25 ranked search, search synonyms included
+    Search {
+        query: String,
+
+        #[arg(default_value = "1.2")]
+        k1: f64,
+
+        #[arg(default_value = "0.75")]
+        b: f64,
+
+        #[arg(default_value = "10")]
+        top_k: usize,
+
+        /// Print how each hit's score was computed
+        #[arg(long)]
+        explain: bool,
+
+        #[command(flatten)]
+        highlight: HighlightArgs,
+    },
+
+    /// "Did you mean" corrections for each word and for the whole text
+    Suggest {
+        text: String,
+
+        #[arg(long, default_value = "5")]
+        size: usize,
+    },
+
+    /// Ranked results for partially typed input
+    SearchAsYouType {
+        text: String,
+
+        #[arg(default_value = "10")]
+        top_k: usize,
+    },
+
+    /// Ranked search tolerating typos in every word
+    Fuzzy {
+        query: String,
+
+        /// Edits (0 to 2) allowed per word
+        #[arg(long, default_value = "2")]
+        fuzziness: u8,
+
+        /// Leading characters that must match exactly
+        #[arg(long, default_value = "0")]
+        prefix_length: usize,
+
+        /// Most index terms a word may expand to
+        #[arg(long, default_value = "50")]
+        max_expansions: usize,
+
+        /// Count a swap of two adjacent characters as two edits
+        #[arg(long)]
+        no_transpositions: bool,
+
+        #[arg(long, default_value = "1.2")]
+        k1: f64,
+
+        #[arg(long, default_value = "0.75")]
+        b: f64,
+
+        #[arg(long, default_value = "10")]
+        top_k: usize,
+    },
+
+    /// Documents with a term matching a pattern like `pika*` or `*chu`
+    Wildcard {
+        field: String,
+        pattern: String,
+    },
+
+    /// Documents with a term matched as a whole by a regular expression
+    Regexp {
+        field: String,
+        pattern: String,
+    },
+
+    /// Completion suggestions for a prefix of a `completion` field
+    Complete {
+        field: String,
+        prefix: String,
+
+        /// Edits (0 to 2) allowed in the prefix
+        #[arg(long, default_value = "0")]
+        fuzziness: u32,
+
+        #[arg(long, default_value = "5")]
+        size: usize,
     },
 
     /// Field range query
     Range {
         field: String,
-        min: i64,
-        max: i64,
+        #[arg(allow_negative_numbers = true)]
+        min: f64,
+        #[arg(allow_negative_numbers = true)]
+        max: f64,
     },
 
     /// field > value
     Gt {
         field: String,
-        min: i64,
+        #[arg(allow_negative_numbers = true)]
+        min: f64,
     },
 
     /// field >= value
     Gte {
         field: String,
-        min: i64,
+        #[arg(allow_negative_numbers = true)]
+        min: f64,
     },
 
     /// field < value
     Lt {
         field: String,
-        max: i64,
+        #[arg(allow_negative_numbers = true)]
+        max: f64,
     },
 
     /// field <= value
     Lte {
         field: String,
-        max: i64,
+        #[arg(allow_negative_numbers = true)]
+        max: f64,
     },
 
     /// min <= field <= max
     Between {
         field: String,
-        min: i64,
-        max: i64,
+        #[arg(allow_negative_numbers = true)]
+        min: f64,
+        #[arg(allow_negative_numbers = true)]
+        max: f64,
+    },
+
+    /// Show the terms a text is analyzed into
+    Analyze {
+        text: String,
+        /// Use this field's analyzer instead of the index default
+        #[arg(long)]
+        field: Option<String>,
+    },
+
+    /// from <= date field <= to; bounds accept date math like `now-7d/d`
+    DateRange {
+        field: String,
+        #[arg(long)]
+        from: Option<String>,
+        #[arg(long)]
+        to: Option<String>,
     },
 }
 
+/// Highlighting of ranked hits.
+#[derive(Args)]
+struct HighlightArgs {
+    /// Print the best fragments of each hit with the matched words tagged
+    #[arg(long)]
+    highlight: bool,
+
+    #[arg(long, default_value = "<em>")]
+    pre_tag: String,
+
+    #[arg(long, default_value = "</em>")]
+    post_tag: String,
+
+    /// Characters per fragment, 0 for whole values
+    #[arg(long, default_value = "100")]
+    fragment_size: usize,
+
+    /// Fragments per field
+    #[arg(long, default_value = "5")]
+    fragments: usize,
+}
+
+fn print_highlights(qs: &QueryService, hits: &[(String, f64)], query: &str, args: HighlightArgs) {
+    let options = HighlightOptions {
+        pre_tag: args.pre_tag,
+        post_tag: args.post_tag,
+        fragment_size: args.fragment_size,
+        number_of_fragments: args.fragments,
+        ..Default::default()
+    };
+    for (doc_id, _) in hits {
+        let mut fields: Vec<(String, Vec<String>)> =
+            qs.highlight(doc_id, query, &options).into_iter().collect();
+        fields.sort();
+        println!("{}:", doc_id);
+        for (field, fragments) in fields {
+            for fragment in fragments {
+                println!("  {}: {}", field, fragment);
+            }
+        }
+    }
+}
+
 //#[derive(Subcommand)]
 // enum Commands {
 //     /// Add a JSON document
@@ -148,8 +368,13 @@ fn json_to_value_map(json: JsonValue) -> HashMap<String, Value> {
     fn convert(v: &JsonValue) -> Value {
         match v {
             JsonValue::String(s) => Value::Text(s.clone()),
-            JsonValue::Number(n) => Value::Number(n.as_f64().unwrap_or(0.0)),
-            JsonValue::Bool(b) => Value::Text(b.to_string()),
+            // whole numbers stay exact as integers, past what a float holds
+            JsonValue::Number(n) => match n.as_i64() {
+                Some(i) => Value::Integer(i),
+                None => Value::Number(n.as_f64().unwrap_or(0.0)),
+            },
+            JsonValue::Bool(b) => Value::Bool(*b),
+            JsonValue::Null => Value::Null,
             JsonValue::Object(map) => {
                 let mut obj = HashMap::new();
                 for (k, v) in map {
@@ -157,14 +382,7 @@ fn json_to_value_map(json: JsonValue) -> HashMap<String, Value> {
                 }
                 Value::Object(obj)
             }
-            JsonValue::Array(arr) => {
-                let mut obj = HashMap::new();
-                for (i, v) in arr.iter().enumerate() {
-                    obj.insert(i.to_string(), convert(v));
-                }
-                Value::Object(obj)
-            }
-            _ => Value::Text(v.to_string()),
+            JsonValue::Array(arr) => Value::Array(arr.iter().map(convert).collect()),
         }
     }
 
@@ -190,14 +408,17 @@ fn main() {
     let snapshots_path =
         env::var("SNAPSHOTS_DIR").unwrap_or_else(|_| "./snapshots_dir/snapshots".into());
 
-    let config = TokenizerConfig {
-        use_stemming: false,
-        min_ngram: Some(2),
-        max_ngram: Some(5),
-    };
+    let settings_exist = LocalStore::exists(&SearchEngine::settings_path_for(&index_path));
+    let settings = cli.requested_settings(settings_exist);
 
-    let mut engine = SearchEngine::new(index_path, commit_log_path, snapshots_path, Some(config))
-        .expect("Failed to open search engine");
+    let mut engine = match SearchEngine::open(index_path, commit_log_path, snapshots_path, settings)
+    {
+        Ok(engine) => engine,
+        Err(err) => {
+            eprintln!("Failed to open search engine: {}", err);
+            std::process::exit(1);
+        }
+    };
 
     match cli.command {
         Commands::Add { file, max_depth } => {
@@ -207,8 +428,10 @@ fn main() {
                 // ---- Case 1: Single object ----
                 JsonValue::Object(_) => {
                     let map = json_to_value_map(json);
-                    let id = engine.add_document(map, Some(max_depth)).unwrap();
-                    println!("Added ID: {}", id);
+                    match engine.add_document(map, max_depth) {
+                        Ok(id) => println!("Added ID: {}", id),
+                        Err(err) => eprintln!("Rejected document: {}", err),
+                    }
                 }
 
                 // ---- Case 2: Array of objects ----
@@ -220,8 +443,10 @@ fn main() {
                         }
 
                         let map = json_to_value_map(item);
-                        let id = engine.add_document(map, Some(max_depth)).unwrap();
-                        println!("Added ID: {}", id);
+                        match engine.add_document(map, max_depth) {
+                            Ok(id) => println!("Added ID: {}", id),
+                            Err(err) => eprintln!("Rejected index {}: {}", idx, err),
+                        }
                     }
                 }
 
@@ -250,9 +475,19 @@ fn main() {
             let qs = engine.query_service();
 
             match query {
-                QueryCommands::Lang { query } => {
-                    let result = run_query(&query, &qs);
-                    println!("Query Language results: {:#?}", result);
+                QueryCommands::Lang { query, profile } => {
+                    if profile {
+                        match run_query_profiled(&query, &qs) {
+                            Ok((result, profile)) => {
+                                println!("Query Language results: {:#?}", result);
+                                print!("Profile:\n{}", profile);
+                            }
+                            Err(e) => eprintln!("Query failed: {}", e),
+                        }
+                    } else {
+                        let result = run_query(&query, &qs);
+                        println!("Query Language results: {:#?}", result);
+                    }
                 }
 
                 QueryCommands::Get { id } => match qs.get_doc_by_id(&id) {
@@ -278,6 +513,17 @@ fn main() {
                     println!("{:#?}", res);
                 }
 
+                QueryCommands::Term { field, value } => {
+                    let res = qs.term(&field, &value);
+                    println!("{:#?}", res);
+                }
+
+                QueryCommands::Terms { field } => {
+                    for (term, count) in qs.terms_aggregation(&field, None) {
+                        println!("{}: {}", term, count);
+                    }
+                }
+
                 QueryCommands::NgramBm25 {
                     query,
                     k1,
@@ -285,11 +531,107 @@ fn main() {
                     alpha,
                     beta,
                     top_k,
+                    explain,
+                    highlight,
                 } => {
                     let res = qs.ngram_bm25(&query, k1, b, alpha, beta, top_k);
                     println!("{:#?}", res);
+                    if explain {
+                        for (doc_id, _) in &res {
+                            if let Some(explanation) =
+                                qs.explain_ngram_bm25(doc_id, &query, k1, b, alpha, beta, top_k)
+                            {
+                                print!("{}", explanation);
+                            }
+                        }
+                    }
+                    if highlight.highlight {
+                        print_highlights(&qs, &res, &query, highlight);
+                    }
                 }
 
+                QueryCommands::Search {
+                    query,
+                    k1,
+                    b,
+                    top_k,
+                    explain,
+                    highlight,
+                } => {
+                    let res = qs.search_with_suggestion(&query, k1, b, top_k);
+                    println!("{:#?}", res.hits);
+                    if explain {
+                        for (doc_id, _) in &res.hits {
+                            if let Some(explanation) = qs.explain(doc_id, &query, k1, b) {
+                                print!("{}", explanation);
+                            }
+                        }
+                    }
+                    if highlight.highlight {
+                        print_highlights(&qs, &res.hits, &query, highlight);
+                    }
+                    if let Some(correction) = res.did_you_mean {
+                        println!("Did you mean: {}", correction);
+                    }
+                }
+
+                QueryCommands::Suggest { text, size } => {
+                    for word in text.split_whitespace() {
+                        let res = qs.suggest_terms(word, MAX_SUGGEST_EDITS, size);
+                        println!("{}: {:#?}", word, res);
+                    }
+                    if let Some(correction) = qs.suggest_phrase(&text) {
+                        println!("Did you mean: {}", correction);
+                    }
+                }
+
+                QueryCommands::SearchAsYouType { text, top_k } => {
+                    let res = qs.search_as_you_type(&text, top_k);
+                    println!("{:#?}", res);
+                }
+
+                QueryCommands::Fuzzy {
+                    query,
+                    fuzziness,
+                    prefix_length,
+                    max_expansions,
+                    no_transpositions,
+                    k1,
+                    b,
+                    top_k,
+                } => {
+                    let options = FuzzyOptions {
+                        fuzziness,
+                        prefix_length,
+                        max_expansions,
+                        transpositions: !no_transpositions,
+                    };
+                    match qs.fuzzy_search(&query, &options, k1, b, top_k) {
+                        Ok(res) => println!("{:#?}", res),
+                        Err(err) => eprintln!("Invalid fuzzy query: {}", err),
+                    }
+                }
+
+                QueryCommands::Wildcard { field, pattern } => match qs.wildcard(&field, &pattern) {
+                    Ok(res) => println!("{:#?}", res),
+                    Err(err) => eprintln!("Invalid wildcard: {}", err),
+                },
+
+                QueryCommands::Regexp { field, pattern } => match qs.regexp(&field, &pattern) {
+                    Ok(res) => println!("{:#?}", res),
+                    Err(err) => eprintln!("Invalid regexp: {}", err),
+                },
+
+                QueryCommands::Complete {
+                    field,
+                    prefix,
+                    fuzziness,
+                    size,
+                } => match qs.complete(&field, &prefix, fuzziness, size) {
+                    Ok(res) => println!("{:#?}", res),
+                    Err(err) => eprintln!("Invalid completion: {}", err),
+                },
+
                 QueryCommands::Range { field, min, max } => {
                     let res = qs.range_query(&field, min, max);
                     println!("{:#?}", res);
@@ -320,6 +662,17 @@ fn main() {
                     let res = qs.between(&field, min, max, None);
                     println!("{:#?}", res);
                 }
+
+                QueryCommands::Analyze { text, field } => {
+                    println!("{:#?}", qs.analyze(field.as_deref(), &text));
+                }
+
+                QueryCommands::DateRange { field, from, to } => {
+                    match qs.date_range(&field, from.as_deref(), to.as_deref()) {
+                        Ok(res) => println!("{:#?}", res),
+                        Err(err) => eprintln!("Invalid date range: {}", err),
+                    }
+                }
             }
         }
     }
@@ -142,7 +142,7 @@ fn main() -> std::io::Result<()> {
     //     "N-gram index enabled? {}",
     //     engine.store().n_gram_index.is_some()
     // );
-    println!("N-gram trie enabled? {:#?}", engine.store().n_gram_trie);
+    println!("N-grams enabled? {:#?}", engine.store().n_grams);
 
     // ✅ Get a QueryService from the engine
     let query_service = engine.query_service();
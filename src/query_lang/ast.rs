@@ -10,10 +10,66 @@ pub enum Expr {
     Not(Box<Expr>),
     Sort {
         expr: Box<Expr>,
-        fields: Vec<(String, SortOrder)>,
+        fields: Vec<(String, SortOrder, Option<SortMode>)>,
     },
 
     Count(Box<Expr>),
+
+    /// `field BETWEEN low AND high`, both ends inclusive.
+    Between {
+        field: String,
+        low: Value,
+        high: Value,
+    },
+
+    Exists(String),
+    Missing(String),
+
+    /// `field = pika*`: a term of `field` matching the wildcard pattern.
+    Wildcard {
+        field: String,
+        pattern: String,
+    },
+
+    /// `field = /pi.a.*/`: a term of `field` matched by the regex.
+    Regexp {
+        field: String,
+        pattern: String,
+    },
+
+    /// `field = term~fuzziness`: a term of `field` within that many edits.
+    Fuzzy {
+        field: String,
+        term: String,
+        fuzziness: u8,
+    },
+
+    /// Documents with a term of `field` starting with `prefix`.
+    Prefix {
+        field: String,
+        prefix: String,
+    },
+
+    /// Points of `field` within `distance_m` metres of (lat, lon).
+    GeoDistance {
+        field: String,
+        lat: f64,
+        lon: f64,
+        distance_m: f64,
+    },
+    /// Points of `field` inside the box given by its top-left and
+    /// bottom-right corners, each as (lat, lon).
+    GeoBoundingBox {
+        field: String,
+        top_left: (f64, f64),
+        bottom_right: (f64, f64),
+    },
+
+    /// Conditions evaluated within single elements of a nested field.
+    Nested {
+        path: String,
+        expr: Box<Expr>,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -27,8 +83,12 @@ pub enum CmpOp {
 
 #[derive(Debug, Clone)]
 pub enum Value {
-    Number(i64),
+    Number(f64),
     Text(String),
+    /// Date literal or date math (`now-7d/d`), resolved at execution.
+    Date(String),
+    Bool(bool),
+    Null,
 }
 
 #[derive(Debug, Clone)]
@@ -36,3 +96,12 @@ pub enum SortOrder {
     Asc,
     Desc,
 }
+
+/// Which value of a multi-valued field a document is sorted by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortMode {
+    Min,
+    Max,
+    Avg,
+    Sum,
+}
@@ -1,8 +1,14 @@
 use std::collections::HashSet;
+use std::ops::Bound;
+use std::time::Instant;
 
 use crate::{
-    engine::query_service::{QueryService, SortField},
-    query_lang::ast::{CmpOp, Expr, SortOrder, Value},
+    engine::query_service::{FuzzyOptions, QueryService, SortField},
+    index::geo_index::GeoPoint,
+    query_lang::{
+        ast::{CmpOp, Expr, SortOrder, Value},
+        profile::{Profile, clause_label},
+    },
 };
 
 /// Convert Vec<(&String, &String)> → HashSet<String>
@@ -10,23 +16,102 @@ fn ids_from_pairs(pairs: Vec<(&String, &String)>) -> HashSet<String> {
     pairs.into_iter().map(|(id, _)| id.clone()).collect()
 }
 
-pub fn execute(expr: &Expr, qs: &QueryService) -> Vec<String> {
-    match expr {
+/// Dates come in as date literals, or as quoted strings compared against a
+/// date field (`created >= "now-7d/d"`).
+fn date_expr<'v>(field: &str, value: &'v Value, qs: &QueryService) -> Option<&'v str> {
+    match value {
+        Value::Date(d) => Some(d),
+        Value::Text(t) if qs.is_date_field(field) => Some(t),
+        _ => None,
+    }
+}
+
+fn compare_dates(
+    field: &str,
+    op: &CmpOp,
+    date: &str,
+    qs: &QueryService,
+) -> Result<Vec<String>, String> {
+    let (from, to) = match op {
+        CmpOp::Eq => (Bound::Included(date), Bound::Included(date)),
+        CmpOp::Gt => (Bound::Excluded(date), Bound::Unbounded),
+        CmpOp::Gte => (Bound::Included(date), Bound::Unbounded),
+        CmpOp::Lt => (Bound::Unbounded, Bound::Excluded(date)),
+        CmpOp::Lte => (Bound::Unbounded, Bound::Included(date)),
+    };
+    qs.date_range_bounds(field, from, to)
+}
+
+pub fn execute(expr: &Expr, qs: &QueryService) -> Result<Vec<String>, String> {
+    eval(expr, qs, &mut None)
+}
+
+/// `execute`, also returning how long each clause took, how many documents
+/// went in and out of it and how many postings it read.
+pub fn execute_profiled(expr: &Expr, qs: &QueryService) -> Result<(Vec<String>, Profile), String> {
+    let mut profiles = Some(Vec::new());
+    let ids = eval(expr, qs, &mut profiles)?;
+    let profile = profiles
+        .and_then(|mut profiles| profiles.pop())
+        .ok_or("query produced no profile")?;
+    Ok((ids, profile))
+}
+
+// run `expr`, adding its profile to `profiles` when profiling
+fn eval(
+    expr: &Expr,
+    qs: &QueryService,
+    profiles: &mut Option<Vec<Profile>>,
+) -> Result<Vec<String>, String> {
+    let Some(siblings) = profiles else {
+        return eval_clause(expr, qs, &mut None);
+    };
+
+    let started = Instant::now();
+    let postings_before = qs.postings_read();
+    let mut children = Some(Vec::new());
+    let ids = eval_clause(expr, qs, &mut children)?;
+    let children = children.unwrap_or_default();
+
+    let docs_in = if children.is_empty() {
+        qs.doc_count()
+    } else {
+        children.iter().map(|child| child.docs_out).sum()
+    };
+    siblings.push(Profile {
+        clause: clause_label(expr),
+        time: started.elapsed(),
+        docs_in,
+        docs_out: ids.len(),
+        postings_read: qs.postings_read() - postings_before,
+        children,
+    });
+    Ok(ids)
+}
+
+fn eval_clause(
+    expr: &Expr,
+    qs: &QueryService,
+    profiles: &mut Option<Vec<Profile>>,
+) -> Result<Vec<String>, String> {
+    let ids = match expr {
         //-------------------------------
         //Aggregation and sorting
         //-------------------------------
         Expr::Count(inner) => {
-            let result = execute(inner, qs);
+            let result = eval(inner, qs, profiles)?;
             println!("Count: {}", result.len());
             result
         }
         Expr::Sort { expr, fields } => {
-            let result_set = execute(expr, qs);
+            let result_set = eval(expr, qs, profiles)?;
             let sort_fields: Vec<SortField> = fields
                 .iter()
-                .map(|(field, order)| SortField {
+                .map(|(field, order, mode)| SortField {
                     field_path: field.clone(),
                     ascending: matches!(order, SortOrder::Asc),
+                    mode: *mode,
+                    origin: None,
                 })
                 .collect();
 
@@ -37,31 +122,78 @@ pub fn execute(expr: &Expr, qs: &QueryService) -> Vec<String> {
         // ------------------------------
         // Comparisons
         // ------------------------------
-        Expr::Compare { field, op, value } => match (op, value) {
-            // text equality
-            (CmpOp::Eq, Value::Text(v)) => qs.get_words(vec![v.as_str()]).into_iter().collect(),
+        Expr::Compare { field, op, value } => match date_expr(field, value, qs) {
+            // dates go through the value tree
+            Some(date) => compare_dates(field, op, date, qs)?,
+            None => match (op, value) {
+                // booleans and null
+                (CmpOp::Eq, Value::Bool(b)) => qs.bool_query(field, *b),
+                (CmpOp::Eq, Value::Null) => qs.missing(field),
 
-            // numeric comparisons
-            (CmpOp::Gt, Value::Number(n)) => ids_from_pairs(qs.greater_than(field, *n, None)).into_iter()
-    .collect::<Vec<String>>(),
-            (CmpOp::Gte, Value::Number(n)) => {
-                ids_from_pairs(qs.greater_than_equal(field, *n, None)).into_iter()
-    .collect::<Vec<String>>()
-            }
-            (CmpOp::Lt, Value::Number(n)) => ids_from_pairs(qs.less_than(field, *n, None)).into_iter()
-    .collect::<Vec<String>>(),
-            (CmpOp::Lte, Value::Number(n)) => ids_from_pairs(qs.less_than_equal(field, *n, None)).into_iter()
-    .collect::<Vec<String>>(),
+                // keyword fields match the whole value exactly
+                (CmpOp::Eq, Value::Text(v)) if qs.is_keyword_field(field) => qs.term(field, v),
+
+                // text equality: every word of the (possibly quoted) value
+                (CmpOp::Eq, Value::Text(v)) => qs.match_text(field, v),
 
-            _ => panic!("invalid comparison"),
+                // numeric comparisons
+                (CmpOp::Eq, Value::Number(n)) => ids_from_pairs(qs.between(field, *n, *n, None))
+                    .into_iter()
+                    .collect(),
+                (CmpOp::Gt, Value::Number(n)) => ids_from_pairs(qs.greater_than(field, *n, None))
+                    .into_iter()
+                    .collect(),
+                (CmpOp::Gte, Value::Number(n)) => {
+                    ids_from_pairs(qs.greater_than_equal(field, *n, None))
+                        .into_iter()
+                        .collect()
+                }
+                (CmpOp::Lt, Value::Number(n)) => ids_from_pairs(qs.less_than(field, *n, None))
+                    .into_iter()
+                    .collect(),
+                (CmpOp::Lte, Value::Number(n)) => {
+                    ids_from_pairs(qs.less_than_equal(field, *n, None))
+                        .into_iter()
+                        .collect()
+                }
+
+                (op, value) => {
+                    return Err(format!(
+                        "invalid comparison: {} {:?} {:?}",
+                        field, op, value
+                    ));
+                }
+            },
+        },
+
+        // ------------------------------
+        // BETWEEN
+        // ------------------------------
+        Expr::Between { field, low, high } => match (low, high) {
+            (Value::Number(low), Value::Number(high)) => {
+                ids_from_pairs(qs.between(field, *low, *high, None))
+                    .into_iter()
+                    .collect()
+            }
+            (low, high) => match (date_expr(field, low, qs), date_expr(field, high, qs)) {
+                (Some(low), Some(high)) => {
+                    qs.date_range_bounds(field, Bound::Included(low), Bound::Included(high))?
+                }
+                _ => {
+                    return Err(format!(
+                        "BETWEEN needs two numbers or two dates, got {:?} and {:?}",
+                        low, high
+                    ));
+                }
+            },
         },
 
         // ------------------------------
         // AND
         // ------------------------------
         Expr::And(a, b) => {
-            let mut left = execute(a, qs);
-            let right = execute(b, qs);
+            let mut left = eval(a, qs, profiles)?;
+            let right = eval(b, qs, profiles)?;
             left.retain(|id| right.contains(id));
             left
         }
@@ -70,8 +202,8 @@ pub fn execute(expr: &Expr, qs: &QueryService) -> Vec<String> {
         // OR
         // ------------------------------
         Expr::Or(a, b) => {
-            let mut left = execute(a, qs);
-            left.extend(execute(b, qs));
+            let mut left = eval(a, qs, profiles)?;
+            left.extend(eval(b, qs, profiles)?);
             left
         }
 
@@ -79,17 +211,53 @@ pub fn execute(expr: &Expr, qs: &QueryService) -> Vec<String> {
         // NOT
         // ------------------------------
         Expr::Not(e) => {
-            // parser guarantees NOT applies to a single term
-            // engine guarantees correctness & fallback
-            match &**e {
-                Expr::Compare {
-                    op: CmpOp::Eq,
-                    value: Value::Text(word),
-                    ..
-                } => qs.not_word([word.as_str()].to_vec()).into_iter().collect(),
-
-                _ => panic!("NOT only supported on term expressions"),
-            }
+            // every document the inner expression misses, so quoted phrases,
+            // keywords and dates are negated with their own semantics
+            let excluded: HashSet<String> = eval(e, qs, profiles)?.into_iter().collect();
+            qs.all_doc_ids()
+                .into_iter()
+                .filter(|id| !excluded.contains(id))
+                .collect()
         }
-    }
+
+        // ------------------------------
+        // Field presence
+        // ------------------------------
+        Expr::Exists(field) => qs.exists(field),
+        Expr::Missing(field) => qs.missing(field),
+        Expr::Prefix { field, prefix } => qs.prefix(field, prefix),
+        Expr::Wildcard { field, pattern } => qs.wildcard(field, pattern)?,
+        Expr::Regexp { field, pattern } => qs.regexp(field, pattern)?,
+        Expr::Fuzzy {
+            field,
+            term,
+            fuzziness,
+        } => qs.fuzzy(field, term, &FuzzyOptions::new(*fuzziness))?,
+
+        // ------------------------------
+        // Geo
+        // ------------------------------
+        Expr::GeoDistance {
+            field,
+            lat,
+            lon,
+            distance_m,
+        } => qs.geo_distance(field, GeoPoint::new(*lat, *lon), *distance_m),
+        Expr::GeoBoundingBox {
+            field,
+            top_left,
+            bottom_right,
+        } => qs.geo_bounding_box(
+            field,
+            GeoPoint::new(top_left.0, top_left.1),
+            GeoPoint::new(bottom_right.0, bottom_right.1),
+        ),
+
+        // ------------------------------
+        // Nested scoping
+        // ------------------------------
+        Expr::Nested { path, expr } => qs.nested(path, |inner| eval(expr, inner, profiles))?,
+    };
+
+    Ok(ids)
 }
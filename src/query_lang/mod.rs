@@ -1,4 +1,5 @@
 pub mod ast;
 pub mod exec;
 pub mod parser;
+pub mod profile;
 pub mod token;
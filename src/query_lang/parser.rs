@@ -1,5 +1,6 @@
+use crate::index::geo_index::distance_in_meters;
 use crate::query_lang::{
-    ast::{CmpOp, Expr, SortOrder, Value},
+    ast::{CmpOp, Expr, SortMode, SortOrder, Value},
     token::Token,
 };
 
@@ -43,6 +44,35 @@ impl Parser {
         }
     }
 
+    fn keyword_at(&self, pos: usize, keyword: &str) -> bool {
+        match self.tokens.get(pos) {
+            Some(Token::Ident(word)) => word.eq_ignore_ascii_case(keyword),
+            _ => false,
+        }
+    }
+
+    // a keyword that is only one where it stands, e.g. `MAX` after a sort
+    // field or `BETWEEN` after a compared one
+    fn consume_keyword(&mut self, keyword: &str) -> bool {
+        if self.keyword_at(self.pos, keyword) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    // a function name such as `EXISTS`, only when a `(` follows it
+    fn consume_function(&mut self, name: &str) -> bool {
+        let next = self.tokens.get(self.pos + 1);
+        if self.keyword_at(self.pos, name) && next == Some(&Token::LParen) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
     fn expect_ident(&mut self) -> ParseResult<String> {
         match self.peek() {
             Some(Token::Ident(name)) => {
@@ -57,6 +87,46 @@ impl Parser {
         }
     }
 
+    fn expect(&mut self, token: Token, name: &str) -> ParseResult<()> {
+        if self.consume(&token) {
+            Ok(())
+        } else {
+            Err(ParseError::UnexpectedToken {
+                expected: name.into(),
+                found: self.peek().cloned(),
+            })
+        }
+    }
+
+    fn expect_number(&mut self) -> ParseResult<f64> {
+        match self.peek() {
+            Some(Token::Number(n)) => {
+                let n = *n;
+                self.advance();
+                Ok(n)
+            }
+            found => Err(ParseError::UnexpectedToken {
+                expected: "number".into(),
+                found: found.cloned(),
+            }),
+        }
+    }
+
+    // quoted or bare text
+    fn expect_text(&mut self) -> ParseResult<String> {
+        match self.peek() {
+            Some(Token::Str(s)) | Some(Token::Ident(s)) => {
+                let s = s.clone();
+                self.advance();
+                Ok(s)
+            }
+            found => Err(ParseError::UnexpectedToken {
+                expected: "string".into(),
+                found: found.cloned(),
+            }),
+        }
+    }
+
     fn expect_op(&mut self) -> ParseResult<CmpOp> {
         let op = match self.peek() {
             Some(Token::Eq) => CmpOp::Eq,
@@ -82,11 +152,27 @@ impl Parser {
                 self.advance();
                 Ok(v)
             }
+            // bare `true`, `false` and `null` are literals, quoted ones text
             Some(Token::Ident(s)) => {
+                let v = match s.to_ascii_uppercase().as_str() {
+                    "TRUE" => Value::Bool(true),
+                    "FALSE" => Value::Bool(false),
+                    "NULL" => Value::Null,
+                    _ => Value::Text(s.clone()),
+                };
+                self.advance();
+                Ok(v)
+            }
+            Some(Token::Str(s)) => {
                 let v = Value::Text(s.clone());
                 self.advance();
                 Ok(v)
             }
+            Some(Token::Date(s)) => {
+                let v = Value::Date(s.clone());
+                self.advance();
+                Ok(v)
+            }
             found => Err(ParseError::UnexpectedToken {
                 expected: "value".into(),
                 found: found.cloned(),
@@ -146,6 +232,19 @@ impl Parser {
                     });
                 };
 
+                // optional MIN | MAX | AVG | SUM for multi-valued fields
+                let mode = if self.consume_keyword("MIN") {
+                    Some(SortMode::Min)
+                } else if self.consume_keyword("MAX") {
+                    Some(SortMode::Max)
+                } else if self.consume_keyword("AVG") {
+                    Some(SortMode::Avg)
+                } else if self.consume_keyword("SUM") {
+                    Some(SortMode::Sum)
+                } else {
+                    None
+                };
+
                 if !self.consume(&Token::RParen) {
                     return Err(ParseError::UnexpectedToken {
                         expected: ")".into(),
@@ -153,7 +252,7 @@ impl Parser {
                     });
                 }
 
-                fields.push((field, order));
+                fields.push((field, order, mode));
 
                 // optional comma
                 if self.consume(&Token::Comma) {
@@ -191,7 +290,8 @@ impl Parser {
     // or    := and (OR and)*
     // and   := not (AND not)*
     // not   := NOT not | primary
-    // primary := comparison | '(' expr ')'
+    // primary := comparison | '(' expr ')' | EXISTS '(' ident ')' | MISSING '(' ident ')'
+    //          | PREFIX '(' ident ',' string ')'
 
     fn parse_or(&mut self) -> ParseResult<Expr> {
         let mut left = self.parse_and()?;
@@ -224,7 +324,19 @@ impl Parser {
     }
 
     fn parse_primary(&mut self) -> ParseResult<Expr> {
-        if self.consume(&Token::LParen) {
+        if self.consume_function("EXISTS") {
+            Ok(Expr::Exists(self.parse_field_arg()?))
+        } else if self.consume_function("MISSING") {
+            Ok(Expr::Missing(self.parse_field_arg()?))
+        } else if self.consume_function("NESTED") {
+            self.parse_nested()
+        } else if self.consume_function("PREFIX") {
+            self.parse_prefix()
+        } else if self.consume_function("GEO_DISTANCE") {
+            self.parse_geo_distance()
+        } else if self.consume_function("GEO_BBOX") {
+            self.parse_geo_bbox()
+        } else if self.consume(&Token::LParen) {
             let expr = self.parse_or()?;
 
             if !self.consume(&Token::RParen) {
@@ -240,11 +352,187 @@ impl Parser {
         }
     }
 
+    // '(' ident ')'
+    fn parse_field_arg(&mut self) -> ParseResult<String> {
+        if !self.consume(&Token::LParen) {
+            return Err(ParseError::UnexpectedToken {
+                expected: "(".into(),
+                found: self.peek().cloned(),
+            });
+        }
+
+        let field = self.expect_ident()?;
+
+        if !self.consume(&Token::RParen) {
+            return Err(ParseError::UnexpectedToken {
+                expected: ")".into(),
+                found: self.peek().cloned(),
+            });
+        }
+
+        Ok(field)
+    }
+
+    // '(' ident ',' expr ')'
+    fn parse_nested(&mut self) -> ParseResult<Expr> {
+        if !self.consume(&Token::LParen) {
+            return Err(ParseError::UnexpectedToken {
+                expected: "(".into(),
+                found: self.peek().cloned(),
+            });
+        }
+
+        let path = self.expect_ident()?;
+
+        if !self.consume(&Token::Comma) {
+            return Err(ParseError::UnexpectedToken {
+                expected: ",".into(),
+                found: self.peek().cloned(),
+            });
+        }
+
+        let expr = self.parse_or()?;
+
+        if !self.consume(&Token::RParen) {
+            return Err(ParseError::UnexpectedToken {
+                expected: ")".into(),
+                found: self.peek().cloned(),
+            });
+        }
+
+        Ok(Expr::Nested {
+            path,
+            expr: Box::new(expr),
+        })
+    }
+
+    // '(' ident ',' string ')'
+    fn parse_prefix(&mut self) -> ParseResult<Expr> {
+        self.expect(Token::LParen, "(")?;
+        let field = self.expect_ident()?;
+        self.expect(Token::Comma, ",")?;
+        let prefix = self.expect_text()?;
+        self.expect(Token::RParen, ")")?;
+
+        Ok(Expr::Prefix { field, prefix })
+    }
+
+    // '(' ident ',' lat ',' lon ',' distance [unit] ')'
+    fn parse_geo_distance(&mut self) -> ParseResult<Expr> {
+        self.expect(Token::LParen, "(")?;
+        let field = self.expect_ident()?;
+        self.expect(Token::Comma, ",")?;
+        let lat = self.expect_number()?;
+        self.expect(Token::Comma, ",")?;
+        let lon = self.expect_number()?;
+        self.expect(Token::Comma, ",")?;
+        let amount = self.expect_number()?;
+
+        // `10km` lexes as a number followed by the unit
+        let unit = match self.peek() {
+            Some(Token::Ident(unit)) => {
+                let unit = unit.clone();
+                self.advance();
+                unit
+            }
+            _ => String::new(),
+        };
+        let distance_m =
+            distance_in_meters(amount, &unit).ok_or_else(|| ParseError::UnexpectedToken {
+                expected: "distance unit (m, km, mi, yd, ft, nmi)".into(),
+                found: Some(Token::Ident(unit)),
+            })?;
+        self.expect(Token::RParen, ")")?;
+
+        Ok(Expr::GeoDistance {
+            field,
+            lat,
+            lon,
+            distance_m,
+        })
+    }
+
+    // '(' ident ',' top ',' left ',' bottom ',' right ')'
+    fn parse_geo_bbox(&mut self) -> ParseResult<Expr> {
+        self.expect(Token::LParen, "(")?;
+        let field = self.expect_ident()?;
+        let mut coords = [0.0; 4];
+        for coord in coords.iter_mut() {
+            self.expect(Token::Comma, ",")?;
+            *coord = self.expect_number()?;
+        }
+        self.expect(Token::RParen, ")")?;
+
+        Ok(Expr::GeoBoundingBox {
+            field,
+            top_left: (coords[0], coords[1]),
+            bottom_right: (coords[2], coords[3]),
+        })
+    }
+
     fn parse_comparison(&mut self) -> ParseResult<Expr> {
         let field = self.expect_ident()?;
+
+        // field BETWEEN low AND high
+        if self.consume_keyword("BETWEEN") {
+            let low = self.expect_value()?;
+            if !self.consume(&Token::And) {
+                return Err(ParseError::UnexpectedToken {
+                    expected: "AND".into(),
+                    found: self.peek().cloned(),
+                });
+            }
+            let high = self.expect_value()?;
+
+            return Ok(Expr::Between { field, low, high });
+        }
+
         let op = self.expect_op()?;
+
+        // field = pika*, field = /pi.a.*/
+        let pattern = match self.peek() {
+            Some(Token::Wildcard(pattern)) => Some(Expr::Wildcard {
+                field: field.clone(),
+                pattern: pattern.clone(),
+            }),
+            Some(Token::Regex(pattern)) => Some(Expr::Regexp {
+                field: field.clone(),
+                pattern: pattern.clone(),
+            }),
+            _ => None,
+        };
+        if let Some(expr) = pattern {
+            if !matches!(op, CmpOp::Eq) {
+                return Err(ParseError::UnexpectedToken {
+                    expected: "'=' before a pattern".into(),
+                    found: self.peek().cloned(),
+                });
+            }
+            self.advance();
+            return Ok(expr);
+        }
+
         let value = self.expect_value()?;
 
+        // field = term~2
+        if let Some(Token::Tilde(fuzziness)) = self.peek() {
+            let fuzziness = *fuzziness;
+            return match (op, value) {
+                (CmpOp::Eq, Value::Text(term)) => {
+                    self.advance();
+                    Ok(Expr::Fuzzy {
+                        field,
+                        term,
+                        fuzziness,
+                    })
+                }
+                _ => Err(ParseError::UnexpectedToken {
+                    expected: "'=' and a word before '~'".into(),
+                    found: Some(Token::Tilde(fuzziness)),
+                }),
+            };
+        }
+
         Ok(Expr::Compare { field, op, value })
     }
 }
@@ -0,0 +1,115 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::query_lang::ast::{CmpOp, Expr, Value};
+
+/// Where the time of a query went: one node per clause, with the clauses it
+/// was built from as children. Time and postings include the children.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub clause: String,
+    pub time: Duration,
+    /// Documents the clause started from: the whole index for a leaf, what
+    /// its clauses returned otherwise.
+    pub docs_in: usize,
+    pub docs_out: usize,
+    /// Postings and other index entries read, every document a scan such
+    /// as EXISTS, MISSING or NOT goes through included.
+    pub postings_read: usize,
+    pub children: Vec<Profile>,
+}
+
+impl Profile {
+    fn write_tree(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        writeln!(
+            f,
+            "{:indent$}{} [{:.3} ms, docs {} -> {}, postings {}]",
+            "",
+            self.clause,
+            self.time.as_secs_f64() * 1000.0,
+            self.docs_in,
+            self.docs_out,
+            self.postings_read,
+            indent = depth * 2
+        )?;
+        for child in &self.children {
+            child.write_tree(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// One line per clause, children indented under their parent.
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_tree(f, 0)
+    }
+}
+
+/// A short label for `expr` itself, without its sub-clauses.
+pub fn clause_label(expr: &Expr) -> String {
+    match expr {
+        Expr::Compare { field, op, value } => {
+            format!("{} {} {}", field, op_symbol(op), value_label(value))
+        }
+        Expr::And(..) => "AND".to_string(),
+        Expr::Or(..) => "OR".to_string(),
+        Expr::Not(_) => "NOT".to_string(),
+        Expr::Sort { fields, .. } => {
+            let fields: Vec<&str> = fields.iter().map(|(field, ..)| field.as_str()).collect();
+            format!("SORT BY {}", fields.join(", "))
+        }
+        Expr::Count(_) => "COUNT".to_string(),
+        Expr::Between { field, low, high } => format!(
+            "{} BETWEEN {} AND {}",
+            field,
+            value_label(low),
+            value_label(high)
+        ),
+        Expr::Exists(field) => format!("EXISTS({})", field),
+        Expr::Missing(field) => format!("MISSING({})", field),
+        Expr::Wildcard { field, pattern } => format!("{} = {}", field, pattern),
+        Expr::Regexp { field, pattern } => format!("{} = /{}/", field, pattern),
+        Expr::Fuzzy {
+            field,
+            term,
+            fuzziness,
+        } => format!("{} = {}~{}", field, term, fuzziness),
+        Expr::Prefix { field, prefix } => format!("PREFIX({}, {:?})", field, prefix),
+        Expr::GeoDistance {
+            field,
+            lat,
+            lon,
+            distance_m,
+        } => format!("GEO_DISTANCE({}, {}, {}, {}m)", field, lat, lon, distance_m),
+        Expr::GeoBoundingBox {
+            field,
+            top_left,
+            bottom_right,
+        } => format!(
+            "GEO_BBOX({}, {}, {}, {}, {})",
+            field, top_left.0, top_left.1, bottom_right.0, bottom_right.1
+        ),
+        Expr::Nested { path, .. } => format!("NESTED({})", path),
+    }
+}
+
+fn op_symbol(op: &CmpOp) -> &'static str {
+    match op {
+        CmpOp::Eq => "=",
+        CmpOp::Gt => ">",
+        CmpOp::Gte => ">=",
+        CmpOp::Lt => "<",
+        CmpOp::Lte => "<=",
+    }
+}
+
+fn value_label(value: &Value) -> String {
+    match value {
+        Value::Number(n) => n.to_string(),
+        Value::Text(t) => format!("{:?}", t),
+        Value::Date(d) => d.clone(),
+        Value::Bool(b) => b.to_string(),
+        Value::Null => "null".to_string(),
+    }
+}
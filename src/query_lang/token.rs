@@ -1,7 +1,11 @@
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Ident(String),
-    Number(i64),
+    Number(f64),
+    /// Quoted string, quotes removed.
+    Str(String),
+    /// Unquoted date literal such as `2024-01-01` or `2024-01-01T10:00:00Z`.
+    Date(String),
 
     // comparison
     Eq,
@@ -27,4 +31,12 @@ pub enum Token {
     Sort,
     By,
     Comma,
+
+    // term-level
+    /// `~` or `~2` after a value: fuzzy match within that many edits.
+    Tilde(u8),
+    /// Unquoted word with `*` or `?` in it, e.g. `pika*`.
+    Wildcard(String),
+    /// `/.../` regular expression, slashes removed.
+    Regex(String),
 }
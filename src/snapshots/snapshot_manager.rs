@@ -1,25 +1,35 @@
 use serde::{Deserialize, Serialize};
 use serde_json;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
 
-use crate::index::documents_store::DocumentStore;
+use crate::index::bool_index::BoolIndex;
+use crate::index::completion::CompletionIndex;
+use crate::index::documents_store::{DocumentStore, NestedBlock};
 use crate::index::forward_indexer::ForwardIndex;
+use crate::index::geo_index::GeoIndex;
 use crate::index::inverted_index::inverted_index::InvertedIndex;
-use crate::index::n_gram::n_gram_index::NgramIndex;
-use crate::index::n_gram::n_gram_trie::NgramTrie;
+use crate::index::keyword_index::KeywordIndex;
 use crate::index::value_tree::b_tree::ValueTreeIndex;
 use crate::storage::local_store::LocalStore;
 
+/// Every index of a store as of `last_commit_id`. No index has a serde
+/// default: a snapshot written before one existed fails to load, and the
+/// commit log is replayed from the start instead of skipping the commits
+/// that index never saw.
 #[derive(Serialize, Deserialize)]
 pub struct Snapshot {
     pub allow_ngram: bool,
     pub normal_index: InvertedIndex,
-    pub n_gram_index: Option<NgramIndex>,
-    pub n_gram_trie: Option<NgramTrie>,
     pub value_tree: ValueTreeIndex,
     pub forward_index: ForwardIndex,
+    pub keyword_index: KeywordIndex,
+    pub bool_index: BoolIndex,
+    pub geo_index: GeoIndex,
+    pub nested: HashMap<String, NestedBlock>,
+    pub completion: CompletionIndex,
     pub last_commit_id: String,
 }
 
@@ -90,3 +100,41 @@ impl SnapshotManager {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::value::Value;
+    use crate::test_support::doc;
+
+    #[test]
+    fn snapshots_missing_an_index_fail_to_load() {
+        let mut store = DocumentStore::new(None);
+        store.add_document("1", &doc(vec![("active", Value::Bool(true))]), None);
+        let json = serde_json::to_value(store.to_snapshot()).unwrap();
+        assert!(serde_json::from_value::<Snapshot>(json.clone()).is_ok());
+
+        for index in [
+            "keyword_index",
+            "bool_index",
+            "geo_index",
+            "nested",
+            "completion",
+        ] {
+            let mut old = json.clone();
+            old.as_object_mut().unwrap().remove(index);
+            assert!(
+                serde_json::from_value::<Snapshot>(old).is_err(),
+                "{}",
+                index
+            );
+        }
+
+        // forward docs written before a field kind existed fail the same way
+        let mut old = json.clone();
+        let forward = old["forward_index"]["docs"].as_object_mut().unwrap();
+        let doc = forward.values_mut().next().unwrap();
+        doc.as_object_mut().unwrap().remove("bool_fields");
+        assert!(serde_json::from_value::<Snapshot>(old).is_err());
+    }
+}
@@ -0,0 +1,45 @@
+//! Fixtures shared by the unit tests.
+
+use std::collections::HashMap;
+
+use crate::index::documents_store::DocumentStore;
+use crate::index::settings::IndexSettings;
+use crate::index::value::Value;
+
+/// A document made of `fields`, given as (path, value) pairs.
+pub fn doc(fields: Vec<(&str, Value)>) -> HashMap<String, Value> {
+    fields
+        .into_iter()
+        .map(|(field, value)| (field.to_string(), value))
+        .collect()
+}
+
+/// A document holding `text` under `field` only.
+pub fn text_doc(field: &str, text: &str) -> HashMap<String, Value> {
+    doc(vec![(field, Value::Text(text.into()))])
+}
+
+/// A store holding `docs`, each given as its id and its fields.
+pub fn store_with<'a>(
+    settings: IndexSettings,
+    docs: impl IntoIterator<Item = (&'a str, Vec<(&'a str, Value)>)>,
+) -> DocumentStore {
+    let mut store = DocumentStore::with_settings(settings);
+    for (id, fields) in docs {
+        store.add_document(id, &doc(fields), None);
+    }
+    store
+}
+
+/// A store holding one text `field` per document, given as (id, text).
+pub fn text_store<'a>(
+    settings: IndexSettings,
+    field: &str,
+    docs: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> DocumentStore {
+    let mut store = DocumentStore::with_settings(settings);
+    for (id, text) in docs {
+        store.add_document(id, &text_doc(field, text), None);
+    }
+    store
+}
@@ -1,22 +1,236 @@
-pub fn normalize_date(date: &str) -> Option<String> {
-    let date = date.trim().replace('/', "-");
-    let parts: Vec<&str> = date.split('-').collect();
-    if parts.len() != 3 {
-        return None;
-    }
-
-    // Detect format
-    if parts[0].len() == 4 {
-        // Already YYYY-MM-DD
-        let y = parts[0];
-        let m = parts[1];
-        let d = parts[2];
-        return Some(format!("{y}-{m}-{d}"));
+use chrono::{
+    DateTime, Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike,
+    Utc,
+};
+
+// tried after the formats configured in the index settings
+const DATETIME_FORMATS: [&str; 4] = [
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+const ISO_DATE_FORMATS: [&str; 1] = ["%Y-%m-%d"];
+const OTHER_DATE_FORMATS: [&str; 4] = ["%Y/%m/%d", "%d-%m-%Y", "%d/%m/%Y", "%d.%m.%Y"];
+
+/// Parse `input` with one chrono format string. Formats with an offset give
+/// that instant; formats without one are read as UTC, and date-only formats
+/// as midnight UTC.
+fn parse_with_format(input: &str, format: &str) -> Option<i64> {
+    if let Ok(dt) = DateTime::parse_from_str(input, format) {
+        return Some(dt.timestamp_millis());
+    }
+    if let Ok(dt) = NaiveDateTime::parse_from_str(input, format) {
+        return Some(dt.and_utc().timestamp_millis());
+    }
+    NaiveDate::parse_from_str(input, format)
+        .ok()
+        .map(|d| d.and_time(NaiveTime::MIN).and_utc().timestamp_millis())
+}
+
+fn parse_iso(input: &str) -> Option<i64> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(input) {
+        return Some(dt.timestamp_millis());
+    }
+    DATETIME_FORMATS
+        .iter()
+        .chain(ISO_DATE_FORMATS.iter())
+        .find_map(|format| parse_with_format(input, format))
+}
+
+/// Parse a date into epoch milliseconds (UTC).
+///
+/// `formats` (chrono strftime patterns from the index settings) are tried
+/// first, then ISO-8601 with or without offset, a few day-first forms
+/// (`DD-MM-YYYY`, `DD/MM/YYYY`) and finally RFC 2822.
+pub fn parse_date(input: &str, formats: &[String]) -> Option<i64> {
+    let input = input.trim();
+
+    formats
+        .iter()
+        .find_map(|format| parse_with_format(input, format))
+        .or_else(|| parse_iso(input))
+        .or_else(|| {
+            OTHER_DATE_FORMATS
+                .iter()
+                .find_map(|format| parse_with_format(input, format))
+        })
+        .or_else(|| {
+            DateTime::parse_from_rfc2822(input)
+                .ok()
+                .map(|dt| dt.timestamp_millis())
+        })
+}
+
+/// Stricter variant of `parse_date` used to detect dates in strings of
+/// unmapped fields: only the configured formats and ISO-8601 count, so
+/// free text such as `"1/2/3"` stays text.
+pub fn detect_date(input: &str, formats: &[String]) -> Option<i64> {
+    let input = input.trim();
+
+    formats
+        .iter()
+        .find_map(|format| parse_with_format(input, format))
+        .or_else(|| parse_iso(input))
+}
+
+fn to_datetime(millis: i64) -> Option<DateTime<Utc>> {
+    Utc.timestamp_millis_opt(millis).single()
+}
+
+/// RFC 3339 rendering of an epoch-millis date, e.g. `2024-03-01T12:00:00Z`.
+pub fn format_date(millis: i64) -> String {
+    match to_datetime(millis) {
+        Some(dt) => dt.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true),
+        None => millis.to_string(),
+    }
+}
+
+/// Calendar day of an epoch-millis date as `YYYY-MM-DD`.
+pub fn format_day(millis: i64) -> String {
+    match to_datetime(millis) {
+        Some(dt) => dt.format("%Y-%m-%d").to_string(),
+        None => millis.to_string(),
+    }
+}
+
+fn add_unit(dt: DateTime<Utc>, amount: i64, unit: char) -> Option<DateTime<Utc>> {
+    match unit {
+        'y' | 'M' => {
+            let months = if unit == 'y' { amount * 12 } else { amount };
+            let delta = Months::new(u32::try_from(months.unsigned_abs()).ok()?);
+            if months >= 0 {
+                dt.checked_add_months(delta)
+            } else {
+                dt.checked_sub_months(delta)
+            }
+        }
+        'w' => dt.checked_add_signed(Duration::try_weeks(amount)?),
+        'd' => dt.checked_add_signed(Duration::try_days(amount)?),
+        'h' | 'H' => dt.checked_add_signed(Duration::try_hours(amount)?),
+        'm' => dt.checked_add_signed(Duration::try_minutes(amount)?),
+        's' => dt.checked_add_signed(Duration::try_seconds(amount)?),
+        _ => None,
+    }
+}
+
+fn round_down(dt: DateTime<Utc>, unit: char) -> Option<DateTime<Utc>> {
+    let day = dt.date_naive();
+    let start = match unit {
+        'y' => NaiveDate::from_ymd_opt(day.year(), 1, 1)?.and_time(NaiveTime::MIN),
+        'M' => NaiveDate::from_ymd_opt(day.year(), day.month(), 1)?.and_time(NaiveTime::MIN),
+        'w' => (day - Duration::days(day.weekday().num_days_from_monday() as i64))
+            .and_time(NaiveTime::MIN),
+        'd' => day.and_time(NaiveTime::MIN),
+        'h' | 'H' => day.and_hms_opt(dt.hour(), 0, 0)?,
+        'm' => day.and_hms_opt(dt.hour(), dt.minute(), 0)?,
+        's' => day.and_hms_opt(dt.hour(), dt.minute(), dt.second())?,
+        _ => return None,
+    };
+    Some(start.and_utc())
+}
+
+/// Evaluate a date math expression into epoch milliseconds.
+///
+/// The anchor is `now` or a date followed by `||` (`2024-01-01||+1M`), then
+/// any number of `+N<unit>` / `-N<unit>` offsets and `/<unit>` roundings with
+/// units `y M w d h m s`. Rounding goes to the start of the unit, or to its
+/// last millisecond when `round_up` is set, which is what an upper bound like
+/// `lte now/d` expects. A plain date without `||` is parsed as is.
+pub fn parse_date_math(
+    expr: &str,
+    now_millis: i64,
+    round_up: bool,
+    formats: &[String],
+) -> Result<i64, String> {
+    let expr = expr.trim();
+    let invalid = || format!("invalid date expression '{}'", expr);
+
+    let (anchor, mut rest) = if let Some(rest) = expr.strip_prefix("now") {
+        (now_millis, rest)
+    } else if let Some((date, rest)) = expr.split_once("||") {
+        (parse_date(date, formats).ok_or_else(invalid)?, rest)
     } else {
-        // DD-MM-YYYY → convert
-        let d = parts[0];
-        let m = parts[1];
-        let y = parts[2];
-        return Some(format!("{y}-{m}-{d}"));
+        return parse_date(expr, formats).ok_or_else(invalid);
+    };
+
+    let mut dt = to_datetime(anchor).ok_or_else(invalid)?;
+
+    while let Some(op) = rest.chars().next() {
+        rest = &rest[op.len_utf8()..];
+        match op {
+            '+' | '-' => {
+                let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+                let amount: i64 = if digits == 0 {
+                    1
+                } else {
+                    rest[..digits].parse().map_err(|_| invalid())?
+                };
+                rest = &rest[digits..];
+
+                let unit = rest.chars().next().ok_or_else(invalid)?;
+                rest = &rest[unit.len_utf8()..];
+
+                let amount = if op == '-' { -amount } else { amount };
+                dt = add_unit(dt, amount, unit).ok_or_else(invalid)?;
+            }
+            '/' => {
+                let unit = rest.chars().next().ok_or_else(invalid)?;
+                rest = &rest[unit.len_utf8()..];
+
+                let start = round_down(dt, unit).ok_or_else(invalid)?;
+                dt = if round_up {
+                    add_unit(start, 1, unit).ok_or_else(invalid)? - Duration::milliseconds(1)
+                } else {
+                    start
+                };
+            }
+            _ => return Err(invalid()),
+        }
+    }
+
+    Ok(dt.timestamp_millis())
+}
+
+/// Current time in epoch milliseconds, the anchor for `now` in date math.
+pub fn now_millis() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 24 * 60 * 60 * 1000;
+
+    #[test]
+    fn parses_common_formats_and_date_math() {
+        let jan_2 = parse_date("2024-01-02", &[]).unwrap();
+
+        assert_eq!(parse_date("02-01-2024", &[]), Some(jan_2));
+        assert_eq!(parse_date("2024-01-02T00:00:00Z", &[]), Some(jan_2));
+        assert_eq!(parse_date("2024-01-02T02:00:00+02:00", &[]), Some(jan_2));
+        assert_eq!(
+            parse_date("Tue, 02 Jan 2024 00:00:00 +0000", &[]),
+            Some(jan_2)
+        );
+        assert_eq!(parse_date("20240102", &["%Y%m%d".to_string()]), Some(jan_2));
+        assert_eq!(parse_date("2024-13-45", &[]), None);
+        assert_eq!(detect_date("02/01/2024", &[]), None);
+
+        let noon = jan_2 + DAY / 2;
+        assert_eq!(
+            parse_date_math("now-7d/d", noon, false, &[]),
+            Ok(jan_2 - 7 * DAY)
+        );
+        assert_eq!(
+            parse_date_math("now/d", noon, true, &[]),
+            Ok(jan_2 + DAY - 1)
+        );
+        assert_eq!(
+            parse_date_math("2024-01-02||+1M/M", 0, false, &[]),
+            Ok(parse_date("2024-02-01", &[]).unwrap())
+        );
+        assert!(parse_date_math("now-7x", noon, false, &[]).is_err());
     }
 }
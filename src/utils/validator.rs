@@ -1,14 +1,240 @@
 use std::collections::HashMap;
 
+use crate::index::geo_index::GeoPoint;
+use crate::index::settings::{FieldMapping, FieldType, IndexSettings, MappingMode};
 use crate::index::value::Value;
+use crate::utils::date_normalizer::{detect_date, format_date, parse_date};
 
-pub fn validate_document(data: &HashMap<String, Value>) -> Result<(), String> {
-    for (key, val) in data {
+/// Check a document against the index mappings.
+///
+/// In dynamic mode the type of every field seen for the first time is recorded
+/// in `settings.mappings`; in strict mode undeclared fields are rejected.
+/// Values whose type does not match their mapping are coerced in place when
+/// `settings.coerce` is set and rejected otherwise.
+pub fn validate_document(
+    data: &mut HashMap<String, Value>,
+    settings: &mut IndexSettings,
+) -> Result<(), String> {
+    for (key, val) in data.iter() {
         if key.is_empty() {
             return Err("Field name cannot be empty".into());
         }
 
         val.validate()?; // deep recursive validation
     }
+
+    // work on a copy so a rejected document leaves no new mappings behind
+    let mut mappings = settings.mappings.clone();
+    for (key, val) in data.iter_mut() {
+        apply_mapping(key, val, settings, &mut mappings)?;
+    }
+    settings.mappings = mappings;
+
+    Ok(())
+}
+
+/// `None` for null, which fits any mapping and never decides a field's type.
+fn value_type(value: &Value) -> Option<FieldType> {
+    match value {
+        Value::Text(_) => Some(FieldType::Text),
+        Value::Number(_) | Value::Integer(_) => Some(FieldType::Number),
+        Value::Date(_) => Some(FieldType::Date),
+        Value::Bool(_) => Some(FieldType::Boolean),
+        Value::GeoPoint(_) => Some(FieldType::GeoPoint),
+        Value::Null => None,
+        // an array has the type of its elements
+        Value::Array(items) => items.iter().find_map(value_type),
+        Value::Object(_) => Some(FieldType::Object),
+    }
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Text(t) => format!("text \"{}\"", t),
+        Value::Number(n) => format!("number {}", n),
+        Value::Integer(i) => format!("number {}", i),
+        Value::Date(d) => format!("date \"{}\"", format_date(*d)),
+        Value::Bool(b) => format!("boolean {}", b),
+        Value::GeoPoint(p) => format!("geo point {},{}", p.lat, p.lon),
+        Value::Null => "null".into(),
+        Value::Array(_) => "an array".into(),
+        Value::Object(_) => "an object".into(),
+    }
+}
+
+fn apply_mapping(
+    field_path: &str,
+    value: &mut Value,
+    settings: &IndexSettings,
+    mappings: &mut HashMap<String, FieldMapping>,
+) -> Result<(), String> {
+    if let Value::Array(items) = value {
+        for item in items.iter_mut() {
+            apply_mapping(field_path, item, settings, mappings)?;
+        }
+        return Ok(());
+    }
+
+    let Some(mut found) = value_type(value) else {
+        // null fits any mapping, but strict mode still wants the field
+        // declared; it may stand for an object declared by its children
+        if settings.mapping_mode == MappingMode::Strict && !is_declared(field_path, true, mappings)
+        {
+            return Err(undeclared(field_path));
+        }
+        return Ok(());
+    };
+
+    let mapped = mappings.get(field_path).and_then(|m| m.field_type);
+
+    // JSON has no date type: strings and epoch-millis numbers in a date field
+    // are always parsed, whatever the coercion policy
+    if mapped == Some(FieldType::Date) {
+        match value {
+            Value::Text(t) => {
+                let millis = parse_date(t, &settings.date_formats).ok_or_else(|| {
+                    format!(
+                        "field '{}' is mapped as date but \"{}\" is not a valid date",
+                        field_path, t
+                    )
+                })?;
+                *value = Value::Date(millis);
+                return Ok(());
+            }
+            Value::Number(n) if n.fract() == 0.0 => {
+                *value = Value::Date(*n as i64);
+                return Ok(());
+            }
+            Value::Integer(i) => {
+                *value = Value::Date(*i);
+                return Ok(());
+            }
+            _ => {}
+        }
+    }
+
+    // geo points arrive as "lat,lon" strings or {"lat", "lon"} objects
+    if mapped == Some(FieldType::GeoPoint) && found != FieldType::GeoPoint {
+        let point = match value {
+            Value::Text(t) => GeoPoint::parse(t),
+            Value::Object(obj) => match (obj.get("lat"), obj.get("lon")) {
+                (Some(lat), Some(lon)) if obj.len() == 2 => match (lat.as_f64(), lon.as_f64()) {
+                    (Some(lat), Some(lon)) => Some(GeoPoint::new(lat, lon)),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+        .filter(|point| point.validate().is_ok())
+        .ok_or_else(|| {
+            format!(
+                "field '{}' is mapped as geo_point but {} is not a valid point",
+                field_path,
+                describe(value)
+            )
+        })?;
+        *value = Value::GeoPoint(point);
+        return Ok(());
+    }
+
+    match mapped {
+        Some(expected) if !expected.accepts(found) => {
+            if !settings.coerce {
+                return Err(format!(
+                    "field '{}' is mapped as {} but got {}",
+                    field_path,
+                    expected.name(),
+                    describe(value)
+                ));
+            }
+            *value = coerce(value, expected).ok_or_else(|| {
+                format!(
+                    "field '{}' is mapped as {} and {} cannot be converted",
+                    field_path,
+                    expected.name(),
+                    describe(value)
+                )
+            })?;
+        }
+        Some(_) => {}
+        None => {
+            if let Value::Text(t) = value
+                && settings.date_detection
+                && let Some(millis) = detect_date(t, &settings.date_formats)
+            {
+                *value = Value::Date(millis);
+                found = FieldType::Date;
+            }
+
+            let object = found == FieldType::Object;
+            if settings.mapping_mode == MappingMode::Strict
+                && !is_declared(field_path, object, mappings)
+            {
+                return Err(undeclared(field_path));
+            }
+            mappings
+                .entry(field_path.to_string())
+                .or_default()
+                .field_type = Some(found);
+        }
+    }
+
+    if let Value::Object(obj) = value {
+        for (key, child) in obj.iter_mut() {
+            apply_mapping(
+                &format!("{}.{}", field_path, key),
+                child,
+                settings,
+                mappings,
+            )?;
+        }
+    }
+
     Ok(())
 }
+
+// Whether `field_path` has a mapping. An object is implicitly declared by
+// declaring one of its children.
+fn is_declared(field_path: &str, object: bool, mappings: &HashMap<String, FieldMapping>) -> bool {
+    mappings.contains_key(field_path)
+        || object
+            && mappings
+                .keys()
+                .any(|k| k.starts_with(&format!("{}.", field_path)))
+}
+
+fn undeclared(field_path: &str) -> String {
+    format!(
+        "field '{}' is not declared in the strict mapping",
+        field_path
+    )
+}
+
+fn coerce(value: &Value, target: FieldType) -> Option<Value> {
+    match (value, target) {
+        (Value::Text(t), FieldType::Number) => match t.trim().parse::<i64>() {
+            Ok(i) => Some(Value::Integer(i)),
+            // "NaN" and "inf" parse as floats but are not numbers to index
+            Err(_) => (t.trim().parse::<f64>().ok())
+                .filter(|n| n.is_finite())
+                .map(Value::Number),
+        },
+        (Value::Number(n), FieldType::Text | FieldType::Keyword) => {
+            Some(Value::Text(n.to_string()))
+        }
+        (Value::Integer(i), FieldType::Text | FieldType::Keyword) => {
+            Some(Value::Text(i.to_string()))
+        }
+        (Value::Date(d), FieldType::Text | FieldType::Keyword) => {
+            Some(Value::Text(format_date(*d)))
+        }
+        (Value::Bool(b), FieldType::Text | FieldType::Keyword) => Some(Value::Text(b.to_string())),
+        (Value::Text(t), FieldType::Boolean) => match t.trim().to_lowercase().as_str() {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        _ => None,
+    }
+}